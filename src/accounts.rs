@@ -0,0 +1,52 @@
+use aws_config::SdkConfig;
+use eyre::{Result, WrapErr};
+
+/// Name to show for the account that `config` has credentials for: its alias,
+/// or its id if it has none or the alias cannot be read
+pub(crate) async fn account_label(config: &SdkConfig) -> Result<String> {
+    match account_alias(config).await {
+        Ok(Some(alias)) => return Ok(alias),
+        Ok(None) => {}
+        Err(e) => tracing::debug!(err = %e, "listing account aliases"),
+    }
+    account_id(config).await
+}
+
+async fn account_id(config: &SdkConfig) -> Result<String> {
+    let res = aws_sdk_sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await
+        .wrap_err("getting caller identity")?;
+    res.account()
+        .map(String::from)
+        .ok_or_else(|| eyre::eyre!("caller identity has no account"))
+}
+
+/// Partition of the account that `config` has credentials for, e.g. `aws` or
+/// `aws-cn`, taken from the arn of the caller identity
+pub(crate) async fn partition(config: &SdkConfig) -> Result<String> {
+    let res = aws_sdk_sts::Client::new(config)
+        .get_caller_identity()
+        .send()
+        .await
+        .wrap_err("getting caller identity")?;
+    res.arn()
+        .and_then(|arn| arn.split(':').nth(1))
+        .map(String::from)
+        .ok_or_else(|| eyre::eyre!("caller identity has no arn"))
+}
+
+// the alias of the account, of which there is at most one
+async fn account_alias(config: &SdkConfig) -> Result<Option<String>> {
+    // `--endpoint-url` points at CloudFormation, so IAM uses its default
+    // endpoint for the region
+    let mut iam_config = aws_sdk_iam::config::Builder::from(config);
+    iam_config.set_endpoint_url(None);
+    let res = aws_sdk_iam::Client::from_conf(iam_config.build())
+        .list_account_aliases()
+        .send()
+        .await
+        .wrap_err("listing account aliases")?;
+    Ok(res.account_aliases().first().cloned())
+}
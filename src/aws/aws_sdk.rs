@@ -2,15 +2,46 @@ use super::AwsCloudFormationClient;
 
 use aws_sdk_cloudformation::config::http::HttpResponse;
 use aws_sdk_cloudformation::error::SdkError;
+use aws_sdk_cloudformation::operation::cancel_update_stack::{
+    CancelUpdateStackError, CancelUpdateStackInput, CancelUpdateStackOutput,
+};
+use aws_sdk_cloudformation::operation::continue_update_rollback::{
+    ContinueUpdateRollbackError, ContinueUpdateRollbackInput, ContinueUpdateRollbackOutput,
+};
+use aws_sdk_cloudformation::operation::create_change_set::{
+    CreateChangeSetError, CreateChangeSetInput, CreateChangeSetOutput,
+};
+use aws_sdk_cloudformation::operation::describe_change_set::{
+    DescribeChangeSetError, DescribeChangeSetInput, DescribeChangeSetOutput,
+};
 use aws_sdk_cloudformation::operation::describe_stack_events::{
     DescribeStackEventsError, DescribeStackEventsInput, DescribeStackEventsOutput,
 };
 use aws_sdk_cloudformation::operation::describe_stack_resources::{
     DescribeStackResourcesError, DescribeStackResourcesInput, DescribeStackResourcesOutput,
 };
+use aws_sdk_cloudformation::operation::describe_stack_set_operation::{
+    DescribeStackSetOperationError, DescribeStackSetOperationInput, DescribeStackSetOperationOutput,
+};
 use aws_sdk_cloudformation::operation::describe_stacks::{
     DescribeStacksError, DescribeStacksInput, DescribeStacksOutput,
 };
+use aws_sdk_cloudformation::operation::execute_change_set::{
+    ExecuteChangeSetError, ExecuteChangeSetInput, ExecuteChangeSetOutput,
+};
+use aws_sdk_cloudformation::operation::get_template::{
+    GetTemplateError, GetTemplateInput, GetTemplateOutput,
+};
+use aws_sdk_cloudformation::operation::list_stack_instances::{
+    ListStackInstancesError, ListStackInstancesInput, ListStackInstancesOutput,
+};
+use aws_sdk_cloudformation::operation::list_stack_set_operation_results::{
+    ListStackSetOperationResultsError, ListStackSetOperationResultsInput,
+    ListStackSetOperationResultsOutput,
+};
+use aws_sdk_cloudformation::operation::list_stack_set_operations::{
+    ListStackSetOperationsError, ListStackSetOperationsInput, ListStackSetOperationsOutput,
+};
 use aws_sdk_cloudformation::Client;
 use backoff::ExponentialBackoff;
 
@@ -31,6 +62,12 @@ macro_rules! send_request_with_retry {
     }};
 }
 
+// token that makes it safe to retry a request that changes a stack, e.g.
+// after it timed out, as CloudFormation ignores repeats of the same request
+fn idempotency_token(token: Option<String>) -> Option<String> {
+    token.or_else(|| Some(format!("cftail-{}", uuid::Uuid::new_v4())))
+}
+
 #[async_trait::async_trait]
 impl AwsCloudFormationClient for Client {
     async fn describe_stacks(
@@ -59,4 +96,129 @@ impl AwsCloudFormationClient for Client {
         let builder = Client::describe_stack_resources(self).stack_name(input.stack_name.unwrap());
         send_request_with_retry!(builder)
     }
+
+    async fn describe_stack_set_operation(
+        &self,
+        input: DescribeStackSetOperationInput,
+    ) -> Result<
+        DescribeStackSetOperationOutput,
+        SdkError<DescribeStackSetOperationError, HttpResponse>,
+    > {
+        let builder = Client::describe_stack_set_operation(self)
+            .set_stack_set_name(input.stack_set_name)
+            .set_operation_id(input.operation_id)
+            .set_call_as(input.call_as);
+        send_request_with_retry!(builder)
+    }
+
+    async fn list_stack_set_operations(
+        &self,
+        input: ListStackSetOperationsInput,
+    ) -> Result<ListStackSetOperationsOutput, SdkError<ListStackSetOperationsError, HttpResponse>>
+    {
+        let builder = Client::list_stack_set_operations(self)
+            .set_stack_set_name(input.stack_set_name)
+            .set_next_token(input.next_token)
+            .set_max_results(input.max_results)
+            .set_call_as(input.call_as);
+        send_request_with_retry!(builder)
+    }
+
+    async fn list_stack_set_operation_results(
+        &self,
+        input: ListStackSetOperationResultsInput,
+    ) -> Result<
+        ListStackSetOperationResultsOutput,
+        SdkError<ListStackSetOperationResultsError, HttpResponse>,
+    > {
+        let builder = Client::list_stack_set_operation_results(self)
+            .set_stack_set_name(input.stack_set_name)
+            .set_operation_id(input.operation_id)
+            .set_next_token(input.next_token)
+            .set_call_as(input.call_as);
+        send_request_with_retry!(builder)
+    }
+
+    async fn list_stack_instances(
+        &self,
+        input: ListStackInstancesInput,
+    ) -> Result<ListStackInstancesOutput, SdkError<ListStackInstancesError, HttpResponse>> {
+        let builder = Client::list_stack_instances(self)
+            .set_stack_set_name(input.stack_set_name)
+            .set_next_token(input.next_token)
+            .set_call_as(input.call_as);
+        send_request_with_retry!(builder)
+    }
+
+    async fn describe_change_set(
+        &self,
+        input: DescribeChangeSetInput,
+    ) -> Result<DescribeChangeSetOutput, SdkError<DescribeChangeSetError, HttpResponse>> {
+        let builder = Client::describe_change_set(self)
+            .set_change_set_name(input.change_set_name)
+            .set_stack_name(input.stack_name)
+            .set_next_token(input.next_token);
+        send_request_with_retry!(builder)
+    }
+
+    async fn create_change_set(
+        &self,
+        input: CreateChangeSetInput,
+    ) -> Result<CreateChangeSetOutput, SdkError<CreateChangeSetError, HttpResponse>> {
+        let builder = Client::create_change_set(self)
+            .set_stack_name(input.stack_name)
+            .set_change_set_name(input.change_set_name)
+            .set_change_set_type(input.change_set_type)
+            .set_template_body(input.template_body)
+            .set_parameters(input.parameters)
+            .set_capabilities(input.capabilities)
+            .set_tags(input.tags)
+            .set_client_token(idempotency_token(input.client_token));
+        send_request_with_retry!(builder)
+    }
+
+    async fn execute_change_set(
+        &self,
+        input: ExecuteChangeSetInput,
+    ) -> Result<ExecuteChangeSetOutput, SdkError<ExecuteChangeSetError, HttpResponse>> {
+        let builder = Client::execute_change_set(self)
+            .set_change_set_name(input.change_set_name)
+            .set_stack_name(input.stack_name)
+            .set_client_request_token(idempotency_token(input.client_request_token));
+        send_request_with_retry!(builder)
+    }
+
+    async fn get_template(
+        &self,
+        input: GetTemplateInput,
+    ) -> Result<GetTemplateOutput, SdkError<GetTemplateError, HttpResponse>> {
+        let builder = Client::get_template(self)
+            .set_stack_name(input.stack_name)
+            .set_change_set_name(input.change_set_name)
+            .set_template_stage(input.template_stage);
+        send_request_with_retry!(builder)
+    }
+
+    async fn cancel_update_stack(
+        &self,
+        input: CancelUpdateStackInput,
+    ) -> Result<CancelUpdateStackOutput, SdkError<CancelUpdateStackError, HttpResponse>> {
+        let builder = Client::cancel_update_stack(self)
+            .set_stack_name(input.stack_name)
+            .set_client_request_token(idempotency_token(input.client_request_token));
+        send_request_with_retry!(builder)
+    }
+
+    async fn continue_update_rollback(
+        &self,
+        input: ContinueUpdateRollbackInput,
+    ) -> Result<ContinueUpdateRollbackOutput, SdkError<ContinueUpdateRollbackError, HttpResponse>>
+    {
+        let builder = Client::continue_update_rollback(self)
+            .set_stack_name(input.stack_name)
+            .set_role_arn(input.role_arn)
+            .set_resources_to_skip(input.resources_to_skip)
+            .set_client_request_token(idempotency_token(input.client_request_token));
+        send_request_with_retry!(builder)
+    }
 }
@@ -2,18 +2,85 @@ use aws_sdk_cloudformation::{
     config::http::HttpResponse,
     error::SdkError,
     operation::{
+        cancel_update_stack::{
+            CancelUpdateStackError, CancelUpdateStackInput, CancelUpdateStackOutput,
+        },
+        continue_update_rollback::{
+            ContinueUpdateRollbackError, ContinueUpdateRollbackInput, ContinueUpdateRollbackOutput,
+        },
+        create_change_set::{CreateChangeSetError, CreateChangeSetInput, CreateChangeSetOutput},
+        describe_change_set::{
+            DescribeChangeSetError, DescribeChangeSetInput, DescribeChangeSetOutput,
+        },
         describe_stack_events::{
             DescribeStackEventsError, DescribeStackEventsInput, DescribeStackEventsOutput,
         },
         describe_stack_resources::{
             DescribeStackResourcesError, DescribeStackResourcesInput, DescribeStackResourcesOutput,
         },
+        describe_stack_set_operation::{
+            DescribeStackSetOperationError, DescribeStackSetOperationInput,
+            DescribeStackSetOperationOutput,
+        },
         describe_stacks::{DescribeStacksError, DescribeStacksInput, DescribeStacksOutput},
+        execute_change_set::{
+            ExecuteChangeSetError, ExecuteChangeSetInput, ExecuteChangeSetOutput,
+        },
+        get_template::{GetTemplateError, GetTemplateInput, GetTemplateOutput},
+        list_stack_instances::{
+            ListStackInstancesError, ListStackInstancesInput, ListStackInstancesOutput,
+        },
+        list_stack_set_operation_results::{
+            ListStackSetOperationResultsError, ListStackSetOperationResultsInput,
+            ListStackSetOperationResultsOutput,
+        },
+        list_stack_set_operations::{
+            ListStackSetOperationsError, ListStackSetOperationsInput, ListStackSetOperationsOutput,
+        },
     },
 };
+use eyre::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::stacks::Location;
 
 mod aws_sdk;
 
+pub(crate) type Fetcher = Arc<dyn AwsCloudFormationClient + Sync + Send>;
+
+/// Clients for each location we are tailing stacks in
+#[derive(Clone, Default)]
+pub(crate) struct Clients {
+    clients: HashMap<Location, Fetcher>,
+    // alias or id of the account of each location
+    labels: HashMap<Location, String>,
+}
+
+impl Clients {
+    pub(crate) fn insert(&mut self, location: Location, client: Fetcher) {
+        self.clients.insert(location, client);
+    }
+
+    pub(crate) fn set_label(&mut self, location: Location, label: String) {
+        self.labels.insert(location, label);
+    }
+
+    /// Label of the account to show next to events, if given explicitly
+    pub(crate) fn account_label<'a>(&'a self, location: &'a Location) -> Option<&'a str> {
+        self.labels
+            .get(location)
+            .map(String::as_str)
+            .or_else(|| location.account_label())
+    }
+
+    pub(crate) fn get(&self, location: &Location) -> Result<&Fetcher> {
+        self.clients
+            .get(location)
+            .ok_or_else(|| eyre::eyre!("no client configured for location {:?}", location))
+    }
+}
+
 /// Trait representing interactions with CloudFormation
 #[async_trait::async_trait]
 pub(crate) trait AwsCloudFormationClient {
@@ -31,4 +98,60 @@ pub(crate) trait AwsCloudFormationClient {
         &self,
         input: DescribeStackResourcesInput,
     ) -> Result<DescribeStackResourcesOutput, SdkError<DescribeStackResourcesError, HttpResponse>>;
+
+    async fn describe_stack_set_operation(
+        &self,
+        input: DescribeStackSetOperationInput,
+    ) -> Result<
+        DescribeStackSetOperationOutput,
+        SdkError<DescribeStackSetOperationError, HttpResponse>,
+    >;
+
+    async fn list_stack_set_operations(
+        &self,
+        input: ListStackSetOperationsInput,
+    ) -> Result<ListStackSetOperationsOutput, SdkError<ListStackSetOperationsError, HttpResponse>>;
+
+    async fn list_stack_set_operation_results(
+        &self,
+        input: ListStackSetOperationResultsInput,
+    ) -> Result<
+        ListStackSetOperationResultsOutput,
+        SdkError<ListStackSetOperationResultsError, HttpResponse>,
+    >;
+
+    async fn list_stack_instances(
+        &self,
+        input: ListStackInstancesInput,
+    ) -> Result<ListStackInstancesOutput, SdkError<ListStackInstancesError, HttpResponse>>;
+
+    async fn describe_change_set(
+        &self,
+        input: DescribeChangeSetInput,
+    ) -> Result<DescribeChangeSetOutput, SdkError<DescribeChangeSetError, HttpResponse>>;
+
+    async fn create_change_set(
+        &self,
+        input: CreateChangeSetInput,
+    ) -> Result<CreateChangeSetOutput, SdkError<CreateChangeSetError, HttpResponse>>;
+
+    async fn execute_change_set(
+        &self,
+        input: ExecuteChangeSetInput,
+    ) -> Result<ExecuteChangeSetOutput, SdkError<ExecuteChangeSetError, HttpResponse>>;
+
+    async fn get_template(
+        &self,
+        input: GetTemplateInput,
+    ) -> Result<GetTemplateOutput, SdkError<GetTemplateError, HttpResponse>>;
+
+    async fn cancel_update_stack(
+        &self,
+        input: CancelUpdateStackInput,
+    ) -> Result<CancelUpdateStackOutput, SdkError<CancelUpdateStackError, HttpResponse>>;
+
+    async fn continue_update_rollback(
+        &self,
+        input: ContinueUpdateRollbackInput,
+    ) -> Result<ContinueUpdateRollbackOutput, SdkError<ContinueUpdateRollbackError, HttpResponse>>;
 }
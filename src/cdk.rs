@@ -0,0 +1,230 @@
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use termcolor::WriteColor;
+
+use crate::stacks::{Location, StackTarget};
+
+const STACK_ARTIFACT: &str = "aws:cloudformation:stack";
+const NESTED_ASSEMBLY_ARTIFACT: &str = "cdk:cloud-assembly";
+
+// `manifest.json` of a cloud assembly, only including what we need
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    artifacts: BTreeMap<String, Artifact>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Artifact {
+    #[serde(rename = "type")]
+    type_: String,
+    environment: Option<String>,
+    #[serde(default)]
+    properties: ArtifactProperties,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactProperties {
+    stack_name: Option<String>,
+    template_file: Option<String>,
+    directory_name: Option<String>,
+}
+
+/// Stack synthesized by the CDK
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CdkStack {
+    /// Artifact id, which other stacks refer to in their dependencies
+    pub(crate) id: String,
+    pub(crate) target: StackTarget,
+    pub(crate) account: Option<String>,
+    pub(crate) template_file: Option<PathBuf>,
+    /// Ids of the stacks that have to be deployed first
+    pub(crate) dependencies: Vec<String>,
+}
+
+// account and region from an environment like `aws://123456789012/eu-west-1`,
+// which are `unknown-account` and `unknown-region` for environment agnostic stacks
+fn parse_environment(environment: &str) -> (Option<String>, Option<String>) {
+    let mut parts = environment.trim_start_matches("aws://").splitn(2, '/');
+    let account = parts
+        .next()
+        .filter(|a| !a.is_empty() && *a != "unknown-account")
+        .map(String::from);
+    let region = parts
+        .next()
+        .filter(|r| !r.is_empty() && *r != "unknown-region")
+        .map(String::from);
+    (account, region)
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest> {
+    let path = dir.join("manifest.json");
+    let contents = std::fs::read_to_string(&path)
+        .wrap_err_with(|| format!("reading cloud assembly manifest {}", path.display()))?;
+    serde_json::from_str(&contents)
+        .wrap_err_with(|| format!("parsing cloud assembly manifest {}", path.display()))
+}
+
+// stacks in the assembly at `dir`, including those of nested assemblies (stages)
+fn collect_stacks(dir: &Path, stacks: &mut Vec<CdkStack>) -> Result<()> {
+    let manifest = read_manifest(dir)?;
+    for (id, artifact) in manifest.artifacts {
+        match artifact.type_.as_str() {
+            STACK_ARTIFACT => {
+                let (account, region) = artifact
+                    .environment
+                    .as_deref()
+                    .map(parse_environment)
+                    .unwrap_or_default();
+                let name = artifact.properties.stack_name.unwrap_or_else(|| id.clone());
+                let location = Location {
+                    credentials: None,
+                    region,
+                };
+                stacks.push(CdkStack {
+                    target: StackTarget::new(location, name),
+                    account,
+                    template_file: artifact.properties.template_file.map(|f| dir.join(f)),
+                    dependencies: artifact.dependencies,
+                    id,
+                });
+            }
+            NESTED_ASSEMBLY_ARTIFACT => {
+                if let Some(directory) = artifact.properties.directory_name {
+                    collect_stacks(&dir.join(directory), stacks)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// order the stacks so that each comes after the stacks it depends on,
+// otherwise keeping them sorted by id
+fn dependency_order(stacks: Vec<CdkStack>) -> Result<Vec<CdkStack>> {
+    let ids: BTreeSet<String> = stacks.iter().map(|s| s.id.clone()).collect();
+    let mut remaining: BTreeMap<String, CdkStack> = stacks
+        .into_iter()
+        .map(|mut stack| {
+            // dependencies also include asset manifests, which are not deployed as stacks
+            stack.dependencies.retain(|d| ids.contains(d));
+            (stack.id.clone(), stack)
+        })
+        .collect();
+
+    let mut ordered: Vec<CdkStack> = Vec::new();
+    while !remaining.is_empty() {
+        let ready: Vec<String> = remaining
+            .values()
+            .filter(|stack| {
+                stack
+                    .dependencies
+                    .iter()
+                    .all(|d| ordered.iter().any(|o| &o.id == d))
+            })
+            .map(|stack| stack.id.clone())
+            .collect();
+        if ready.is_empty() {
+            let ids: Vec<_> = remaining.keys().cloned().collect();
+            eyre::bail!("stacks have circular dependencies: {}", ids.join(", "));
+        }
+        for id in ready {
+            ordered.extend(remaining.remove(&id));
+        }
+    }
+    Ok(ordered)
+}
+
+/// Load the stacks from a CDK cloud assembly directory, usually `cdk.out`, in
+/// the order that they are deployed
+pub(crate) fn load_stacks(dir: &Path) -> Result<Vec<CdkStack>> {
+    let mut stacks = Vec::new();
+    collect_stacks(dir, &mut stacks)?;
+    dependency_order(stacks)
+}
+
+/// Print the stacks in the order they will be deployed
+pub(crate) fn print_stacks(writer: &mut impl WriteColor, stacks: &[CdkStack]) -> Result<()> {
+    writeln!(writer, "Stacks in deployment order:").wrap_err("printing stacks header")?;
+    for (i, stack) in stacks.iter().enumerate() {
+        write!(writer, "  {}. {}", i + 1, stack.target).wrap_err("printing stack")?;
+        if let Some(account) = &stack.account {
+            write!(writer, " in account {}", account).wrap_err("printing stack account")?;
+        }
+        if !stack.dependencies.is_empty() {
+            write!(writer, " (after {})", stack.dependencies.join(", "))
+                .wrap_err("printing stack dependencies")?;
+        }
+        writeln!(writer).wrap_err("printing stack")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_stacks() {
+        let dir = std::env::temp_dir().join(format!("cftail-cdk-test-{}", std::process::id()));
+        let stage_dir = dir.join("assembly-Prod");
+        std::fs::create_dir_all(&stage_dir).unwrap();
+        std::fs::write(
+            dir.join("manifest.json"),
+            r#"{
+                "version": "36.0.0",
+                "artifacts": {
+                    "App": {
+                        "type": "aws:cloudformation:stack",
+                        "environment": "aws://123456789012/eu-west-1",
+                        "properties": {"templateFile": "App.template.json", "stackName": "my-app"},
+                        "dependencies": ["Network", "App.assets"]
+                    },
+                    "App.assets": {"type": "cdk:asset-manifest", "properties": {"file": "App.assets.json"}},
+                    "Network": {
+                        "type": "aws:cloudformation:stack",
+                        "environment": "aws://unknown-account/unknown-region",
+                        "properties": {"templateFile": "Network.template.json"}
+                    },
+                    "Tree": {"type": "cdk:tree", "properties": {"file": "tree.json"}},
+                    "assembly-Prod": {
+                        "type": "cdk:cloud-assembly",
+                        "properties": {"directoryName": "assembly-Prod"}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            stage_dir.join("manifest.json"),
+            r#"{
+                "artifacts": {
+                    "ProdDatabase": {
+                        "type": "aws:cloudformation:stack",
+                        "environment": "aws://123456789012/us-east-1",
+                        "properties": {"templateFile": "ProdDatabase.template.json", "stackName": "Prod-Database"}
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let stacks = load_stacks(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<String> = stacks.iter().map(|s| s.target.to_string()).collect();
+        assert_eq!(
+            names,
+            vec!["Network", "us-east-1:Prod-Database", "eu-west-1:my-app"]
+        );
+        assert_eq!(stacks[2].dependencies, vec!["Network"]);
+        assert_eq!(stacks[2].account.as_deref(), Some("123456789012"));
+        assert_eq!(stacks[2].template_file, Some(dir.join("App.template.json")));
+    }
+}
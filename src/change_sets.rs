@@ -0,0 +1,351 @@
+use aws_sdk_cloudformation::operation::describe_change_set::{
+    DescribeChangeSetInput, DescribeChangeSetOutput,
+};
+use aws_sdk_cloudformation::operation::describe_stacks::DescribeStacksInput;
+use aws_sdk_cloudformation::types::{
+    ChangeSetStatus, ExecutionStatus, ResourceChange, Stack, StackStatus,
+};
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+use std::time::Duration;
+use term_table::{row::Row, Table, TableStyle};
+use termcolor::WriteColor;
+use tokio::time::sleep;
+
+use crate::aws::Fetcher;
+
+/// What happened to a change set once it stopped waiting to be executed
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ChangeSetOutcome {
+    /// The change set is being executed, producing events on `stack_name` after `since`
+    Executing {
+        stack_name: String,
+        since: DateTime<Utc>,
+    },
+    /// The change set was not created because there is nothing to change
+    NoChanges(String),
+    /// The change set could not be created or executed
+    Failed(String),
+    /// The change set was not executed because the deployment was not confirmed
+    Declined,
+}
+
+// how many times to look for a change set that has gone, without the stack
+// having been updated, before giving up
+const NOT_FOUND_RETRIES: usize = 3;
+
+// CloudFormation reports empty change sets as failures, with one of these reasons
+fn is_no_changes_reason(reason: &str) -> bool {
+    reason.contains("didn't contain changes") || reason.contains("No updates are to be performed")
+}
+
+/// A change set, identified by its arn or by name along with its stack
+pub(crate) struct ChangeSet {
+    client: Fetcher,
+    change_set_name: String,
+    stack_name: Option<String>,
+}
+
+impl ChangeSet {
+    pub(crate) fn new(
+        client: Fetcher,
+        change_set_name: impl Into<String>,
+        stack_name: Option<String>,
+    ) -> Self {
+        Self {
+            client,
+            change_set_name: change_set_name.into(),
+            stack_name,
+        }
+    }
+
+    fn describe_input(&self, next_token: Option<String>) -> DescribeChangeSetInput {
+        DescribeChangeSetInput::builder()
+            .change_set_name(&self.change_set_name)
+            .set_stack_name(self.stack_name.clone())
+            .set_next_token(next_token)
+            .build()
+            .expect("building describe change set input")
+    }
+
+    /// Wait for CloudFormation to finish computing the change set
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn wait_until_created(&self) -> Result<DescribeChangeSetOutput> {
+        loop {
+            let description = self
+                .client
+                .describe_change_set(self.describe_input(None))
+                .await
+                .wrap_err_with(|| format!("describing change set {}", self.change_set_name))?;
+            match description.status() {
+                Some(ChangeSetStatus::CreatePending) | Some(ChangeSetStatus::CreateInProgress) => {
+                    tracing::debug!("waiting for change set to be created");
+                    sleep(Duration::from_secs(2)).await;
+                }
+                _ => return Ok(description),
+            }
+        }
+    }
+
+    /// Check whether the change set can never be executed
+    pub(crate) fn failure(description: &DescribeChangeSetOutput) -> Option<ChangeSetOutcome> {
+        let reason = description
+            .status_reason()
+            .unwrap_or("no reason given")
+            .to_string();
+        match (description.status(), description.execution_status()) {
+            (Some(ChangeSetStatus::Failed), _) if is_no_changes_reason(&reason) => {
+                Some(ChangeSetOutcome::NoChanges(reason))
+            }
+            (Some(ChangeSetStatus::Failed), _) => Some(ChangeSetOutcome::Failed(reason)),
+            (_, Some(ExecutionStatus::Obsolete)) => Some(ChangeSetOutcome::Failed(
+                "change set is obsolete, the stack has been updated since it was created"
+                    .to_string(),
+            )),
+            _ => None,
+        }
+    }
+
+    async fn changes(&self) -> Result<Vec<ResourceChange>> {
+        let mut changes = Vec::new();
+        let mut next_token = None;
+        loop {
+            let res = self
+                .client
+                .describe_change_set(self.describe_input(next_token))
+                .await
+                .wrap_err("describing change set")?;
+            changes.extend(
+                res.changes()
+                    .iter()
+                    .filter_map(|c| c.resource_change().cloned()),
+            );
+            match res.next_token {
+                Some(token) => next_token = Some(token),
+                None => break,
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Print each resource that the change set will change
+    #[tracing::instrument(skip(self, writer))]
+    pub(crate) async fn print_changes(&self, writer: &mut impl WriteColor) -> Result<()> {
+        let changes = self.changes().await?;
+        writeln!(writer, "\nChanges:").wrap_err("printing changes header")?;
+
+        let mut table = Table::new();
+        table.style = TableStyle::thin();
+        table.add_row(Row::new(vec![
+            "Action",
+            "Logical ID",
+            "Type",
+            "Replacement",
+            "Scope",
+        ]));
+        for change in &changes {
+            let scope: Vec<_> = change.scope().iter().map(|s| s.as_str()).collect();
+            table.add_row(Row::new(vec![
+                change.action().map(|a| a.as_str()).unwrap_or("-"),
+                change.logical_resource_id().unwrap_or("-"),
+                change.resource_type().unwrap_or("-"),
+                change.replacement().map(|r| r.as_str()).unwrap_or("-"),
+                &scope.join(", "),
+            ]));
+        }
+        writeln!(writer, "{}", table.render()).wrap_err("printing changes")?;
+        Ok(())
+    }
+
+    /// The outcome once the change set has started executing
+    pub(crate) fn executing(description: &DescribeChangeSetOutput) -> Result<ChangeSetOutcome> {
+        let stack_name = description
+            .stack_name()
+            .ok_or_else(|| eyre::eyre!("change set has no stack name"))?
+            .to_string();
+        let since = description
+            .creation_time()
+            .ok_or_else(|| eyre::eyre!("change set has no creation time"))?
+            .to_chrono_utc()
+            .wrap_err("converting change set creation time")?;
+        Ok(ChangeSetOutcome::Executing { stack_name, since })
+    }
+
+    /// Wait for the change set to start executing
+    #[tracing::instrument(skip(self, writer, description))]
+    pub(crate) async fn wait_for_execution(
+        &self,
+        writer: &mut impl WriteColor,
+        description: &DescribeChangeSetOutput,
+    ) -> Result<ChangeSetOutcome> {
+        let executing = Self::executing(description)?;
+        let stack_name = description.stack_name().unwrap_or_default();
+
+        let mut waiting = false;
+        let mut not_found = 0;
+        loop {
+            let input = self.describe_input(None);
+            let description = match self.client.describe_change_set(input).await {
+                Ok(description) => description,
+                // change sets are removed once they have been executed, but
+                // also when they are deleted instead
+                Err(e) if e.code() == Some("ChangeSetNotFound") => {
+                    if self.stack_updated(&executing).await? {
+                        return Ok(executing);
+                    }
+                    not_found += 1;
+                    if not_found > NOT_FOUND_RETRIES {
+                        eyre::bail!(
+                            "change set {} was deleted without being executed",
+                            self.change_set_name
+                        );
+                    }
+                    tracing::debug!(%not_found, "change set not found, checking again");
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+                Err(e) => return Err(e).wrap_err("describing change set"),
+            };
+
+            if let Some(outcome) = Self::failure(&description) {
+                return Ok(outcome);
+            }
+
+            match description.execution_status() {
+                Some(ExecutionStatus::ExecuteInProgress)
+                | Some(ExecutionStatus::ExecuteComplete)
+                | Some(ExecutionStatus::ExecuteFailed) => return Ok(executing),
+                _ => {
+                    if !waiting {
+                        writeln!(
+                            writer,
+                            "Waiting for change set {} to be executed on {}",
+                            self.change_set_name, stack_name
+                        )
+                        .wrap_err("printing waiting message")?;
+                        waiting = true;
+                    }
+                    sleep(Duration::from_secs(5)).await;
+                }
+            }
+        }
+    }
+
+    // whether the stack has started an operation since the change set was
+    // created, so that the change set has gone because it was executed
+    async fn stack_updated(&self, executing: &ChangeSetOutcome) -> Result<bool> {
+        let ChangeSetOutcome::Executing { stack_name, since } = executing else {
+            return Ok(false);
+        };
+        let input = DescribeStacksInput::builder()
+            .stack_name(stack_name)
+            .build()
+            .wrap_err("building describe stacks input")?;
+        match self.client.describe_stacks(input).await {
+            Ok(res) => Ok(res
+                .stacks()
+                .first()
+                .is_some_and(|stack| started_since(stack, *since))),
+            // the stack was deleted along with the change set
+            Err(e) if e.message().is_some_and(|m| m.contains("does not exist")) => Ok(false),
+            Err(e) => Err(e).wrap_err_with(|| format!("describing stack {}", stack_name)),
+        }
+    }
+
+    /// Show the change set, and wait until it is executed or cannot be
+    pub(crate) async fn follow(&self, writer: &mut impl WriteColor) -> Result<ChangeSetOutcome> {
+        let description = self.wait_until_created().await?;
+        if let Some(outcome) = Self::failure(&description) {
+            return Ok(outcome);
+        }
+        self.print_changes(writer).await?;
+        self.wait_for_execution(writer, &description).await
+    }
+}
+
+// whether the stack has started an operation after `since`
+fn started_since(stack: &Stack, since: DateTime<Utc>) -> bool {
+    match stack.stack_status() {
+        // a stack created by a change set waits for it in review
+        Some(StackStatus::ReviewInProgress) | None => false,
+        Some(StackStatus::CreateInProgress)
+        | Some(StackStatus::CreateComplete)
+        | Some(StackStatus::CreateFailed)
+        | Some(StackStatus::RollbackInProgress)
+        | Some(StackStatus::RollbackComplete)
+        | Some(StackStatus::RollbackFailed) => true,
+        Some(_) => stack
+            .last_updated_time()
+            .and_then(|t| t.to_chrono_utc().ok())
+            .is_some_and(|updated| updated >= since),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_set_failure() {
+        let description = DescribeChangeSetOutput::builder()
+            .status(ChangeSetStatus::Failed)
+            .status_reason("The submitted information didn't contain changes. Submit different information to create a change set.")
+            .build();
+        assert!(matches!(
+            ChangeSet::failure(&description),
+            Some(ChangeSetOutcome::NoChanges(_))
+        ));
+
+        let description = DescribeChangeSetOutput::builder()
+            .status(ChangeSetStatus::Failed)
+            .status_reason("Template format error")
+            .build();
+        assert_eq!(
+            ChangeSet::failure(&description),
+            Some(ChangeSetOutcome::Failed(
+                "Template format error".to_string()
+            ))
+        );
+
+        let description = DescribeChangeSetOutput::builder()
+            .status(ChangeSetStatus::CreateComplete)
+            .execution_status(ExecutionStatus::Available)
+            .build();
+        assert_eq!(ChangeSet::failure(&description), None);
+    }
+
+    #[test]
+    fn test_started_since() {
+        use aws_smithy_types::DateTime as SmithyDateTime;
+        use chrono::TimeZone;
+
+        let since = Utc.timestamp_opt(100, 0).unwrap();
+        let stack = |status: StackStatus, updated: Option<i64>| {
+            Stack::builder()
+                .stack_name("app")
+                .stack_status(status)
+                .creation_time(SmithyDateTime::from_secs(0))
+                .set_last_updated_time(updated.map(SmithyDateTime::from_secs))
+                .build()
+        };
+        assert!(!started_since(
+            &stack(StackStatus::ReviewInProgress, None),
+            since
+        ));
+        assert!(started_since(
+            &stack(StackStatus::CreateInProgress, None),
+            since
+        ));
+        assert!(started_since(
+            &stack(StackStatus::UpdateInProgress, Some(120)),
+            since
+        ));
+        // last updated before the change set was created, so it was deleted
+        assert!(!started_since(
+            &stack(StackStatus::UpdateComplete, Some(50)),
+            since
+        ));
+    }
+}
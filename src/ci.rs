@@ -0,0 +1,213 @@
+use eyre::{Result, WrapErr};
+use std::io::Write as _;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use termcolor::WriteColor;
+
+/// File that stack outputs are written to on GitLab, to be picked up as a
+/// dotenv report artifact
+pub(crate) const DOTENV_FILE: &str = "cftail.env";
+
+// delimiter for multi-line values in `$GITHUB_OUTPUT`
+const GITHUB_OUTPUT_DELIMITER: &str = "CFTAIL_EOF";
+
+/// CI service to produce native output for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CiPlatform {
+    Github,
+    Gitlab,
+}
+
+impl FromStr for CiPlatform {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "github" => Ok(Self::Github),
+            "gitlab" => Ok(Self::Gitlab),
+            _ => eyre::bail!("unknown CI platform {:?}, expected github or gitlab", s),
+        }
+    }
+}
+
+impl std::fmt::Display for CiPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Github => f.write_str("github"),
+            Self::Gitlab => f.write_str("gitlab"),
+        }
+    }
+}
+
+// escape the message of a workflow command
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+// escape a property of a workflow command, e.g. `title`
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+// entry for `$GITHUB_OUTPUT`, using the heredoc syntax for multi-line values
+fn github_output(key: &str, value: &str) -> String {
+    if value.contains('\n') {
+        format!(
+            "{key}<<{delimiter}\n{value}\n{delimiter}\n",
+            key = key,
+            value = value,
+            delimiter = GITHUB_OUTPUT_DELIMITER
+        )
+    } else {
+        format!("{}={}\n", key, value)
+    }
+}
+
+// gitlab sections need an id that is the same at the start and end
+fn section_id(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// append to a file named by an environment variable, if it is set
+fn append_to_env_file(var: &str, contents: &str) -> Result<()> {
+    let Some(path) = std::env::var_os(var) else {
+        tracing::debug!(%var, "environment variable not set");
+        return Ok(());
+    };
+    append_to_file(&path, contents)
+}
+
+fn append_to_file(path: impl AsRef<std::path::Path>, contents: &str) -> Result<()> {
+    let path = path.as_ref();
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("opening {}", path.display()))?;
+    file.write_all(contents.as_bytes())
+        .wrap_err_with(|| format!("writing to {}", path.display()))
+}
+
+impl CiPlatform {
+    /// The CI service cftail is running on, from the variables they set
+    pub(crate) fn detect() -> Option<Self> {
+        let is_set = |var: &str| std::env::var(var).is_ok_and(|v| v == "true");
+        if is_set("GITHUB_ACTIONS") {
+            Some(Self::Github)
+        } else if is_set("GITLAB_CI") {
+            Some(Self::Gitlab)
+        } else {
+            None
+        }
+    }
+
+    /// Start a collapsible group of log lines
+    pub(crate) fn start_group(&self, writer: &mut impl WriteColor, title: &str) -> Result<()> {
+        match self {
+            Self::Github => writeln!(writer, "::group::{}", escape_data(title)),
+            Self::Gitlab => writeln!(
+                writer,
+                "\x1b[0Ksection_start:{}:{}[collapsed=false]\r\x1b[0K{}",
+                unix_time(),
+                section_id(title),
+                title
+            ),
+        }
+        .wrap_err("starting group")
+    }
+
+    /// End the group started with the same title
+    pub(crate) fn end_group(&self, writer: &mut impl WriteColor, title: &str) -> Result<()> {
+        match self {
+            Self::Github => writeln!(writer, "::endgroup::"),
+            Self::Gitlab => writeln!(
+                writer,
+                "\x1b[0Ksection_end:{}:{}\r\x1b[0K",
+                unix_time(),
+                section_id(title)
+            ),
+        }
+        .wrap_err("ending group")
+    }
+
+    /// Annotate the job with an error, on services that support it
+    pub(crate) fn error(
+        &self,
+        writer: &mut impl WriteColor,
+        title: &str,
+        message: &str,
+    ) -> Result<()> {
+        match self {
+            Self::Github => writeln!(
+                writer,
+                "::error title={}::{}",
+                escape_property(title),
+                escape_data(message)
+            )
+            .wrap_err("printing error annotation"),
+            Self::Gitlab => Ok(()),
+        }
+    }
+
+    /// Add markdown to the summary of the job, on services that support it
+    pub(crate) fn write_summary(&self, markdown: &str) -> Result<()> {
+        match self {
+            Self::Github => append_to_env_file("GITHUB_STEP_SUMMARY", markdown),
+            Self::Gitlab => Ok(()),
+        }
+    }
+
+    /// Make stack outputs available to later steps, as step outputs on GitHub
+    /// and a dotenv file on GitLab
+    pub(crate) fn export_outputs(&self, outputs: &[(&str, &str)]) -> Result<()> {
+        match self {
+            Self::Github => {
+                let contents: String = outputs
+                    .iter()
+                    .map(|(key, value)| github_output(key, value))
+                    .collect();
+                append_to_env_file("GITHUB_OUTPUT", &contents)
+            }
+            Self::Gitlab => {
+                // dotenv values cannot span lines
+                let contents: String = outputs
+                    .iter()
+                    .map(|(key, value)| format!("{}={}\n", key, value.replace('\n', " ")))
+                    .collect();
+                append_to_file(DOTENV_FILE, &contents)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_commands() {
+        assert_eq!(
+            escape_property("Function (AWS::Lambda::Function)"),
+            "Function (AWS%3A%3ALambda%3A%3AFunction)"
+        );
+        assert_eq!(escape_data("100% failed\nretry"), "100%25 failed%0Aretry");
+        assert_eq!(github_output("Url", "https://x"), "Url=https://x\n");
+        assert_eq!(
+            github_output("Cert", "a\nb"),
+            "Cert<<CFTAIL_EOF\na\nb\nCFTAIL_EOF\n"
+        );
+    }
+}
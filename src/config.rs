@@ -0,0 +1,277 @@
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::stacks::{check_region, Credentials, Location, StackTarget};
+
+const LOCAL_CONFIG_NAME: &str = ".cftail.toml";
+
+/// Defaults for the command line options, loaded from a configuration file
+///
+/// Keys are named after the command line flags, e.g. `no-show-outputs = true`.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) stacks: Option<Vec<StackEntry>>,
+    pub(crate) regions: Option<Vec<String>>,
+    pub(crate) since: Option<String>,
+    pub(crate) nested: Option<bool>,
+    pub(crate) no_show_separators: Option<bool>,
+    pub(crate) no_show_notifications: Option<bool>,
+    pub(crate) no_show_outputs: Option<bool>,
+    pub(crate) no_show_resource_types: Option<bool>,
+    pub(crate) construct_paths: Option<bool>,
+    pub(crate) no_show_progress: Option<bool>,
+    pub(crate) no_exit_on_completion: Option<bool>,
+    pub(crate) no_history: Option<bool>,
+    /// Duration such as `10m`
+    pub(crate) stuck_after: Option<String>,
+    pub(crate) trace_out: Option<PathBuf>,
+    /// Duration such as `30m`
+    pub(crate) timeout: Option<String>,
+    pub(crate) cancel_on_timeout: Option<bool>,
+    pub(crate) ci: Option<String>,
+    pub(crate) webhook: Option<Vec<String>>,
+    pub(crate) webhook_template: Option<PathBuf>,
+    pub(crate) webhook_on_rollback: Option<bool>,
+    pub(crate) on_complete: Option<String>,
+    pub(crate) on_failure: Option<String>,
+    pub(crate) on_event: Option<String>,
+    pub(crate) sound: Option<String>,
+    pub(crate) failure_sound: Option<String>,
+    pub(crate) endpoint_url: Option<String>,
+    pub(crate) template: Option<Vec<String>>,
+    pub(crate) cdk_out: Option<PathBuf>,
+    pub(crate) stack_set_role_name: Option<String>,
+    pub(crate) exec_prefix: Option<String>,
+    /// File to write each report to, keyed by format, e.g. `html = "report.html"`
+    pub(crate) report: Option<BTreeMap<String, PathBuf>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) groups: BTreeMap<String, Group>,
+}
+
+/// Named collection of stacks, tailed with `cftail @name`
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Group {
+    pub(crate) stacks: Vec<StackEntry>,
+}
+
+/// Stack in a configuration file, either in the command line syntax or as a table
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub(crate) enum StackEntry {
+    Target(String),
+    #[serde(rename_all = "kebab-case")]
+    Table {
+        name: String,
+        region: Option<String>,
+        profile: Option<String>,
+        role_arn: Option<String>,
+    },
+}
+
+impl StackEntry {
+    pub(crate) fn to_target(&self) -> Result<StackTarget> {
+        match self {
+            StackEntry::Target(s) => s.parse(),
+            StackEntry::Table {
+                name,
+                region,
+                profile,
+                role_arn,
+            } => {
+                let credentials = match (profile, role_arn) {
+                    (Some(_), Some(_)) => {
+                        eyre::bail!("stack {} has both a profile and a role-arn", name)
+                    }
+                    (Some(profile), None) => Some(Credentials::Profile(profile.clone())),
+                    (None, Some(arn)) => Some(Credentials::AssumeRole(arn.clone())),
+                    (None, None) => None,
+                };
+                if let Some(region) = region {
+                    check_region(region).wrap_err_with(|| format!("stack {}", name))?;
+                }
+                let location = Location {
+                    credentials,
+                    region: region.clone(),
+                };
+                Ok(StackTarget::new(location, name.clone()))
+            }
+        }
+    }
+}
+
+impl From<&StackTarget> for StackEntry {
+    fn from(target: &StackTarget) -> Self {
+        StackEntry::Target(target.to_string())
+    }
+}
+
+impl Config {
+    /// Load the configuration, either from `path` or by merging the user
+    /// configuration with the nearest `.cftail.toml`
+    pub(crate) fn load(path: Option<&Path>) -> Result<Config> {
+        if let Some(path) = path {
+            return Config::from_file(path);
+        }
+
+        let mut config = Config::default();
+        for path in [user_config_path(), local_config_path()].iter().flatten() {
+            if path.is_file() {
+                config = config.merge(Config::from_file(path)?);
+            }
+        }
+        Ok(config)
+    }
+
+    fn from_file(path: &Path) -> Result<Config> {
+        tracing::debug!(path = %path.display(), "loading config file");
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("reading config file {}", path.display()))?;
+        toml::from_str(&contents)
+            .wrap_err_with(|| format!("parsing config file {}", path.display()))
+    }
+
+    // combine two configurations, with values from `other` taking precedence
+    fn merge(mut self, other: Config) -> Config {
+        self.groups.extend(other.groups);
+        Config {
+            stacks: other.stacks.or(self.stacks),
+            regions: other.regions.or(self.regions),
+            since: other.since.or(self.since),
+            nested: other.nested.or(self.nested),
+            no_show_separators: other.no_show_separators.or(self.no_show_separators),
+            no_show_notifications: other.no_show_notifications.or(self.no_show_notifications),
+            no_show_outputs: other.no_show_outputs.or(self.no_show_outputs),
+            no_show_resource_types: other.no_show_resource_types.or(self.no_show_resource_types),
+            construct_paths: other.construct_paths.or(self.construct_paths),
+            no_show_progress: other.no_show_progress.or(self.no_show_progress),
+            no_exit_on_completion: other.no_exit_on_completion.or(self.no_exit_on_completion),
+            no_history: other.no_history.or(self.no_history),
+            stuck_after: other.stuck_after.or(self.stuck_after),
+            trace_out: other.trace_out.or(self.trace_out),
+            timeout: other.timeout.or(self.timeout),
+            cancel_on_timeout: other.cancel_on_timeout.or(self.cancel_on_timeout),
+            ci: other.ci.or(self.ci),
+            webhook: other.webhook.or(self.webhook),
+            webhook_template: other.webhook_template.or(self.webhook_template),
+            webhook_on_rollback: other.webhook_on_rollback.or(self.webhook_on_rollback),
+            on_complete: other.on_complete.or(self.on_complete),
+            on_failure: other.on_failure.or(self.on_failure),
+            on_event: other.on_event.or(self.on_event),
+            sound: other.sound.or(self.sound),
+            failure_sound: other.failure_sound.or(self.failure_sound),
+            endpoint_url: other.endpoint_url.or(self.endpoint_url),
+            template: other.template.or(self.template),
+            cdk_out: other.cdk_out.or(self.cdk_out),
+            stack_set_role_name: other.stack_set_role_name.or(self.stack_set_role_name),
+            exec_prefix: other.exec_prefix.or(self.exec_prefix),
+            report: other.report.or(self.report),
+            groups: self.groups,
+        }
+    }
+
+    /// Turn stack names from the command line into targets, expanding `@group` references
+    pub(crate) fn resolve_targets(&self, names: &[String]) -> Result<Vec<StackTarget>> {
+        let mut targets = Vec::new();
+        for name in names {
+            match name.strip_prefix('@') {
+                Some(group_name) => {
+                    let group = self
+                        .groups
+                        .get(group_name)
+                        .ok_or_else(|| eyre::eyre!("no stack group named {} found", group_name))?;
+                    for entry in &group.stacks {
+                        targets.push(entry.to_target()?);
+                    }
+                }
+                None => targets.push(name.parse()?),
+            }
+        }
+        Ok(targets)
+    }
+}
+
+// `$XDG_CONFIG_HOME/cftail/config.toml`, or `~/.config/cftail/config.toml`
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("cftail").join("config.toml"))
+}
+
+/// `$XDG_DATA_HOME/cftail`, or `~/.local/share/cftail`, where history is kept
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    let data_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(data_dir.join("cftail"))
+}
+
+// nearest `.cftail.toml` in the current directory or its parents
+fn local_config_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(LOCAL_CONFIG_NAME))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            nested = true
+            sound = "Glass"
+
+            [groups.prod-api]
+            stacks = [
+                "prod:eu-west-1:api",
+                { name = "api", region = "us-east-1", role-arn = "arn:aws:iam::123456789012:role/deployer" },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.nested, Some(true));
+        assert_eq!(config.sound.as_deref(), Some("Glass"));
+
+        let targets = config
+            .resolve_targets(&["@prod-api".to_string(), "other".to_string()])
+            .unwrap();
+        let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                "prod:eu-west-1:api",
+                "arn:aws:iam::123456789012:role/deployer:us-east-1:api",
+                "other",
+            ]
+        );
+
+        assert!(config.resolve_targets(&["@missing".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_merge_config() {
+        let user = Config {
+            nested: Some(true),
+            sound: Some("Glass".to_string()),
+            ..Default::default()
+        };
+        let local = Config {
+            sound: Some("Ping".to_string()),
+            ..Default::default()
+        };
+        let merged = user.merge(local);
+        assert_eq!(merged.nested, Some(true));
+        assert_eq!(merged.sound.as_deref(), Some("Ping"));
+    }
+}
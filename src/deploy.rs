@@ -0,0 +1,271 @@
+use aws_sdk_cloudformation::operation::create_change_set::CreateChangeSetInput;
+use aws_sdk_cloudformation::operation::describe_stacks::DescribeStacksInput;
+use aws_sdk_cloudformation::operation::execute_change_set::ExecuteChangeSetInput;
+use aws_sdk_cloudformation::types::{
+    Capability, ChangeSetType, Parameter, Stack, StackStatus, Tag,
+};
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
+use chrono::Utc;
+use eyre::{Context, Result};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use termcolor::WriteColor;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::aws::Fetcher;
+use crate::change_sets::{ChangeSet, ChangeSetOutcome};
+use crate::stacks::StackTarget;
+use crate::template::Template;
+
+// largest template that can be passed in the request rather than from S3
+const MAX_TEMPLATE_BODY: usize = 51_200;
+
+fn parse_key_value(src: &str) -> Result<(String, String)> {
+    match src.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => eyre::bail!("invalid value {:?}, expected Key=Value", src),
+    }
+}
+
+/// Deploy a template by creating a change set, then tail its execution
+#[derive(Debug, StructOpt)]
+pub(crate) struct DeployOpts {
+    /// Name of the stack to deploy, optionally prefixed with a profile or IAM
+    /// role arn and region as `[profile:][region:]stack-name`
+    pub(crate) stack: StackTarget,
+
+    /// Template file to deploy, in JSON or YAML format
+    #[structopt(short, long, parse(from_os_str))]
+    pub(crate) template_file: PathBuf,
+
+    /// Parameter values as `Key=Value`. Parameters that are not given keep
+    /// their current value
+    #[structopt(long, parse(try_from_str = parse_key_value))]
+    pub(crate) parameter_overrides: Vec<(String, String)>,
+
+    /// Capabilities to acknowledge, e.g. `CAPABILITY_IAM`
+    #[structopt(long, use_delimiter = true)]
+    pub(crate) capabilities: Vec<String>,
+
+    /// Tags to apply to the stack as `Key=Value`
+    #[structopt(long, parse(try_from_str = parse_key_value))]
+    pub(crate) tags: Vec<(String, String)>,
+
+    /// Execute the change set without asking for confirmation
+    #[structopt(short, long)]
+    pub(crate) yes: bool,
+}
+
+// the stack, if it exists
+async fn existing_stack(client: &Fetcher, stack_name: &str) -> Result<Option<Stack>> {
+    let input = DescribeStacksInput::builder()
+        .stack_name(stack_name)
+        .build()
+        .wrap_err("building describe stacks input")?;
+    match client.describe_stacks(input).await {
+        Ok(res) => Ok(res.stacks.and_then(|stacks| stacks.into_iter().next())),
+        Err(e) if e.message().is_some_and(|m| m.contains("does not exist")) => Ok(None),
+        Err(e) => Err(e).wrap_err_with(|| format!("describing stack {}", stack_name)),
+    }
+}
+
+// the overridden parameters, keeping the current value of any other
+// parameter that the template still declares
+fn parameters(
+    overrides: &[(String, String)],
+    current: &[String],
+    declared: &[String],
+) -> Vec<Parameter> {
+    let mut parameters: Vec<_> = overrides
+        .iter()
+        .map(|(key, value)| {
+            Parameter::builder()
+                .parameter_key(key)
+                .parameter_value(value)
+                .build()
+        })
+        .collect();
+    for key in current {
+        if declared.contains(key) && !overrides.iter().any(|(k, _)| k == key) {
+            parameters.push(
+                Parameter::builder()
+                    .parameter_key(key)
+                    .use_previous_value(true)
+                    .build(),
+            );
+        }
+    }
+    parameters
+}
+
+// whether to create the stack or update it, going by its current status
+fn change_set_type(stack_name: &str, status: Option<&StackStatus>) -> Result<ChangeSetType> {
+    match status {
+        // stacks are left in review when only a change set has been created for them
+        None | Some(StackStatus::ReviewInProgress) => Ok(ChangeSetType::Create),
+        // the stack failed to be created, and cannot be updated
+        Some(status @ (StackStatus::RollbackComplete | StackStatus::RollbackFailed)) => {
+            eyre::bail!(
+                "stack {} is in {} after it failed to be created, delete it before deploying it again",
+                stack_name,
+                status.as_str()
+            )
+        }
+        Some(_) => Ok(ChangeSetType::Update),
+    }
+}
+
+async fn confirm(writer: &mut impl WriteColor) -> Result<bool> {
+    write!(writer, "Execute this change set? [y/N] ").wrap_err("printing prompt")?;
+    writer.flush().wrap_err("flushing prompt")?;
+    let mut answer = String::new();
+    BufReader::new(tokio::io::stdin())
+        .read_line(&mut answer)
+        .await
+        .wrap_err("reading confirmation")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Create a change set for the template, and execute it once confirmed
+#[tracing::instrument(skip(client, opts, writer))]
+pub(crate) async fn deploy(
+    client: Fetcher,
+    opts: &DeployOpts,
+    writer: &mut impl WriteColor,
+) -> Result<ChangeSetOutcome> {
+    let template = Template::from_file(&opts.template_file)?;
+    if template.body.len() > MAX_TEMPLATE_BODY {
+        eyre::bail!(
+            "template {} is {} bytes, over the {} byte limit for deploying templates directly. Upload it to S3 and deploy it with `aws cloudformation deploy --s3-bucket` instead",
+            opts.template_file.display(),
+            template.body.len(),
+            MAX_TEMPLATE_BODY
+        );
+    }
+    let stack_name = &opts.stack.name;
+
+    let stack = existing_stack(&client, stack_name).await?;
+    let change_set_type =
+        change_set_type(stack_name, stack.as_ref().and_then(|s| s.stack_status()))?;
+    let current: Vec<String> = stack
+        .as_ref()
+        .map(|s| {
+            s.parameters()
+                .iter()
+                .filter_map(|p| p.parameter_key().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let tags = opts
+        .tags
+        .iter()
+        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+        .collect();
+    let input = CreateChangeSetInput::builder()
+        .stack_name(stack_name)
+        .change_set_name(format!("cftail-{}", Utc::now().format("%Y%m%d%H%M%S")))
+        .change_set_type(change_set_type.clone())
+        .template_body(&template.body)
+        .set_parameters(Some(parameters(
+            &opts.parameter_overrides,
+            &current,
+            &template.parameter_names(),
+        )))
+        .set_capabilities(Some(
+            opts.capabilities
+                .iter()
+                .map(|c| Capability::from(c.as_str()))
+                .collect(),
+        ))
+        .set_tags(Some(tags))
+        .build()
+        .wrap_err("building create change set input")?;
+    let res = client
+        .create_change_set(input)
+        .await
+        .wrap_err_with(|| format!("creating change set for stack {}", stack_name))?;
+    let change_set_id = res
+        .id
+        .ok_or_else(|| eyre::eyre!("created change set has no id"))?;
+    writeln!(
+        writer,
+        "Created {} change set {}",
+        change_set_type.as_str().to_lowercase(),
+        change_set_id
+    )
+    .wrap_err("printing change set")?;
+
+    let change_set = ChangeSet::new(client.clone(), &change_set_id, None);
+    let description = change_set.wait_until_created().await?;
+    if let Some(outcome) = ChangeSet::failure(&description) {
+        return Ok(outcome);
+    }
+    change_set.print_changes(writer).await?;
+
+    if !opts.yes && !confirm(writer).await? {
+        return Ok(ChangeSetOutcome::Declined);
+    }
+
+    let input = ExecuteChangeSetInput::builder()
+        .change_set_name(&change_set_id)
+        .build()
+        .wrap_err("building execute change set input")?;
+    client
+        .execute_change_set(input)
+        .await
+        .wrap_err("executing change set")?;
+    ChangeSet::executing(&description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters() {
+        let overrides = vec![("Environment".to_string(), "prod".to_string())];
+        let current = vec![
+            "Environment".to_string(),
+            "BucketName".to_string(),
+            "Removed".to_string(),
+        ];
+        let declared = vec!["Environment".to_string(), "BucketName".to_string()];
+
+        let parameters = parameters(&overrides, &current, &declared);
+        assert_eq!(
+            parameters,
+            vec![
+                Parameter::builder()
+                    .parameter_key("Environment")
+                    .parameter_value("prod")
+                    .build(),
+                Parameter::builder()
+                    .parameter_key("BucketName")
+                    .use_previous_value(true)
+                    .build(),
+            ]
+        );
+
+        assert!(parse_key_value("Key=a=b").is_ok());
+        assert!(parse_key_value("=value").is_err());
+        assert!(parse_key_value("Key").is_err());
+    }
+
+    #[test]
+    fn test_change_set_type() {
+        assert_eq!(change_set_type("app", None).unwrap(), ChangeSetType::Create);
+        assert_eq!(
+            change_set_type("app", Some(&StackStatus::ReviewInProgress)).unwrap(),
+            ChangeSetType::Create
+        );
+        assert_eq!(
+            change_set_type("app", Some(&StackStatus::UpdateRollbackComplete)).unwrap(),
+            ChangeSetType::Update
+        );
+        let err = change_set_type("app", Some(&StackStatus::RollbackComplete)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("delete it before deploying it again"));
+    }
+}
@@ -0,0 +1,551 @@
+use aws_sdk_cloudformation::operation::describe_stack_events::DescribeStackEventsInput;
+use aws_sdk_cloudformation::operation::describe_stacks::DescribeStacksInput;
+use aws_sdk_cloudformation::types::StackEvent;
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::{DateTime, Duration, Utc};
+use eyre::{Result, WrapErr};
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use structopt::StructOpt;
+use term_table::{row::Row, Table, TableStyle};
+use termcolor::WriteColor;
+
+use crate::aws::Fetcher;
+use crate::history::{History, Operation};
+use crate::progress::format_duration;
+use crate::stack_status::StackStatus;
+use crate::stacks::StackTarget;
+
+// operations to load from the history when looking for the ones to compare
+const MAX_OPERATIONS: usize = 100;
+
+// resources that got slower by less than this are not worth mentioning
+const REGRESSION_THRESHOLD_SECS: i64 = 10;
+
+/// Compare two operations on the same stack
+#[derive(Debug, StructOpt)]
+pub(crate) struct DiffOpts {
+    /// Name of the stack, optionally prefixed with a profile or IAM role arn
+    /// and region as `[profile:][region:]stack-name`
+    pub(crate) stack: StackTarget,
+
+    /// Earlier operation, given as its client request token or the time it
+    /// started (e.g. `2024-05-01` or `2024-05-01T10:15`). Defaults to the
+    /// operation before `to`
+    pub(crate) from: Option<String>,
+
+    /// Later operation, defaults to the most recent one
+    pub(crate) to: Option<String>,
+}
+
+/// Operation along with the events that happened during it
+#[derive(Debug, Clone)]
+pub(crate) struct Deployment {
+    pub(crate) operation: Operation,
+    /// Events of the operation, oldest first
+    pub(crate) events: Vec<StackEvent>,
+    /// Whether the parameters and outputs were recorded when the operation
+    /// finished, which is not the case for operations rebuilt from events
+    pub(crate) recorded: bool,
+}
+
+impl Deployment {
+    fn matches(&self, selector: &str) -> bool {
+        let started = self.operation.started;
+        self.operation.client_request_token.as_deref() == Some(selector)
+            || started
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string()
+                .starts_with(selector)
+            || started
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+                .starts_with(selector)
+    }
+
+    fn duration(&self) -> Duration {
+        self.operation.finished - self.operation.started
+    }
+}
+
+// latest status of a resource during an operation
+#[derive(Debug, Clone, PartialEq)]
+struct ResourceSummary {
+    resource_type: String,
+    status: String,
+    // from when the resource first started changing until its last terminal status
+    duration: Option<Duration>,
+}
+
+fn status(event: &StackEvent) -> &str {
+    event
+        .resource_status()
+        .map(|s| s.as_str())
+        .unwrap_or_default()
+}
+
+fn timestamp(event: &StackEvent) -> Option<DateTime<Utc>> {
+    event.timestamp()?.to_chrono_utc().ok()
+}
+
+fn is_stack_event(event: &StackEvent) -> bool {
+    event.logical_resource_id().is_some() && event.logical_resource_id() == event.stack_name()
+}
+
+// whether the operation is over, rather than e.g. failing and about to roll
+// back, or cleaning up
+fn is_terminal(status: &str) -> bool {
+    StackStatus::try_from(status).is_ok_and(|s| s.is_terminal())
+}
+
+fn resources(events: &[StackEvent]) -> BTreeMap<String, ResourceSummary> {
+    let mut starts: BTreeMap<String, DateTime<Utc>> = BTreeMap::new();
+    let mut resources: BTreeMap<String, ResourceSummary> = BTreeMap::new();
+    for event in events.iter().filter(|e| !is_stack_event(e)) {
+        let (Some(logical_id), Some(timestamp)) = (event.logical_resource_id(), timestamp(event))
+        else {
+            continue;
+        };
+        let status = status(event);
+        let resource = resources
+            .entry(logical_id.to_string())
+            .or_insert_with(|| ResourceSummary {
+                resource_type: event.resource_type().unwrap_or_default().to_string(),
+                status: String::new(),
+                duration: None,
+            });
+        resource.status = status.to_string();
+        if status.ends_with("_IN_PROGRESS") {
+            starts.entry(logical_id.to_string()).or_insert(timestamp);
+        } else if let Some(started) = starts.get(logical_id) {
+            resource.duration = Some(timestamp - *started);
+        }
+    }
+    resources
+}
+
+/// Split the events of a stack, oldest first, into the operations they belong
+/// to, newest first
+pub(crate) fn rebuild(events: &[StackEvent]) -> Vec<Deployment> {
+    let mut deployments = Vec::new();
+    let mut current: Option<Deployment> = None;
+    for event in events {
+        let Some(timestamp) = timestamp(event) else {
+            continue;
+        };
+        let status = status(event);
+        if is_stack_event(event)
+            && matches!(
+                status,
+                "CREATE_IN_PROGRESS"
+                    | "UPDATE_IN_PROGRESS"
+                    | "DELETE_IN_PROGRESS"
+                    | "IMPORT_IN_PROGRESS"
+            )
+        {
+            current = Some(Deployment {
+                operation: Operation {
+                    stack_id: event.stack_id().unwrap_or_default().to_string(),
+                    stack_name: event.stack_name().unwrap_or_default().to_string(),
+                    started: timestamp,
+                    finished: timestamp,
+                    status: status.to_string(),
+                    client_request_token: event.client_request_token().map(String::from),
+                    parameters: BTreeMap::new(),
+                    outputs: BTreeMap::new(),
+                    tags: BTreeMap::new(),
+                    template_hash: None,
+                },
+                events: Vec::new(),
+                recorded: false,
+            });
+        }
+
+        let Some(deployment) = current.as_mut() else {
+            continue;
+        };
+        deployment.events.push(event.clone());
+        if is_stack_event(event) && is_terminal(status) {
+            deployment.operation.finished = timestamp;
+            deployment.operation.status = status.to_string();
+            deployments.extend(current.take());
+        }
+    }
+    deployments.reverse();
+    deployments
+}
+
+// id of the stack, which tells it apart from earlier stacks with the same name
+async fn fetch_stack_id(client: &Fetcher, stack_name: &str) -> Result<String> {
+    let input = DescribeStacksInput::builder()
+        .stack_name(stack_name)
+        .build()
+        .wrap_err("building describe stacks input")?;
+    let res = client
+        .describe_stacks(input)
+        .await
+        .wrap_err_with(|| format!("describing stack {}", stack_name))?;
+    res.stacks()
+        .first()
+        .and_then(|stack| stack.stack_id())
+        .map(String::from)
+        .ok_or_else(|| eyre::eyre!("stack {} not found", stack_name))
+}
+
+// every event of a stack, oldest first
+async fn fetch_events(client: &Fetcher, stack_id: &str) -> Result<Vec<StackEvent>> {
+    let mut events = Vec::new();
+    let mut next_token = None;
+    loop {
+        let input = DescribeStackEventsInput::builder()
+            .stack_name(stack_id)
+            .set_next_token(next_token)
+            .build()
+            .wrap_err("building describe stack events input")?;
+        let res = client
+            .describe_stack_events(input)
+            .await
+            .wrap_err("fetching stack events")?;
+        events.extend(res.stack_events().iter().cloned());
+        next_token = res.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+    events.reverse();
+    Ok(events)
+}
+
+fn history_deployments(history: &History, stack_id: &str) -> Result<Vec<Deployment>> {
+    history
+        .stack_operations(stack_id, MAX_OPERATIONS)?
+        .into_iter()
+        .map(|operation| {
+            let events =
+                history.events(&operation.stack_id, operation.started, operation.finished)?;
+            Ok(Deployment {
+                operation,
+                events,
+                recorded: true,
+            })
+        })
+        .collect()
+}
+
+/// The two operations to compare from a list of operations, newest first
+fn select<'d>(
+    deployments: &'d [Deployment],
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(&'d Deployment, &'d Deployment)> {
+    let find = |selector: &str, skip: usize| {
+        deployments
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .find(|(_, d)| d.matches(selector))
+            .ok_or_else(|| eyre::eyre!("no operation matching {:?}", selector))
+    };
+
+    let (to_index, to) = match to {
+        Some(selector) => find(selector, 0)?,
+        None => (
+            0,
+            deployments
+                .first()
+                .ok_or_else(|| eyre::eyre!("no operations found"))?,
+        ),
+    };
+    // the earlier operation has to come after the later one in the list
+    let (_, from) = match from {
+        Some(selector) => match find(selector, to_index + 1) {
+            Ok(found) => found,
+            Err(_) if find(selector, 0).is_ok() => eyre::bail!(
+                "operation {:?} is not older than the operation it is compared with",
+                selector
+            ),
+            Err(e) => return Err(e),
+        },
+        None => (
+            0,
+            deployments
+                .get(to_index + 1)
+                .ok_or_else(|| eyre::eyre!("no operation before {}", to.operation.started))?,
+        ),
+    };
+    Ok((from, to))
+}
+
+/// Compare two operations on a stack, from the local history if it has them
+/// and otherwise rebuilt from the stack's events
+pub(crate) async fn diff(
+    client: Fetcher,
+    history: Option<&History>,
+    opts: &DiffOpts,
+    writer: &mut impl WriteColor,
+) -> Result<()> {
+    let (from, to) = (opts.from.as_deref(), opts.to.as_deref());
+    let stack_id = fetch_stack_id(&client, &opts.stack.name).await?;
+    if let Some(history) = history {
+        let deployments = history_deployments(history, &stack_id)?;
+        match select(&deployments, from, to) {
+            Ok((from, to)) => return print_diff(writer, from, to),
+            Err(e) => tracing::debug!(err = %e, "operations not found in history"),
+        }
+    }
+
+    let events = fetch_events(&client, &stack_id).await?;
+    let deployments = rebuild(&events);
+    let (from, to) = select(&deployments, from, to)?;
+    print_diff(writer, from, to)
+}
+
+fn format_change(before: Duration, after: Duration) -> String {
+    let change = after - before;
+    let sign = if change < Duration::zero() { "-" } else { "+" };
+    let mut text = format!("{}{}", sign, format_duration(change.abs()));
+    if before.num_seconds() > 0 {
+        text.push_str(&format!(
+            ", {:.1}x",
+            after.num_seconds() as f64 / before.num_seconds() as f64
+        ));
+    }
+    text
+}
+
+fn print_table(writer: &mut impl WriteColor, title: &str, rows: Vec<Vec<String>>) -> Result<()> {
+    if rows.len() <= 1 {
+        return Ok(());
+    }
+    writeln!(writer, "\n{}:", title).wrap_err("printing diff header")?;
+    let mut table = Table::new();
+    table.style = TableStyle::thin();
+    for row in rows {
+        table.add_row(Row::new(row));
+    }
+    writeln!(writer, "{}", table.render()).wrap_err("printing diff")?;
+    Ok(())
+}
+
+// rows for values that were added, removed or changed between two maps
+fn map_rows(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<Vec<String>> {
+    let mut rows = vec![vec![
+        "Change".to_string(),
+        "Key".to_string(),
+        "Before".to_string(),
+        "After".to_string(),
+    ]];
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    for key in keys {
+        let change = match (before.get(key), after.get(key)) {
+            (Some(b), Some(a)) if a == b => continue,
+            (Some(_), Some(_)) => "Changed",
+            (None, _) => "Added",
+            (_, None) => "Removed",
+        };
+        rows.push(vec![
+            change.to_string(),
+            key.clone(),
+            before.get(key).cloned().unwrap_or_default(),
+            after.get(key).cloned().unwrap_or_default(),
+        ]);
+    }
+    rows
+}
+
+/// Print what changed between two operations
+pub(crate) fn print_diff(
+    writer: &mut impl WriteColor,
+    from: &Deployment,
+    to: &Deployment,
+) -> Result<()> {
+    for (label, deployment) in [("From", from), ("To", to)].iter() {
+        let operation = &deployment.operation;
+        write!(
+            writer,
+            "{:<5} {} {} in {}",
+            format!("{}:", label),
+            operation.started.format("%Y-%m-%d %H:%M:%S"),
+            operation.status,
+            format_duration(deployment.duration())
+        )
+        .wrap_err("printing operation")?;
+        if let Some(token) = &operation.client_request_token {
+            write!(writer, " ({})", token).wrap_err("printing operation token")?;
+        }
+        writeln!(writer).wrap_err("printing operation")?;
+    }
+    writeln!(
+        writer,
+        "Duration: {}",
+        format_change(from.duration(), to.duration())
+    )
+    .wrap_err("printing duration change")?;
+
+    let before = resources(&from.events);
+    let after = resources(&to.events);
+    let ids: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+
+    let mut resource_rows = vec![vec![
+        "Change".to_string(),
+        "Logical ID".to_string(),
+        "Type".to_string(),
+        "Before".to_string(),
+        "After".to_string(),
+    ]];
+    let mut regressions = Vec::new();
+    for id in ids {
+        let (b, a) = (before.get(id), after.get(id));
+        let change = match (b, a) {
+            (Some(b), Some(a)) if a.status == b.status => None,
+            (Some(_), Some(_)) => Some("Changed"),
+            (None, _) => Some("Added"),
+            (_, None) => Some("Removed"),
+        };
+        let resource = a.or(b).expect("resource is in at least one operation");
+        if let Some(change) = change {
+            resource_rows.push(vec![
+                change.to_string(),
+                id.clone(),
+                resource.resource_type.clone(),
+                b.map(|r| r.status.clone()).unwrap_or_default(),
+                a.map(|r| r.status.clone()).unwrap_or_default(),
+            ]);
+        }
+        if let (Some(before), Some(after)) =
+            (b.and_then(|r| r.duration), a.and_then(|r| r.duration))
+        {
+            if (after - before).num_seconds() >= REGRESSION_THRESHOLD_SECS {
+                regressions.push((after - before, id, resource, before, after));
+            }
+        }
+    }
+    print_table(writer, "Resources", resource_rows)?;
+
+    // biggest regressions first
+    regressions.sort_by_key(|r| std::cmp::Reverse(r.0));
+    let mut regression_rows = vec![vec![
+        "Logical ID".to_string(),
+        "Type".to_string(),
+        "Before".to_string(),
+        "After".to_string(),
+        "Change".to_string(),
+    ]];
+    for (_, id, resource, before, after) in regressions {
+        regression_rows.push(vec![
+            id.clone(),
+            resource.resource_type.clone(),
+            format_duration(before),
+            format_duration(after),
+            format_change(before, after),
+        ]);
+    }
+    print_table(writer, "Slower resources", regression_rows)?;
+
+    if from.recorded && to.recorded {
+        print_table(
+            writer,
+            "Parameters",
+            map_rows(&from.operation.parameters, &to.operation.parameters),
+        )?;
+        print_table(
+            writer,
+            "Outputs",
+            map_rows(&from.operation.outputs, &to.operation.outputs),
+        )?;
+        if from.operation.template_hash != to.operation.template_hash {
+            writeln!(writer, "\nThe template changed").wrap_err("printing template change")?;
+        }
+    } else {
+        writeln!(
+            writer,
+            "\nParameters and outputs are only compared for operations in the local history"
+        )
+        .wrap_err("printing note")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_cloudformation::types::ResourceStatus;
+    use aws_smithy_types::DateTime as SmithyDateTime;
+
+    fn event(logical_id: &str, status: ResourceStatus, secs: i64, token: &str) -> StackEvent {
+        StackEvent::builder()
+            .stack_id("stack-id")
+            .stack_name("my-stack")
+            .logical_resource_id(logical_id)
+            .resource_type("AWS::CloudFront::Distribution")
+            .resource_status(status)
+            .timestamp(SmithyDateTime::from_secs(1_600_000_000 + secs))
+            .client_request_token(token)
+            .build()
+    }
+
+    #[test]
+    fn test_rebuild_rollback() {
+        // the operation carries on after a resource fails, until it has
+        // rolled back
+        let events = vec![
+            event("my-stack", ResourceStatus::UpdateInProgress, 0, "one"),
+            event("Cdn", ResourceStatus::UpdateFailed, 10, "one"),
+            event(
+                "my-stack",
+                ResourceStatus::UpdateRollbackInProgress,
+                12,
+                "one",
+            ),
+            event(
+                "my-stack",
+                ResourceStatus::from("UPDATE_ROLLBACK_COMPLETE_CLEANUP_IN_PROGRESS"),
+                50,
+                "one",
+            ),
+            event(
+                "my-stack",
+                ResourceStatus::UpdateRollbackComplete,
+                60,
+                "one",
+            ),
+        ];
+        let deployments = rebuild(&events);
+        assert_eq!(deployments.len(), 1);
+        assert_eq!(deployments[0].operation.status, "UPDATE_ROLLBACK_COMPLETE");
+        assert_eq!(deployments[0].events.len(), 5);
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let events = vec![
+            event("my-stack", ResourceStatus::CreateInProgress, 0, "one"),
+            event("Cdn", ResourceStatus::CreateInProgress, 10, "one"),
+            event("Cdn", ResourceStatus::CreateComplete, 70, "one"),
+            event("my-stack", ResourceStatus::CreateComplete, 80, "one"),
+            event("my-stack", ResourceStatus::UpdateInProgress, 1000, "two"),
+            event("Cdn", ResourceStatus::UpdateInProgress, 1010, "two"),
+            event("Cdn", ResourceStatus::UpdateComplete, 1190, "two"),
+            event("my-stack", ResourceStatus::UpdateComplete, 1200, "two"),
+        ];
+        let deployments = rebuild(&events);
+        assert_eq!(deployments.len(), 2);
+        assert_eq!(deployments[0].operation.status, "UPDATE_COMPLETE");
+        assert_eq!(deployments[0].duration(), Duration::seconds(200));
+
+        let (from, to) = select(&deployments, None, None).unwrap();
+        assert_eq!(from.operation.client_request_token.as_deref(), Some("one"));
+        assert_eq!(to.operation.client_request_token.as_deref(), Some("two"));
+        assert!(select(&deployments, Some("two"), None).is_err());
+        // the earlier operation has to be given first
+        assert!(select(&deployments, Some("one"), Some("two")).is_ok());
+        assert!(select(&deployments, Some("two"), Some("one")).is_err());
+
+        let after = resources(&to.events);
+        assert_eq!(after["Cdn"].status, "UPDATE_COMPLETE");
+        assert_eq!(after["Cdn"].duration, Some(Duration::minutes(3)));
+    }
+}
@@ -0,0 +1,177 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeMap;
+
+// most recent samples to keep for each resource and action
+pub(crate) const MAX_SAMPLES: usize = 10;
+
+// how long one operation on a resource took
+#[derive(Debug, Clone, PartialEq)]
+struct Sample {
+    // unix timestamp of when the operation finished, so that events seen
+    // again are not recorded twice
+    finished: i64,
+    seconds: i64,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ResourceDurations {
+    resource_type: String,
+    // samples for each action, e.g. `CREATE` or `UPDATE`
+    samples: BTreeMap<String, Vec<Sample>>,
+}
+
+/// How long operations on resources have taken in the past, keyed by stack,
+/// logical id and resource type
+///
+/// These are worked out from the events in the local history, and kept up to
+/// date in memory while tailing.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Durations {
+    stacks: BTreeMap<String, BTreeMap<String, ResourceDurations>>,
+}
+
+// action of a resource status, e.g. `CREATE` for `CREATE_IN_PROGRESS`
+pub(crate) fn action(status: &str) -> &str {
+    status.split('_').next().unwrap_or(status)
+}
+
+// resources are only unusually slow once they take this many times longer
+// than usual, and at least a few minutes longer
+const SLOW_FACTOR: i32 = 3;
+const MIN_SLOWDOWN: i64 = 5;
+
+/// Whether an operation that has been running for `elapsed` is taking much
+/// longer than the `expected` duration
+pub(crate) fn is_unusually_slow(elapsed: Duration, expected: Duration) -> bool {
+    elapsed > expected * SLOW_FACTOR && elapsed - expected >= Duration::minutes(MIN_SLOWDOWN)
+}
+
+fn median(mut seconds: Vec<i64>) -> Option<Duration> {
+    if seconds.is_empty() {
+        return None;
+    }
+    seconds.sort_unstable();
+    Some(Duration::seconds(seconds[seconds.len() / 2]))
+}
+
+impl Durations {
+    /// Record that `action` on a resource ran from `started` until `finished`
+    pub(crate) fn record(
+        &mut self,
+        stack_name: &str,
+        logical_id: &str,
+        resource_type: &str,
+        action: &str,
+        started: DateTime<Utc>,
+        finished: DateTime<Utc>,
+    ) {
+        let resource = self
+            .stacks
+            .entry(stack_name.to_string())
+            .or_default()
+            .entry(logical_id.to_string())
+            .or_default();
+        resource.resource_type = resource_type.to_string();
+        let samples = resource.samples.entry(action.to_string()).or_default();
+        if samples.iter().any(|s| s.finished == finished.timestamp()) {
+            return;
+        }
+
+        samples.push(Sample {
+            finished: finished.timestamp(),
+            seconds: (finished - started).num_seconds(),
+        });
+        samples.sort_by_key(|s| s.finished);
+        if samples.len() > MAX_SAMPLES {
+            samples.drain(..samples.len() - MAX_SAMPLES);
+        }
+    }
+
+    /// How long `action` on a resource usually takes, falling back to other
+    /// resources of the same type if it has not been seen before
+    pub(crate) fn expected(
+        &self,
+        stack_name: &str,
+        logical_id: &str,
+        resource_type: &str,
+        action: &str,
+    ) -> Option<Duration> {
+        let seconds = |resource: &ResourceDurations| -> Vec<i64> {
+            resource
+                .samples
+                .get(action)
+                .map(|samples| samples.iter().map(|s| s.seconds).collect())
+                .unwrap_or_default()
+        };
+
+        let resource = self
+            .stacks
+            .get(stack_name)
+            .and_then(|resources| resources.get(logical_id))
+            .filter(|resource| resource.resource_type == resource_type);
+        if let Some(expected) = resource.and_then(|r| median(seconds(r))) {
+            return Some(expected);
+        }
+
+        let same_type = self
+            .stacks
+            .values()
+            .flat_map(|resources| resources.values())
+            .filter(|resource| resource.resource_type == resource_type)
+            .flat_map(seconds)
+            .collect();
+        median(same_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_expected_durations() {
+        let start = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        let distribution = "AWS::CloudFront::Distribution";
+        let mut durations = Durations::default();
+        for minutes in [5, 6, 9].iter() {
+            let started = start + Duration::hours(*minutes);
+            let finished = started + Duration::minutes(*minutes);
+            durations.record("web", "Cdn", distribution, "CREATE", started, finished);
+            // seeing the same event again does not add a sample
+            durations.record("web", "Cdn", distribution, "CREATE", started, finished);
+        }
+
+        assert_eq!(
+            durations.expected("web", "Cdn", distribution, "CREATE"),
+            Some(Duration::minutes(6))
+        );
+        assert_eq!(
+            durations.expected("web", "Cdn", distribution, "DELETE"),
+            None
+        );
+        // other distributions are expected to take as long
+        assert_eq!(
+            durations.expected("other", "Assets", distribution, "CREATE"),
+            Some(Duration::minutes(6))
+        );
+        assert_eq!(
+            durations.expected("web", "Bucket", "AWS::S3::Bucket", "CREATE"),
+            None
+        );
+
+        assert!(is_unusually_slow(
+            Duration::minutes(19),
+            Duration::minutes(6)
+        ));
+        assert!(!is_unusually_slow(
+            Duration::minutes(17),
+            Duration::minutes(6)
+        ));
+        // quick resources are not slow after a few seconds more
+        assert!(!is_unusually_slow(
+            Duration::seconds(40),
+            Duration::seconds(10)
+        ));
+    }
+}
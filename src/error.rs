@@ -4,6 +4,7 @@ use serde::Deserialize;
 use std::str::FromStr;
 
 #[derive(thiserror::Error, Debug)]
+#[allow(dead_code)]
 pub(crate) enum Error<E> {
     #[error("error parsing --since argument")]
     ParseSince,
@@ -0,0 +1,79 @@
+use eyre::{Result, WrapErr};
+use std::process::{ExitStatus, Stdio};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+
+/// Spawn the command that performs the deployment
+///
+/// Its output is passed straight through, unless `prefix` is given in which
+/// case each line is sent after the prefix over the returned channel, to be
+/// printed along with the events.
+pub(crate) fn spawn(
+    command: &[String],
+    prefix: Option<&str>,
+) -> Result<(Child, Option<mpsc::UnboundedReceiver<String>>)> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| eyre::eyre!("no command given to run"))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if prefix.is_some() {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+
+    tracing::debug!(?command, "spawning command");
+    let mut child = cmd
+        .spawn()
+        .wrap_err_with(|| format!("running command {}", program))?;
+
+    let Some(prefix) = prefix else {
+        return Ok((child, None));
+    };
+    let (tx, rx) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(forward_lines(stdout, prefix.to_string(), tx.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(forward_lines(stderr, prefix.to_string(), tx));
+    }
+    Ok((child, Some(rx)))
+}
+
+async fn forward_lines(
+    output: impl AsyncRead + Unpin,
+    prefix: String,
+    tx: mpsc::UnboundedSender<String>,
+) {
+    let mut lines = BufReader::new(output).lines();
+    loop {
+        match lines.next_line().await {
+            Ok(Some(line)) => {
+                if tx.send(format!("{}{}", prefix, line)).is_err() {
+                    break;
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                tracing::warn!(err = %e, "reading command output");
+                break;
+            }
+        }
+    }
+}
+
+/// Next line of output from the command, or `None` once it has closed its
+/// output. Never resolves when the output is passed straight through.
+pub(crate) async fn next_line(
+    output: &mut Option<mpsc::UnboundedReceiver<String>>,
+) -> Option<String> {
+    match output {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Exit code of the command, treating termination by a signal as a failure
+pub(crate) fn exit_code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
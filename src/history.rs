@@ -0,0 +1,594 @@
+use aws_sdk_cloudformation::types::{ResourceStatus, StackEvent};
+use aws_smithy_types::DateTime as SmithyDateTime;
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::{DateTime, SecondsFormat, Utc};
+use eyre::{Result, WrapErr};
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use structopt::StructOpt;
+use term_table::{row::Row, Table, TableStyle};
+use termcolor::WriteColor;
+
+use crate::durations::{self, Durations};
+use crate::progress::format_duration;
+
+/// File the history is stored in, within the cftail data directory
+pub(crate) const HISTORY_FILE: &str = "history.sqlite";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    event_id TEXT PRIMARY KEY,
+    stack_id TEXT NOT NULL,
+    stack_name TEXT NOT NULL,
+    logical_id TEXT NOT NULL,
+    physical_id TEXT,
+    resource_type TEXT,
+    status TEXT NOT NULL,
+    status_reason TEXT,
+    timestamp TEXT NOT NULL,
+    client_request_token TEXT
+);
+CREATE INDEX IF NOT EXISTS events_by_stack ON events (stack_id, timestamp);
+
+CREATE TABLE IF NOT EXISTS operations (
+    stack_id TEXT NOT NULL,
+    stack_name TEXT NOT NULL,
+    started TEXT NOT NULL,
+    finished TEXT NOT NULL,
+    status TEXT NOT NULL,
+    client_request_token TEXT,
+    parameters TEXT NOT NULL,
+    outputs TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    template_hash TEXT,
+    PRIMARY KEY (stack_id, started)
+);
+CREATE INDEX IF NOT EXISTS operations_by_name ON operations (stack_name, started);
+
+CREATE TABLE IF NOT EXISTS durations (
+    stack_id TEXT NOT NULL,
+    stack_name TEXT NOT NULL,
+    logical_id TEXT NOT NULL,
+    resource_type TEXT NOT NULL,
+    action TEXT NOT NULL,
+    started TEXT NOT NULL,
+    finished TEXT NOT NULL,
+    PRIMARY KEY (stack_id, logical_id, finished)
+);
+CREATE INDEX IF NOT EXISTS durations_by_resource ON durations (stack_name, logical_id, action, finished);
+";
+
+// timestamps are stored as RFC 3339 strings, which sort in time order
+fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn parse_timestamp(timestamp: &str) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn parse_map(json: &str) -> rusqlite::Result<BTreeMap<String, String>> {
+    serde_json::from_str(json).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+/// List past operations from the local history
+#[derive(Debug, StructOpt)]
+pub(crate) struct HistoryOpts {
+    /// Only show operations on this stack
+    pub(crate) stack: Option<String>,
+
+    /// Number of operations to show
+    #[structopt(long, default_value = "20")]
+    pub(crate) limit: usize,
+}
+
+/// Stack operation that has finished, along with the state of the stack afterwards
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Operation {
+    pub(crate) stack_id: String,
+    pub(crate) stack_name: String,
+    pub(crate) started: DateTime<Utc>,
+    pub(crate) finished: DateTime<Utc>,
+    /// Final status of the stack
+    pub(crate) status: String,
+    pub(crate) client_request_token: Option<String>,
+    pub(crate) parameters: BTreeMap<String, String>,
+    pub(crate) outputs: BTreeMap<String, String>,
+    pub(crate) tags: BTreeMap<String, String>,
+    /// SHA-256 of the template body
+    pub(crate) template_hash: Option<String>,
+}
+
+impl Operation {
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Operation {
+            stack_id: row.get(0)?,
+            stack_name: row.get(1)?,
+            started: parse_timestamp(&row.get::<_, String>(2)?)?,
+            finished: parse_timestamp(&row.get::<_, String>(3)?)?,
+            status: row.get(4)?,
+            client_request_token: row.get(5)?,
+            parameters: parse_map(&row.get::<_, String>(6)?)?,
+            outputs: parse_map(&row.get::<_, String>(7)?)?,
+            tags: parse_map(&row.get::<_, String>(8)?)?,
+            template_hash: row.get(9)?,
+        })
+    }
+}
+
+const OPERATION_COLUMNS: &str = "stack_id, stack_name, started, finished, status, \
+     client_request_token, parameters, outputs, tags, template_hash";
+
+/// Every event that cftail has seen, along with the operations they belong to
+pub(crate) struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .wrap_err_with(|| format!("creating directory {}", dir.display()))?;
+        }
+        let conn = Connection::open(path)
+            .wrap_err_with(|| format!("opening history {}", path.display()))?;
+        Self::new(conn)
+    }
+
+    fn new(conn: Connection) -> Result<Self> {
+        let has_durations: bool = conn
+            .query_row(
+                "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'durations'",
+                [],
+                |row| row.get(0),
+            )
+            .wrap_err("checking history tables")?;
+        conn.execute_batch(SCHEMA)
+            .wrap_err("creating history tables")?;
+        let history = Self { conn };
+        if !has_durations {
+            // histories from before durations were stored keep what they knew
+            for operation in history.operations(None, usize::MAX)? {
+                history.record_durations(&operation)?;
+            }
+        }
+        Ok(history)
+    }
+
+    /// Store events, ignoring any that have been stored already
+    pub(crate) fn record_events<'a>(
+        &mut self,
+        events: impl Iterator<Item = &'a StackEvent>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction().wrap_err("starting transaction")?;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO events (event_id, stack_id, stack_name, logical_id,
+                     physical_id, resource_type, status, status_reason, timestamp,
+                     client_request_token) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )
+                .wrap_err("preparing event insert")?;
+            for event in events {
+                let timestamp = match event.timestamp().map(|t| t.to_chrono_utc()) {
+                    Some(Ok(timestamp)) => timestamp,
+                    _ => continue,
+                };
+                insert
+                    .execute(params![
+                        event.event_id(),
+                        event.stack_id(),
+                        event.stack_name(),
+                        event.logical_resource_id(),
+                        event.physical_resource_id(),
+                        event.resource_type(),
+                        event.resource_status().map(|s| s.as_str()),
+                        event.resource_status_reason(),
+                        format_timestamp(timestamp),
+                        event.client_request_token(),
+                    ])
+                    .wrap_err("storing event")?;
+            }
+        }
+        tx.commit().wrap_err("committing events")
+    }
+
+    pub(crate) fn record_operation(&self, operation: &Operation) -> Result<()> {
+        self.conn
+            .execute(
+                &format!(
+                    "INSERT OR REPLACE INTO operations ({}) VALUES
+                     (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    OPERATION_COLUMNS
+                ),
+                params![
+                    operation.stack_id,
+                    operation.stack_name,
+                    format_timestamp(operation.started),
+                    format_timestamp(operation.finished),
+                    operation.status,
+                    operation.client_request_token,
+                    serde_json::to_string(&operation.parameters)?,
+                    serde_json::to_string(&operation.outputs)?,
+                    serde_json::to_string(&operation.tags)?,
+                    operation.template_hash,
+                ],
+            )
+            .wrap_err("storing operation")?;
+        self.record_durations(operation)
+    }
+
+    // store how long each resource took during the operation, from its events
+    fn record_durations(&self, operation: &Operation) -> Result<()> {
+        let events = self.events(&operation.stack_id, operation.started, operation.finished)?;
+        let mut insert = self
+            .conn
+            .prepare_cached(
+                "INSERT OR IGNORE INTO durations (stack_id, stack_name, logical_id,
+                 resource_type, action, started, finished)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )
+            .wrap_err("preparing duration insert")?;
+        for duration in resource_durations(&events) {
+            insert
+                .execute(params![
+                    operation.stack_id,
+                    operation.stack_name,
+                    duration.logical_id,
+                    duration.resource_type,
+                    duration.action,
+                    format_timestamp(duration.started),
+                    format_timestamp(duration.finished),
+                ])
+                .wrap_err("storing duration")?;
+        }
+        Ok(())
+    }
+
+    /// Events of a stack between two times, oldest first
+    pub(crate) fn events(
+        &self,
+        stack_id: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<StackEvent>> {
+        let mut query = self
+            .conn
+            .prepare(
+                "SELECT event_id, stack_id, stack_name, logical_id, physical_id, resource_type,
+                 status, status_reason, timestamp, client_request_token FROM events
+                 WHERE stack_id = ?1 AND timestamp >= ?2 AND timestamp <= ?3
+                 ORDER BY timestamp, rowid",
+            )
+            .wrap_err("preparing events query")?;
+        let events = query
+            .query_map(
+                params![stack_id, format_timestamp(from), format_timestamp(to)],
+                |row| {
+                    let timestamp = parse_timestamp(&row.get::<_, String>(8)?)?;
+                    Ok(StackEvent::builder()
+                        .event_id(row.get::<_, String>(0)?)
+                        .stack_id(row.get::<_, String>(1)?)
+                        .stack_name(row.get::<_, String>(2)?)
+                        .logical_resource_id(row.get::<_, String>(3)?)
+                        .set_physical_resource_id(row.get(4)?)
+                        .set_resource_type(row.get(5)?)
+                        .resource_status(ResourceStatus::from(row.get::<_, String>(6)?.as_str()))
+                        .set_resource_status_reason(row.get(7)?)
+                        .timestamp(SmithyDateTime::from_millis(timestamp.timestamp_millis()))
+                        .set_client_request_token(row.get(9)?)
+                        .build())
+                },
+            )
+            .wrap_err("querying events")?
+            .collect::<rusqlite::Result<_>>()
+            .wrap_err("reading events")?;
+        Ok(events)
+    }
+
+    /// How long the most recent operations on each resource took, as stored
+    /// when the operations finished
+    pub(crate) fn durations(&self) -> Result<Durations> {
+        let mut query = self
+            .conn
+            .prepare(
+                "SELECT stack_name, logical_id, resource_type, action, started, finished
+                 FROM (SELECT *, row_number() OVER (
+                     PARTITION BY stack_name, logical_id, action ORDER BY finished DESC
+                 ) AS n FROM durations)
+                 WHERE n <= ?1",
+            )
+            .wrap_err("preparing durations query")?;
+        let mut rows = query
+            .query(params![durations::MAX_SAMPLES as i64])
+            .wrap_err("querying durations")?;
+
+        let mut durations = Durations::default();
+        while let Some(row) = rows.next().wrap_err("reading durations")? {
+            let stack_name: String = row.get(0)?;
+            let logical_id: String = row.get(1)?;
+            let resource_type: String = row.get(2)?;
+            let action: String = row.get(3)?;
+            durations.record(
+                &stack_name,
+                &logical_id,
+                &resource_type,
+                &action,
+                parse_timestamp(&row.get::<_, String>(4)?)?,
+                parse_timestamp(&row.get::<_, String>(5)?)?,
+            );
+        }
+        Ok(durations)
+    }
+
+    /// The most recent operations, optionally only on one stack, newest first
+    pub(crate) fn operations(
+        &self,
+        stack_name: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Operation>> {
+        let mut query = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM operations WHERE ?1 IS NULL OR stack_name = ?1
+                 ORDER BY started DESC LIMIT ?2",
+                OPERATION_COLUMNS
+            ))
+            .wrap_err("preparing operations query")?;
+        let operations = query
+            .query_map(params![stack_name, limit as i64], Operation::from_row)
+            .wrap_err("querying operations")?
+            .collect::<rusqlite::Result<_>>()
+            .wrap_err("reading operations")?;
+        Ok(operations)
+    }
+
+    /// The most recent operations on the stack with the given id, newest first
+    pub(crate) fn stack_operations(&self, stack_id: &str, limit: usize) -> Result<Vec<Operation>> {
+        let mut query = self
+            .conn
+            .prepare(&format!(
+                "SELECT {} FROM operations WHERE stack_id = ?1
+                 ORDER BY started DESC LIMIT ?2",
+                OPERATION_COLUMNS
+            ))
+            .wrap_err("preparing operations query")?;
+        let operations = query
+            .query_map(params![stack_id, limit as i64], Operation::from_row)
+            .wrap_err("querying operations")?
+            .collect::<rusqlite::Result<_>>()
+            .wrap_err("reading operations")?;
+        Ok(operations)
+    }
+}
+
+// one change to a resource that completed
+struct ResourceDuration {
+    logical_id: String,
+    resource_type: String,
+    action: String,
+    started: DateTime<Utc>,
+    finished: DateTime<Utc>,
+}
+
+// how long each resource took, from its events between going in progress and
+// completing, given events in time order
+fn resource_durations(events: &[StackEvent]) -> Vec<ResourceDuration> {
+    let mut result = Vec::new();
+    // (action, start) of each resource that is in progress
+    let mut starts: HashMap<&str, (&str, DateTime<Utc>)> = HashMap::new();
+    for event in events {
+        let (Some(logical_id), Some(status), Some(Ok(timestamp))) = (
+            event.logical_resource_id(),
+            event.resource_status().map(|s| s.as_str()),
+            event.timestamp().map(|t| t.to_chrono_utc()),
+        ) else {
+            continue;
+        };
+        if event.stack_name() == Some(logical_id) {
+            continue;
+        }
+        let action = durations::action(status);
+        if status.ends_with("_IN_PROGRESS") {
+            starts.entry(logical_id).or_insert((action, timestamp));
+            continue;
+        }
+        let Some((started_action, started)) = starts.remove(logical_id) else {
+            continue;
+        };
+        if status.ends_with("_COMPLETE") && action == started_action {
+            result.push(ResourceDuration {
+                logical_id: logical_id.to_string(),
+                resource_type: event.resource_type().unwrap_or_default().to_string(),
+                action: action.to_string(),
+                started,
+                finished: timestamp,
+            });
+        }
+    }
+    result
+}
+
+/// Print a table of past operations
+pub(crate) fn print_operations(
+    writer: &mut impl WriteColor,
+    operations: &[Operation],
+) -> Result<()> {
+    if operations.is_empty() {
+        writeln!(writer, "No operations found").wrap_err("printing operations")?;
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.style = TableStyle::thin();
+    table.add_row(Row::new(vec![
+        "Stack", "Started", "Duration", "Status", "Token",
+    ]));
+    for operation in operations {
+        table.add_row(Row::new(vec![
+            operation.stack_name.clone(),
+            operation.started.format("%Y-%m-%d %H:%M:%S").to_string(),
+            format_duration(operation.finished - operation.started),
+            operation.status.clone(),
+            operation.client_request_token.clone().unwrap_or_default(),
+        ]));
+    }
+    writeln!(writer, "{}", table.render()).wrap_err("printing operations")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new(Connection::open_in_memory().unwrap()).unwrap();
+        let started = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        let event = StackEvent::builder()
+            .event_id("event-1")
+            .stack_id("stack-id")
+            .stack_name("my-stack")
+            .logical_resource_id("my-stack")
+            .resource_status(ResourceStatus::UpdateInProgress)
+            .timestamp(SmithyDateTime::from_secs(started.timestamp()))
+            .client_request_token("deploy-1")
+            .build();
+        // events seen again are ignored
+        history
+            .record_events([event.clone(), event.clone()].iter())
+            .unwrap();
+        let count: i64 = history
+            .conn
+            .query_row("SELECT count(*) FROM events", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            history
+                .events("stack-id", started, started + chrono::Duration::minutes(3))
+                .unwrap(),
+            vec![event]
+        );
+
+        let operation = Operation {
+            stack_id: "stack-id".to_string(),
+            stack_name: "my-stack".to_string(),
+            started,
+            finished: started + chrono::Duration::minutes(3),
+            status: "UPDATE_COMPLETE".to_string(),
+            client_request_token: Some("deploy-1".to_string()),
+            parameters: vec![("Environment".to_string(), "prod".to_string())]
+                .into_iter()
+                .collect(),
+            outputs: BTreeMap::new(),
+            tags: BTreeMap::new(),
+            template_hash: Some("abc".to_string()),
+        };
+        history.record_operation(&operation).unwrap();
+
+        assert_eq!(
+            history.operations(Some("my-stack"), 10).unwrap(),
+            vec![operation.clone()]
+        );
+        assert!(history.operations(Some("other"), 10).unwrap().is_empty());
+        assert_eq!(
+            history.stack_operations("stack-id", 10).unwrap(),
+            vec![operation.clone()]
+        );
+        // an earlier stack with the same name
+        assert!(history
+            .stack_operations("old-stack-id", 10)
+            .unwrap()
+            .is_empty());
+        assert_eq!(history.operations(None, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_durations() {
+        let mut history = History::new(Connection::open_in_memory().unwrap()).unwrap();
+        let started = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        let event = |id: &str, logical_id: &str, status: ResourceStatus, minutes: i64| {
+            StackEvent::builder()
+                .event_id(id)
+                .stack_id("stack-id")
+                .stack_name("web")
+                .logical_resource_id(logical_id)
+                .resource_type("AWS::CloudFront::Distribution")
+                .resource_status(status)
+                .timestamp(SmithyDateTime::from_secs(
+                    started.timestamp() + minutes * 60,
+                ))
+                .build()
+        };
+        history
+            .record_events(
+                [
+                    event("1", "web", ResourceStatus::CreateInProgress, 0),
+                    event("2", "Cdn", ResourceStatus::CreateInProgress, 0),
+                    event("3", "Cdn", ResourceStatus::CreateInProgress, 1),
+                    event("4", "Cdn", ResourceStatus::CreateComplete, 6),
+                    event("5", "web", ResourceStatus::CreateComplete, 7),
+                    // a failed update is not a sample
+                    event("6", "web", ResourceStatus::UpdateInProgress, 10),
+                    event("7", "Cdn", ResourceStatus::UpdateInProgress, 10),
+                    event("8", "Cdn", ResourceStatus::UpdateFailed, 12),
+                    event("9", "web", ResourceStatus::UpdateRollbackComplete, 15),
+                    // only operations that have finished are used
+                    event("10", "web", ResourceStatus::DeleteInProgress, 20),
+                    event("11", "Cdn", ResourceStatus::DeleteInProgress, 20),
+                    event("12", "Cdn", ResourceStatus::DeleteComplete, 30),
+                ]
+                .iter(),
+            )
+            .unwrap();
+        let operation = |minutes: i64, finished: i64, status: &str| Operation {
+            stack_id: "stack-id".to_string(),
+            stack_name: "web".to_string(),
+            started: started + chrono::Duration::minutes(minutes),
+            finished: started + chrono::Duration::minutes(finished),
+            status: status.to_string(),
+            client_request_token: None,
+            parameters: BTreeMap::new(),
+            outputs: BTreeMap::new(),
+            tags: BTreeMap::new(),
+            template_hash: None,
+        };
+        history
+            .record_operation(&operation(0, 7, "CREATE_COMPLETE"))
+            .unwrap();
+        history
+            .record_operation(&operation(10, 15, "UPDATE_ROLLBACK_COMPLETE"))
+            .unwrap();
+
+        let distribution = "AWS::CloudFront::Distribution";
+        let durations = history.durations().unwrap();
+        assert_eq!(
+            durations.expected("web", "Cdn", distribution, "CREATE"),
+            Some(chrono::Duration::minutes(6))
+        );
+        assert_eq!(
+            durations.expected("web", "Cdn", distribution, "UPDATE"),
+            None
+        );
+        assert_eq!(
+            durations.expected("web", "Cdn", distribution, "DELETE"),
+            None
+        );
+
+        // histories from before durations were stored are filled in
+        history.conn.execute_batch("DROP TABLE durations").unwrap();
+        let history = History::new(history.conn).unwrap();
+        assert_eq!(
+            history
+                .durations()
+                .unwrap()
+                .expected("web", "Cdn", distribution, "CREATE"),
+            Some(chrono::Duration::minutes(6))
+        );
+    }
+}
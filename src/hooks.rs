@@ -0,0 +1,176 @@
+use aws_sdk_cloudformation::types::StackEvent;
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::webhook::{Payload, WebhookEvent};
+
+/// Shell commands to run as a deployment progresses
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) on_complete: Option<String>,
+    pub(crate) on_failure: Option<String>,
+    pub(crate) on_event: Option<String>,
+}
+
+// event as passed to the `--on-event` hook
+#[derive(Debug, Serialize)]
+struct EventPayload<'e> {
+    stack_id: Option<&'e str>,
+    stack_name: Option<&'e str>,
+    logical_id: Option<&'e str>,
+    physical_id: Option<&'e str>,
+    resource_type: Option<&'e str>,
+    status: Option<&'e str>,
+    reason: Option<&'e str>,
+    timestamp: Option<String>,
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+// run the command with `input` on stdin, warning if it fails
+async fn run(command: &str, input: &str, env: Vec<(&str, String)>) -> Result<()> {
+    tracing::debug!(%command, "running hook");
+    let mut child = shell(command)
+        .envs(env)
+        .stdin(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| format!("running hook {}", command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command does not have to read its input
+        if let Err(e) = stdin.write_all(input.as_bytes()).await {
+            tracing::debug!(err = %e, "writing hook input");
+        }
+    }
+    let status = child
+        .wait()
+        .await
+        .wrap_err_with(|| format!("waiting for hook {}", command))?;
+    if !status.success() {
+        tracing::warn!(%command, %status, "hook failed");
+    }
+    Ok(())
+}
+
+impl Hooks {
+    /// Whether a hook needs the result of each operation
+    pub(crate) fn on_result(&self) -> bool {
+        self.on_complete.is_some() || self.on_failure.is_some()
+    }
+
+    /// Run the `--on-event` hook for an event
+    pub(crate) async fn event(&self, event: &StackEvent) -> Result<()> {
+        let Some(command) = &self.on_event else {
+            return Ok(());
+        };
+        let payload = EventPayload {
+            stack_id: event.stack_id(),
+            stack_name: event.stack_name(),
+            logical_id: event.logical_resource_id(),
+            physical_id: event.physical_resource_id(),
+            resource_type: event.resource_type(),
+            status: event.resource_status().map(|s| s.as_str()),
+            reason: event.resource_status_reason(),
+            timestamp: event
+                .timestamp()
+                .and_then(|t| t.to_chrono_utc().ok())
+                .map(|t| t.to_rfc3339()),
+        };
+        let input = serde_json::to_string(&payload).wrap_err("serializing event")?;
+        let env = vec![
+            ("CFTAIL_HOOK", "event".to_string()),
+            (
+                "CFTAIL_STACK",
+                payload.stack_name.unwrap_or_default().to_string(),
+            ),
+            (
+                "CFTAIL_STACK_ID",
+                payload.stack_id.unwrap_or_default().to_string(),
+            ),
+            (
+                "CFTAIL_RESOURCE",
+                payload.logical_id.unwrap_or_default().to_string(),
+            ),
+            (
+                "CFTAIL_RESOURCE_TYPE",
+                payload.resource_type.unwrap_or_default().to_string(),
+            ),
+            (
+                "CFTAIL_STATUS",
+                payload.status.unwrap_or_default().to_string(),
+            ),
+            (
+                "CFTAIL_REASON",
+                payload.reason.unwrap_or_default().to_string(),
+            ),
+            (
+                "CFTAIL_TIMESTAMP",
+                payload.timestamp.clone().unwrap_or_default(),
+            ),
+        ];
+        run(command, &input, env).await
+    }
+
+    /// Run the `--on-complete` or `--on-failure` hook with the result of an
+    /// operation
+    pub(crate) async fn result(&self, payload: &Payload) -> Result<()> {
+        let (name, command) = match payload.event {
+            WebhookEvent::Complete => ("complete", &self.on_complete),
+            WebhookEvent::Failure => ("failure", &self.on_failure),
+            WebhookEvent::RollbackStarted => return Ok(()),
+        };
+        let Some(command) = command else {
+            return Ok(());
+        };
+        let input = serde_json::to_string(payload).wrap_err("serializing result")?;
+        let env = vec![
+            ("CFTAIL_HOOK", name.to_string()),
+            ("CFTAIL_STACK", payload.stack.clone()),
+            ("CFTAIL_STATUS", payload.status.clone()),
+            ("CFTAIL_DURATION", payload.duration_seconds.to_string()),
+        ];
+        run(command, &input, env).await
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::report::tests::failed_update;
+
+    #[tokio::test]
+    async fn test_result_hook() {
+        let path = std::env::temp_dir().join(format!("cftail-hook-test-{}", std::process::id()));
+        let hooks = Hooks {
+            on_failure: Some(format!(
+                "printf '%s %s ' \"$CFTAIL_STACK\" \"$CFTAIL_DURATION\" > {path}; cat >> {path}",
+                path = path.display()
+            )),
+            ..Hooks::default()
+        };
+        let payload = Payload::new(WebhookEvent::Failure, &failed_update());
+        hooks.result(&payload).await.unwrap();
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (env, input) = output.split_at("my-stack 100 ".len());
+        assert_eq!(env, "my-stack 100 ");
+        let input: serde_json::Value = serde_json::from_str(input).unwrap();
+        assert_eq!(input["status"], "UPDATE_ROLLBACK_COMPLETE");
+    }
+}
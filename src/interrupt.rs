@@ -0,0 +1,159 @@
+use eyre::{Result, WrapErr};
+use std::io::IsTerminal;
+use termcolor::WriteColor;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Signal that interrupted cftail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signal {
+    /// Ctrl-C
+    Interrupt,
+    Terminate,
+}
+
+impl Signal {
+    /// Exit code of a process killed by the signal, as shells report it
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Signal::Interrupt => 130,
+            Signal::Terminate => 143,
+        }
+    }
+}
+
+/// Listens for SIGINT and SIGTERM, which no longer stop the process once this
+/// is created
+pub(crate) struct Interrupts {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(not(unix))]
+    ctrl_c: tokio::signal::windows::CtrlC,
+}
+
+impl Interrupts {
+    #[cfg(unix)]
+    pub(crate) fn new() -> Result<Self> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt()).wrap_err("listening for SIGINT")?,
+            terminate: signal(SignalKind::terminate()).wrap_err("listening for SIGTERM")?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn new() -> Result<Self> {
+        Ok(Self {
+            ctrl_c: tokio::signal::windows::ctrl_c().wrap_err("listening for Ctrl-C")?,
+        })
+    }
+
+    /// Wait for the next signal, including any that arrived since the last
+    /// call
+    #[cfg(unix)]
+    pub(crate) async fn recv(&mut self) -> Signal {
+        tokio::select! {
+            _ = self.interrupt.recv() => Signal::Interrupt,
+            _ = self.terminate.recv() => Signal::Terminate,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) async fn recv(&mut self) -> Signal {
+        self.ctrl_c.recv().await;
+        Signal::Interrupt
+    }
+}
+
+/// What can be done about the deployment once interrupted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Options {
+    /// Whether any of the stacks is still deploying
+    pub(crate) in_progress: bool,
+    pub(crate) can_cancel: bool,
+    pub(crate) can_continue_rollback: bool,
+}
+
+/// What to do once interrupted during a deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Choice {
+    CancelUpdate,
+    ContinueRollback,
+    Watch,
+    Detach,
+}
+
+// the choice for an answer to the prompt, if it is one of those offered
+fn parse_choice(answer: &str, can_cancel: bool, can_continue_rollback: bool) -> Option<Choice> {
+    match answer.trim() {
+        "" | "d" | "D" => Some(Choice::Detach),
+        "w" | "W" => Some(Choice::Watch),
+        "c" | "C" if can_cancel => Some(Choice::CancelUpdate),
+        "r" | "R" if can_continue_rollback => Some(Choice::ContinueRollback),
+        _ => None,
+    }
+}
+
+/// Ask what to do after Ctrl-C, detaching when not in a terminal or when
+/// interrupted again
+pub(crate) async fn prompt(
+    writer: &mut impl WriteColor,
+    interrupts: &mut Interrupts,
+    can_cancel: bool,
+    can_continue_rollback: bool,
+) -> Result<Choice> {
+    if !(std::io::stdin().is_terminal() && std::io::stdout().is_terminal()) {
+        return Ok(Choice::Detach);
+    }
+    let mut options = Vec::new();
+    if can_cancel {
+        options.push("[c]ancel the update");
+    }
+    if can_continue_rollback {
+        options.push("[r]etry the rollback");
+    }
+    options.push("keep [w]atching");
+    options.push("[D]etach");
+
+    let mut stdin = BufReader::new(tokio::io::stdin());
+    loop {
+        write!(writer, "{}? ", options.join(", ")).wrap_err("printing prompt")?;
+        writer.flush().wrap_err("flushing prompt")?;
+        let mut answer = String::new();
+        tokio::select! {
+            res = stdin.read_line(&mut answer) => {
+                // stdin has been closed
+                if res.wrap_err("reading answer")? == 0 {
+                    return Ok(Choice::Detach);
+                }
+            }
+            _ = interrupts.recv() => {
+                writeln!(writer).wrap_err("printing prompt")?;
+                return Ok(Choice::Detach);
+            }
+        }
+        if let Some(choice) = parse_choice(&answer, can_cancel, can_continue_rollback) {
+            return Ok(choice);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("\n", true, false), Some(Choice::Detach));
+        assert_eq!(parse_choice("c\n", true, false), Some(Choice::CancelUpdate));
+        // only the options that were offered can be picked
+        assert_eq!(parse_choice("c\n", false, true), None);
+        assert_eq!(
+            parse_choice("r\n", false, true),
+            Some(Choice::ContinueRollback)
+        );
+        assert_eq!(parse_choice("w", false, false), Some(Choice::Watch));
+    }
+}
@@ -1,29 +1,93 @@
-use aws_config::BehaviorVersion;
+use aws_config::{sts::AssumeRoleProvider, BehaviorVersion, Region};
 use chrono::{prelude::*, Duration as ChronoDuration};
 use eyre::{Result, WrapErr};
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 use structopt::StructOpt;
 use termcolor::{ColorChoice, StandardStream};
-use tokio::time::sleep;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{sleep, sleep_until, Instant};
 
+use aws_sdk_cloudformation::types::StackSetOperationStatus;
 use aws_sdk_cloudformation::Client;
 
+use crate::aws::Clients;
+
+mod accounts;
 mod aws;
+mod cdk;
+mod change_sets;
+mod ci;
+mod config;
+mod deploy;
+mod diff;
+mod durations;
 mod error;
+mod exec;
+mod history;
+mod hooks;
+mod interrupt;
 mod nested_stacks;
+mod progress;
+mod report;
+mod stack_sets;
 mod stack_status;
 mod stacks;
 mod tail;
-mod utils;
+mod template;
+mod trace;
+mod webhook;
 mod writer;
 
+use crate::cdk::CdkStack;
+use crate::change_sets::{ChangeSet, ChangeSetOutcome};
+use crate::ci::CiPlatform;
+use crate::config::{Config, StackEntry};
+use crate::deploy::DeployOpts;
+use crate::diff::DiffOpts;
 use crate::error::Error;
-use crate::stacks::build_stack_list;
-use crate::tail::{Tail, TailConfig};
+use crate::history::{History, HistoryOpts};
+use crate::hooks::Hooks;
+use crate::interrupt::Interrupts;
+use crate::report::ReportFormat;
+use crate::stack_sets::{InstanceRole, StackSetWatcher};
+use crate::stacks::{
+    build_stack_list, check_region, expand_regions, Credentials, Location, StackInfo, StackTarget,
+};
+use crate::tail::{Outcome, Tail, TailConfig};
+use crate::template::Template;
+use crate::webhook::Webhook;
 use crate::writer::Writer;
 
+// system sounds that are available without any configuration
+#[cfg(target_os = "macos")]
+const DEFAULT_FAILURE_SOUND: &str = "Basso";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_FAILURE_SOUND: &str = "dialog-error";
+
+// how long resources can go without new events before cftail warns about them
+const DEFAULT_STUCK_AFTER: Duration = Duration::from_secs(10 * 60);
+
+// value of a pair of flags turning a setting on and off, if either was given
+fn flag(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_duration_setting(name: &str, value: Option<&str>) -> Result<Option<Duration>> {
+    value
+        .map(humantime::parse_duration)
+        .transpose()
+        .wrap_err_with(|| format!("parsing {} from config file", name))
+}
+
 // Custom parser for parsing the datetime as either a timestamp, or as a handy string.
 fn parse_since_argument(src: &str) -> Result<DateTime<Utc>> {
     // Try to parse as relative offset
@@ -38,24 +102,31 @@ fn parse_since_argument(src: &str) -> Result<DateTime<Utc>> {
     }
 
     // Try to parse as naive datetime (and assume UTC)
-    if let Ok(dt) = NaiveDateTime::from_str(src).map(|n| DateTime::<Utc>::from_utc(n, Utc)) {
+    if let Ok(dt) = NaiveDateTime::from_str(src).map(|n| Utc.from_utc_datetime(&n)) {
         return Ok(dt);
     }
 
     // Try to parse as timestamp
-    if let Ok(dt) = src.parse::<i64>().map(|i| Utc.timestamp(i, 0)) {
+    if let Some(dt) = src
+        .parse::<i64>()
+        .ok()
+        .and_then(|i| Utc.timestamp_opt(i, 0).single())
+    {
         return Ok(dt);
     }
 
     // some common terms
     if src == "today" {
-        let today = Utc::today();
-        let dt = today.and_hms(0, 0, 0);
-        return Ok(dt);
+        let today = Utc::now().date_naive();
+        return today
+            .and_hms_opt(0, 0, 0)
+            .map(|dt| Utc.from_utc_datetime(&dt))
+            .ok_or(eyre::eyre!("invalid time"));
     } else if src == "yesterday" {
-        let yesterday = Utc::today() - ChronoDuration::days(1);
+        let yesterday = Utc::now().date_naive() - ChronoDuration::days(1);
         return yesterday
             .and_hms_opt(0, 0, 0)
+            .map(|dt| Utc.from_utc_datetime(&dt))
             .ok_or(eyre::eyre!("invalid time"));
     }
 
@@ -68,9 +139,15 @@ fn parse_since_argument(src: &str) -> Result<DateTime<Utc>> {
 #[derive(StructOpt)]
 #[structopt(author = "Simon Walker")]
 struct Opts {
-    /// Name of the stacks to tail
+    /// Name of the stacks to tail, optionally prefixed with a profile or IAM
+    /// role arn and region as `[profile:][region:]stack-name`, or `@group` to
+    /// tail a group of stacks from the configuration file
     stack_names: Vec<String>,
 
+    /// Tail each stack without an explicit region in all of these regions
+    #[structopt(long, use_delimiter = true)]
+    regions: Vec<String>,
+
     /// When to start fetching data from. This could be a timestamp, text
     /// string, a relative offset like 10s, 10m, or the words `today` or
     /// `yesterday`
@@ -78,49 +155,678 @@ struct Opts {
     since: Option<DateTime<Utc>>,
 
     /// Also fetch nested stacks and their deploy status
-    #[structopt(short, long)]
+    #[structopt(short, long, overrides_with = "no-nested")]
     nested: bool,
 
+    /// Do not fetch nested stacks, overriding the configuration file
+    #[structopt(long, overrides_with = "nested")]
+    no_nested: bool,
+
     /// Do not print stack separators
-    #[structopt(long)]
+    #[structopt(long, overrides_with = "show-separators")]
     no_show_separators: bool,
 
+    /// Print stack separators, overriding the configuration file
+    #[structopt(long, overrides_with = "no-show-separators")]
+    show_separators: bool,
+
     // Do not show notifications
-    #[structopt(long)]
+    #[structopt(long, overrides_with = "show-notifications")]
     no_show_notifications: bool,
 
+    /// Show notifications, overriding the configuration file
+    #[structopt(long, overrides_with = "no-show-notifications")]
+    show_notifications: bool,
+
     // Do not print stack outputs on completion
-    #[structopt(long)]
+    #[structopt(long, overrides_with = "show-outputs")]
     no_show_outputs: bool,
 
+    /// Print stack outputs on completion, overriding the configuration file
+    #[structopt(long, overrides_with = "no-show-outputs")]
+    show_outputs: bool,
+
     // Do not show resource types
-    #[structopt(long)]
+    #[structopt(long, overrides_with = "show-resource-types")]
     no_show_resource_types: bool,
 
+    /// Show resource types, overriding the configuration file
+    #[structopt(long, overrides_with = "no-show-resource-types")]
+    show_resource_types: bool,
+
+    /// Show the CDK construct path of each resource next to its logical id.
+    /// Enabled by `--cdk-out`
+    #[structopt(long, overrides_with = "no-construct-paths")]
+    construct_paths: bool,
+
+    /// Do not show construct paths, overriding the configuration file
+    #[structopt(long, overrides_with = "construct-paths")]
+    no_construct_paths: bool,
+
+    /// Do not show the progress of the deployment
+    #[structopt(long, overrides_with = "show-progress")]
+    no_show_progress: bool,
+
+    /// Show the progress of the deployment, overriding the configuration file
+    #[structopt(long, overrides_with = "no-show-progress")]
+    show_progress: bool,
+
     // Do not exit on completion
-    #[structopt(long)]
+    #[structopt(long, overrides_with = "exit-on-completion")]
     no_exit_on_completion: bool,
 
-    // Sound to play
-    #[structopt(long, default_value = "Ping")]
-    sound: String,
+    /// Exit on completion, overriding the configuration file
+    #[structopt(long, overrides_with = "no-exit-on-completion")]
+    exit_on_completion: bool,
+
+    /// Do not record events and operations in the local history, or estimate
+    /// how long resources will take from past deployments
+    #[structopt(long, overrides_with = "history")]
+    no_history: bool,
+
+    /// Use the local history, overriding the configuration file
+    #[structopt(long, overrides_with = "no-history")]
+    history: bool,
+
+    /// Warn when resources have been in progress with no new events for this
+    /// long, e.g. `10m`. `0s` turns the warning off [default: 10m]
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    stuck_after: Option<std::time::Duration>,
+
+    /// Write the span of each resource to this file in the Chrome Trace Event
+    /// Format, to open in `chrome://tracing` or Perfetto
+    #[structopt(long, parse(from_os_str))]
+    trace_out: Option<PathBuf>,
+
+    /// Write a report each time a stack finishes deploying, given as a format
+    /// (`markdown`, `html` or `junit`) and a file. Can be given more than once
+    #[structopt(long, number_of_values = 2, value_names = &["format", "file"])]
+    report: Vec<String>,
+
+    #[structopt(skip)]
+    reports: Vec<(ReportFormat, PathBuf)>,
+
+    /// Give up if the stacks have not finished deploying within this long,
+    /// e.g. `30m`, exiting with code 3
+    #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+    timeout: Option<std::time::Duration>,
+
+    /// Cancel updates that are still in progress when `--timeout` passes, and
+    /// tail the rollback before exiting
+    #[structopt(long, overrides_with = "no-cancel-on-timeout")]
+    cancel_on_timeout: bool,
+
+    /// Do not cancel updates at the timeout, overriding the configuration file
+    #[structopt(long, overrides_with = "cancel-on-timeout")]
+    no_cancel_on_timeout: bool,
+
+    /// Produce native output for a CI service (`github` or `gitlab`), which
+    /// is detected from the environment by default
+    #[structopt(long)]
+    ci: Option<CiPlatform>,
+
+    /// URL to post the result of each deployment to, when it completes or
+    /// fails. Can be given more than once
+    #[structopt(long)]
+    webhook: Vec<String>,
+
+    /// minijinja template for the JSON body of webhooks, which is otherwise
+    /// the result of the deployment as JSON
+    #[structopt(long, parse(from_os_str))]
+    webhook_template: Option<PathBuf>,
+
+    /// Also post to webhooks when a stack starts rolling back
+    #[structopt(long, overrides_with = "no-webhook-on-rollback")]
+    webhook_on_rollback: bool,
+
+    /// Do not post to webhooks when a stack starts rolling back, overriding
+    /// the configuration file
+    #[structopt(long, overrides_with = "webhook-on-rollback")]
+    no_webhook_on_rollback: bool,
+
+    #[structopt(skip)]
+    webhooks: Option<Webhook>,
+
+    /// Shell command to run when a stack finishes deploying, with the result
+    /// as JSON on stdin
+    #[structopt(long)]
+    on_complete: Option<String>,
+
+    /// Shell command to run when a stack rolls back or fails, with the result
+    /// as JSON on stdin
+    #[structopt(long)]
+    on_failure: Option<String>,
+
+    /// Shell command to run for each new event, with the event as JSON on stdin
+    #[structopt(long)]
+    on_event: Option<String>,
+
+    // Sound to play [default: Ping]
+    #[structopt(long)]
+    sound: Option<String>,
+
+    // Sound to play when a stack fails or starts rolling back [default: Basso
+    // on macOS, dialog-error elsewhere]
+    #[structopt(long)]
+    failure_sound: Option<String>,
 
     /// Local enpdoint url
     #[structopt(long)]
     endpoint_url: Option<String>,
+
+    /// Local template of the stacks, as `file` or `stack-name=file`, to show
+    /// where failed resources are defined
+    #[structopt(long = "template")]
+    template_files: Vec<String>,
+
+    // templates loaded from `template_files`, keyed by stack name
+    #[structopt(skip)]
+    templates: HashMap<String, Template>,
+
+    /// Also tail the stacks of this AWS CDK cloud assembly, usually `cdk.out`
+    #[structopt(long, parse(from_os_str))]
+    cdk_out: Option<PathBuf>,
+
+    /// Configuration file to use instead of `~/.config/cftail/config.toml`
+    /// and `.cftail.toml`
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Print the resolved configuration and exit
+    #[structopt(long)]
+    show_config: bool,
+
+    /// Follow an operation on this StackSet instead of tailing stacks. The
+    /// StackSet can be prefixed with a profile and region like stack names
+    #[structopt(long)]
+    stack_set: Option<StackTarget>,
+
+    /// StackSet operation to follow, defaults to the most recent operation
+    #[structopt(long, requires = "stack-set")]
+    operation_id: Option<String>,
+
+    /// Also tail the stack events of each StackSet instance
+    #[structopt(long, requires = "stack-set")]
+    stack_set_instances: bool,
+
+    /// Role to assume in each account when tailing StackSet instances, e.g.
+    /// `AWSCloudFormationStackSetExecutionRole`
+    #[structopt(long, requires = "stack-set-instances")]
+    stack_set_role_name: Option<String>,
+
+    /// Show this change set, then tail its execution. Either a change set arn,
+    /// or a change set name along with the name of its stack
+    #[structopt(long)]
+    change_set: Option<String>,
+
+    /// Run the command given after `--`, e.g. `cftail --exec my-stack -- cdk
+    /// deploy`, tailing the stacks from when it starts until it exits
+    #[structopt(long)]
+    exec: bool,
+
+    /// Print each line of output from the `--exec` command after this prefix,
+    /// instead of passing it through unchanged
+    #[structopt(long, requires = "exec")]
+    exec_prefix: Option<String>,
+
+    /// Command to run with `--exec`
+    #[structopt(last = true)]
+    exec_command: Vec<String>,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    Deploy(DeployOpts),
+    Diff(DiffOpts),
+    History(HistoryOpts),
+}
+
+impl Opts {
+    // fill in anything not given on the command line from the configuration file
+    fn apply_config(&mut self, config: &Config) -> Result<()> {
+        if self.stack_names.is_empty() {
+            if let Some(stacks) = &config.stacks {
+                for entry in stacks {
+                    let name = match entry {
+                        StackEntry::Target(name) => name.clone(),
+                        entry => entry.to_target()?.to_string(),
+                    };
+                    self.stack_names.push(name);
+                }
+            }
+        }
+        if self.regions.is_empty() {
+            self.regions = config.regions.clone().unwrap_or_default();
+        }
+        for region in &self.regions {
+            check_region(region).wrap_err("invalid --regions")?;
+        }
+        if self.since.is_none() {
+            self.since = config
+                .since
+                .as_deref()
+                .map(parse_since_argument)
+                .transpose()
+                .wrap_err("parsing since from config file")?;
+        }
+        // flags given on the command line, either way, take precedence
+        self.nested = flag(self.nested, self.no_nested)
+            .or(config.nested)
+            .unwrap_or(false);
+        self.no_show_separators = flag(self.no_show_separators, self.show_separators)
+            .or(config.no_show_separators)
+            .unwrap_or(false);
+        self.no_show_notifications = flag(self.no_show_notifications, self.show_notifications)
+            .or(config.no_show_notifications)
+            .unwrap_or(false);
+        self.no_show_outputs = flag(self.no_show_outputs, self.show_outputs)
+            .or(config.no_show_outputs)
+            .unwrap_or(false);
+        self.no_show_resource_types = flag(self.no_show_resource_types, self.show_resource_types)
+            .or(config.no_show_resource_types)
+            .unwrap_or(false);
+        self.construct_paths = flag(self.construct_paths, self.no_construct_paths)
+            .or(config.construct_paths)
+            .unwrap_or(false);
+        self.no_show_progress = flag(self.no_show_progress, self.show_progress)
+            .or(config.no_show_progress)
+            .unwrap_or(false);
+        self.no_exit_on_completion = flag(self.no_exit_on_completion, self.exit_on_completion)
+            .or(config.no_exit_on_completion)
+            .unwrap_or(false);
+        self.no_history = flag(self.no_history, self.history)
+            .or(config.no_history)
+            .unwrap_or(false);
+        self.cancel_on_timeout = flag(self.cancel_on_timeout, self.no_cancel_on_timeout)
+            .or(config.cancel_on_timeout)
+            .unwrap_or(false);
+        self.webhook_on_rollback = flag(self.webhook_on_rollback, self.no_webhook_on_rollback)
+            .or(config.webhook_on_rollback)
+            .unwrap_or(false);
+
+        if self.stuck_after.is_none() {
+            self.stuck_after =
+                parse_duration_setting("stuck-after", config.stuck_after.as_deref())?;
+        }
+        if self.timeout.is_none() {
+            self.timeout = parse_duration_setting("timeout", config.timeout.as_deref())?;
+        }
+        if self.trace_out.is_none() {
+            self.trace_out = config.trace_out.clone();
+        }
+        if self.report.is_empty() {
+            for (format, file) in config.report.iter().flatten() {
+                self.report.push(format.clone());
+                self.report.push(file.display().to_string());
+            }
+        }
+        if self.ci.is_none() {
+            self.ci = config
+                .ci
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .wrap_err("parsing ci from config file")?;
+        }
+        if self.webhook.is_empty() {
+            self.webhook = config.webhook.clone().unwrap_or_default();
+        }
+        if self.webhook_template.is_none() {
+            self.webhook_template = config.webhook_template.clone();
+        }
+        if self.on_complete.is_none() {
+            self.on_complete = config.on_complete.clone();
+        }
+        if self.on_failure.is_none() {
+            self.on_failure = config.on_failure.clone();
+        }
+        if self.on_event.is_none() {
+            self.on_event = config.on_event.clone();
+        }
+        if self.sound.is_none() {
+            self.sound = config.sound.clone();
+        }
+        if self.failure_sound.is_none() {
+            self.failure_sound = config.failure_sound.clone();
+        }
+        if self.endpoint_url.is_none() {
+            self.endpoint_url = config.endpoint_url.clone();
+        }
+        if self.template_files.is_empty() {
+            self.template_files = config.template.clone().unwrap_or_default();
+        }
+        if self.cdk_out.is_none() {
+            self.cdk_out = config.cdk_out.clone();
+        }
+        if self.stack_set_role_name.is_none() {
+            self.stack_set_role_name = config.stack_set_role_name.clone();
+        }
+        if self.exec_prefix.is_none() {
+            self.exec_prefix = config.exec_prefix.clone();
+        }
+
+        // these can only be checked once the configuration file is taken
+        // into account
+        if self.cancel_on_timeout && self.timeout.is_none() {
+            eyre::bail!("--cancel-on-timeout needs a --timeout");
+        }
+        if self.webhook.is_empty() && (self.webhook_template.is_some() || self.webhook_on_rollback)
+        {
+            eyre::bail!("--webhook-template and --webhook-on-rollback need a --webhook");
+        }
+        Ok(())
+    }
+
+    fn history_path(&self) -> Option<PathBuf> {
+        if self.no_history {
+            return None;
+        }
+        Some(config::data_dir()?.join(history::HISTORY_FILE))
+    }
+
+    fn sound(&self) -> String {
+        self.sound.clone().unwrap_or_else(|| "Ping".to_string())
+    }
+
+    fn failure_sound(&self) -> String {
+        self.failure_sound
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FAILURE_SOUND.to_string())
+    }
+
+    // when `--timeout` passes, counting from now
+    fn deadline(&self) -> Result<Option<DateTime<Utc>>> {
+        let Some(timeout) = self.timeout else {
+            return Ok(None);
+        };
+        let timeout = ChronoDuration::from_std(timeout).wrap_err("invalid --timeout")?;
+        Ok(Some(Utc::now() + timeout))
+    }
+
+    fn tail_config<'a>(&self, since: DateTime<Utc>, stack_info: &'a StackInfo) -> TailConfig<'a> {
+        TailConfig {
+            since,
+            stack_info,
+            show_separators: !self.no_show_separators,
+            show_notifications: !self.no_show_notifications,
+            show_outputs: !self.no_show_outputs,
+            show_resource_types: !self.no_show_resource_types,
+            show_construct_paths: self.construct_paths || self.cdk_out.is_some(),
+            templates: self.templates.clone(),
+            show_progress: !self.no_show_progress && std::io::stdout().is_terminal(),
+            stuck_after: Some(self.stuck_after.unwrap_or(DEFAULT_STUCK_AFTER))
+                .filter(|d| !d.is_zero())
+                .and_then(|d| ChronoDuration::from_std(d).ok()),
+            history_path: self.history_path(),
+            trace_out: self.trace_out.clone(),
+            reports: self.reports.clone(),
+            ci: self.ci.or_else(CiPlatform::detect),
+            webhook: self.webhooks.clone(),
+            hooks: Hooks {
+                on_complete: self.on_complete.clone(),
+                on_failure: self.on_failure.clone(),
+                on_event: self.on_event.clone(),
+            },
+            sound: self.sound(),
+            failure_sound: self.failure_sound(),
+            deadline: None,
+            cancel_on_timeout: self.cancel_on_timeout,
+            should_exit_on_completion: !self.no_exit_on_completion,
+        }
+    }
+
+    // the configuration that is in effect, for `--show-config`
+    fn resolved_config(&self, config: &Config, targets: &[StackTarget]) -> Config {
+        Config {
+            stacks: Some(targets.iter().map(StackEntry::from).collect()),
+            regions: Some(self.regions.clone()),
+            since: self.since.map(|since| since.to_rfc3339()),
+            nested: Some(self.nested),
+            no_show_separators: Some(self.no_show_separators),
+            no_show_notifications: Some(self.no_show_notifications),
+            no_show_outputs: Some(self.no_show_outputs),
+            no_show_resource_types: Some(self.no_show_resource_types),
+            construct_paths: Some(self.construct_paths),
+            no_show_progress: Some(self.no_show_progress),
+            no_exit_on_completion: Some(self.no_exit_on_completion),
+            no_history: Some(self.no_history),
+            stuck_after: Some(
+                humantime::format_duration(self.stuck_after.unwrap_or(DEFAULT_STUCK_AFTER))
+                    .to_string(),
+            ),
+            trace_out: self.trace_out.clone(),
+            report: Some(
+                self.report
+                    .chunks(2)
+                    .filter_map(|pair| match pair {
+                        [format, file] => Some((format.clone(), PathBuf::from(file))),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            timeout: self
+                .timeout
+                .map(|timeout| humantime::format_duration(timeout).to_string()),
+            cancel_on_timeout: Some(self.cancel_on_timeout),
+            ci: self.ci.map(|ci| ci.to_string()),
+            webhook: Some(self.webhook.clone()),
+            webhook_template: self.webhook_template.clone(),
+            webhook_on_rollback: Some(self.webhook_on_rollback),
+            on_complete: self.on_complete.clone(),
+            on_failure: self.on_failure.clone(),
+            on_event: self.on_event.clone(),
+            sound: Some(self.sound()),
+            failure_sound: Some(self.failure_sound()),
+            endpoint_url: self.endpoint_url.clone(),
+            template: Some(self.template_files.clone()),
+            cdk_out: self.cdk_out.clone(),
+            stack_set_role_name: self.stack_set_role_name.clone(),
+            exec_prefix: self.exec_prefix.clone(),
+            groups: config.groups.clone(),
+        }
+    }
 }
 
-async fn create_client(endpoint_url: &Option<String>) -> aws_sdk_cloudformation::Client {
+// templates given with `--template`, along with those of CDK stacks and the
+// stack being deployed
+fn load_templates(
+    opts: &Opts,
+    targets: &[StackTarget],
+    cdk_stacks: &[CdkStack],
+) -> Result<HashMap<String, Template>> {
+    let mut files: Vec<(String, &Path)> = Vec::new();
+    for stack in cdk_stacks {
+        if let Some(file) = &stack.template_file {
+            files.push((stack.target.name.clone(), file));
+        }
+    }
+    if let Some(Command::Deploy(deploy_opts)) = &opts.command {
+        files.push((deploy_opts.stack.name.clone(), &deploy_opts.template_file));
+    }
+    for value in &opts.template_files {
+        match value.split_once('=') {
+            Some((name, file)) if targets.iter().any(|t| t.name == name) => {
+                files.push((name.to_string(), Path::new(file)));
+            }
+            _ => files.extend(
+                targets
+                    .iter()
+                    .map(|t| (t.name.clone(), Path::new(value.as_str()))),
+            ),
+        }
+    }
+
+    let mut templates = HashMap::new();
+    for (name, file) in files {
+        templates.insert(name, Template::from_file(file)?);
+    }
+    Ok(templates)
+}
+
+fn config_loader(endpoint_url: &Option<String>, location: &Location) -> aws_config::ConfigLoader {
     let behaviour_version = BehaviorVersion::latest();
-    let config = if let Some(url) = endpoint_url {
-        aws_config::defaults(behaviour_version)
-            .endpoint_url(url)
-            .load()
-            .await
-    } else {
-        aws_config::load_defaults(behaviour_version).await
+    let mut loader = aws_config::defaults(behaviour_version);
+    if let Some(url) = endpoint_url {
+        loader = loader.endpoint_url(url);
+    }
+    if let Some(region) = &location.region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    loader
+}
+
+async fn create_client(
+    endpoint_url: &Option<String>,
+    location: &Location,
+) -> aws_sdk_cloudformation::Client {
+    Client::new(&load_config(endpoint_url, location).await)
+}
+
+// configuration with the credentials and region of a location
+async fn load_config(endpoint_url: &Option<String>, location: &Location) -> aws_config::SdkConfig {
+    let mut loader = config_loader(endpoint_url, location);
+    match &location.credentials {
+        Some(Credentials::Profile(name)) => {
+            loader = loader.profile_name(name);
+        }
+        Some(Credentials::AssumeRole(role_arn)) => {
+            // assume the role using the default credentials
+            let base_config = config_loader(endpoint_url, location).load().await;
+            let provider = AssumeRoleProvider::builder(role_arn)
+                .session_name("cftail")
+                .configure(&base_config)
+                .build()
+                .await;
+            loader = loader.credentials_provider(provider);
+        }
+        None => {}
+    }
+    loader.load().await
+}
+
+// create one client for each distinct location of the stacks
+async fn create_clients(endpoint_url: &Option<String>, targets: &[StackTarget]) -> Clients {
+    let mut clients = Clients::default();
+    add_clients(&mut clients, endpoint_url, targets).await;
+    clients
+}
+
+// create clients for the locations of the stacks that do not have one yet
+async fn add_clients(
+    clients: &mut Clients,
+    endpoint_url: &Option<String>,
+    targets: &[StackTarget],
+) {
+    for target in targets {
+        if clients.get(&target.location).is_ok() {
+            continue;
+        }
+        let config = load_config(endpoint_url, &target.location).await;
+        clients.insert(target.location.clone(), Arc::new(Client::new(&config)));
+        // show which account the stacks are in when they were tailed with
+        // explicit credentials
+        if target.location.credentials.is_some() {
+            match accounts::account_label(&config).await {
+                Ok(label) => clients.set_label(target.location.clone(), label),
+                Err(e) => {
+                    tracing::warn!(err = %e, location = ?target.location, "looking up account")
+                }
+            }
+        }
+    }
+}
+
+/// How following a StackSet operation ended
+enum StackSetEnd {
+    Finished(StackSetOperationStatus),
+    /// Stopped following before the operation finished
+    Stopped(Outcome),
+}
+
+// follow a StackSet operation until it finishes, optionally tailing the
+// stacks of each instance alongside it
+async fn run_stack_set(
+    opts: &Opts,
+    stack_set: &StackTarget,
+    writer: &mut Writer<'_>,
+) -> Result<StackSetEnd> {
+    // Ctrl-C shows what is going on rather than stopping cftail mid-line
+    let mut interrupts = Interrupts::new()?;
+    let config = load_config(&opts.endpoint_url, &stack_set.location).await;
+    let client = Arc::new(Client::new(&config));
+    let mut watcher =
+        StackSetWatcher::new(client, &stack_set.name, opts.operation_id.clone()).await?;
+
+    if !opts.stack_set_instances {
+        loop {
+            if let Some(status) = watcher.poll_step(writer).await? {
+                return Ok(StackSetEnd::Finished(status));
+            }
+            tokio::select! {
+                _ = sleep(Duration::from_secs(5)) => {}
+                // there are no stacks being tailed to summarise or cancel
+                signal = interrupts.recv() => {
+                    return Ok(StackSetEnd::Stopped(Outcome::Interrupted(signal)));
+                }
+            }
+        }
+    }
+
+    let role = match &opts.stack_set_role_name {
+        Some(name) => Some(InstanceRole {
+            partition: accounts::partition(&config)
+                .await
+                .wrap_err("looking up the partition of the stack set account")?,
+            name: name.clone(),
+        }),
+        None => None,
     };
-    Client::new(&config)
+    let mut since = opts.since.map(Ok).unwrap_or(watcher.start_time().await)?;
+    let deadline = opts.deadline()?;
+    let mut clients = Clients::default();
+    loop {
+        // instances gain a stack as the operation progresses, so rebuild the
+        // tail whenever the set of instance stacks changes, keeping the
+        // clients of the locations seen so far
+        let targets = watcher.instance_targets(role.as_ref()).await?;
+        add_clients(&mut clients, &opts.endpoint_url, &targets).await;
+        let stack_info = build_stack_list(&clients, &targets, opts.nested)
+            .await
+            .wrap_err("building stack list")?;
+        let mut config = opts.tail_config(since, &stack_info);
+        config.deadline = deadline;
+        config.should_exit_on_completion = false;
+        let mut tail = Tail::new(config, clients.clone(), &mut *writer);
+
+        loop {
+            if !targets.is_empty() && !tail.poll_once().await? {
+                tail.finish().await;
+                return Ok(StackSetEnd::Stopped(tail.outcome()));
+            }
+            if let Some(status) = watcher.poll_step(tail.writer_mut()).await? {
+                tail.finish().await;
+                return Ok(StackSetEnd::Finished(status));
+            }
+            since = tail.since();
+
+            tokio::select! {
+                _ = sleep(Duration::from_secs(5)) => {}
+                signal = interrupts.recv() => {
+                    if !tail.interrupted(signal, &mut interrupts).await? {
+                        tail.finish().await;
+                        return Ok(StackSetEnd::Stopped(tail.outcome()));
+                    }
+                }
+            }
+            if watcher.instance_targets(role.as_ref()).await? != targets {
+                tail.finish().await;
+                break;
+            }
+        }
+    }
 }
 
 #[tokio::main]
@@ -128,42 +834,302 @@ async fn main() -> eyre::Result<()> {
     tracing_subscriber::fmt::init();
     color_eyre::install().unwrap();
 
-    let opts = Opts::from_args();
+    let mut opts = Opts::from_args();
+    let config = Config::load(opts.config.as_deref()).wrap_err("loading config file")?;
+    opts.apply_config(&config)?;
+    let mut targets = config.resolve_targets(&opts.stack_names)?;
+    let cdk_stacks = match &opts.cdk_out {
+        Some(dir) => cdk::load_stacks(dir).wrap_err("loading CDK stacks")?,
+        None => Vec::new(),
+    };
+    targets.extend(cdk_stacks.iter().map(|stack| stack.target.clone()));
+    let targets = expand_regions(&targets, &opts.regions);
+    opts.templates = load_templates(&opts, &targets, &cdk_stacks)?;
+    opts.reports = report::parse_reports(&opts.report)?;
+    if !opts.webhook.is_empty() {
+        opts.webhooks = Some(Webhook::new(
+            opts.webhook.clone(),
+            opts.webhook_template.as_deref(),
+            opts.webhook_on_rollback,
+        )?);
+    }
+
+    if opts.show_config {
+        let resolved = opts.resolved_config(&config, &targets);
+        print!(
+            "{}",
+            toml::to_string_pretty(&resolved).wrap_err("serializing config")?
+        );
+        return Ok(());
+    }
+
+    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+    let mut writer = Writer::new(&mut stdout);
+
+    if !cdk_stacks.is_empty() {
+        cdk::print_stacks(&mut writer, &cdk_stacks)?;
+    }
+
+    if let Some(Command::History(history_opts)) = &opts.command {
+        let path = opts
+            .history_path()
+            .ok_or_else(|| eyre::eyre!("history is turned off"))?;
+        let history = History::open(&path)?;
+        let operations = history.operations(history_opts.stack.as_deref(), history_opts.limit)?;
+        return history::print_operations(&mut writer, &operations);
+    }
+
+    if let Some(Command::Diff(diff_opts)) = &opts.command {
+        let history = match opts.history_path() {
+            Some(path) => Some(History::open(&path)?),
+            None => None,
+        };
+        let client = Arc::new(create_client(&opts.endpoint_url, &diff_opts.stack.location).await);
+        return diff::diff(client, history.as_ref(), diff_opts, &mut writer).await;
+    }
+
+    if let Some(stack_set) = &opts.stack_set {
+        return match run_stack_set(&opts, stack_set, &mut writer).await? {
+            StackSetEnd::Finished(StackSetOperationStatus::Succeeded) => Ok(()),
+            StackSetEnd::Finished(status) => {
+                eprintln!("Error: stack set operation {}", status.as_str());
+                std::process::exit(1);
+            }
+            StackSetEnd::Stopped(outcome) => exit_with(outcome),
+        };
+    }
+
+    if let Some(change_set) = &opts.change_set {
+        let (location, stack_name) = match targets.as_slice() {
+            [] if change_set.starts_with("arn:") => {
+                let region = change_set
+                    .split(':')
+                    .nth(3)
+                    .filter(|r| !r.is_empty())
+                    .map(String::from);
+                let location = Location {
+                    credentials: None,
+                    region,
+                };
+                (location, None)
+            }
+            [target] => (target.location.clone(), Some(target.name.clone())),
+            _ => {
+                eprintln!("Error: --change-set needs either a change set arn, or a change set name and a single stack");
+                std::process::exit(1);
+            }
+        };
+
+        let client = Arc::new(create_client(&opts.endpoint_url, &location).await);
+        let change_set = ChangeSet::new(client, change_set, stack_name);
+        let outcome = change_set.follow(&mut writer).await?;
+        return run_change_set(&opts, location, outcome, &mut writer).await;
+    }
+
+    if let Some(Command::Deploy(deploy_opts)) = &opts.command {
+        let location = deploy_opts.stack.location.clone();
+        let client = Arc::new(create_client(&opts.endpoint_url, &location).await);
+        let outcome = deploy::deploy(client, deploy_opts, &mut writer).await?;
+        return run_change_set(&opts, location, outcome, &mut writer).await;
+    }
+
     let since = opts.since.unwrap_or_else(Utc::now);
 
-    tracing::info!(stack_names = ?opts.stack_names, since = %since, nested = ?opts.nested, "tailing stack events");
+    if opts.exec {
+        if opts.exec_command.is_empty() {
+            eprintln!("Error: --exec needs a command to run after --");
+            std::process::exit(1);
+        }
+        let code = run_exec(&opts, &targets, since, &mut writer).await?;
+        std::process::exit(code);
+    }
+
+    tracing::info!(stack_names = ?opts.stack_names, regions = ?opts.regions, since = %since, nested = ?opts.nested, "tailing stack events");
 
-    if opts.stack_names.is_empty() {
+    if targets.is_empty() {
         let mut app = Opts::clap();
         eprintln!("Error: no stacks specified");
         app.print_help().unwrap();
         std::process::exit(1);
     }
 
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    let mut writer = Writer::new(&mut stdout);
+    let outcome = run_tail(&opts, &targets, since, &mut writer).await?;
+    exit_with(outcome)
+}
+
+// run the deploy command while tailing the stacks, returning the child's exit
+// code if it failed and otherwise the outcome of the deployment
+async fn run_exec(
+    opts: &Opts,
+    targets: &[StackTarget],
+    since: DateTime<Utc>,
+    writer: &mut Writer<'_>,
+) -> Result<i32> {
+    // Ctrl-C shows what is going on rather than stopping cftail mid-line
+    let mut interrupts = Interrupts::new()?;
+    let deadline = opts.deadline()?;
+    let (mut child, mut output) = exec::spawn(&opts.exec_command, opts.exec_prefix.as_deref())?;
+    let clients = create_clients(&opts.endpoint_url, targets).await;
+
+    // the command may create the stacks, so wait for them to exist
+    let stack_info = loop {
+        match build_stack_list(&clients, targets, opts.nested).await {
+            Ok(stack_info) => break stack_info,
+            Err(e) => tracing::debug!(err = %e, "stacks not available yet"),
+        }
+        let next_poll = Instant::now() + Duration::from_secs(5);
+        loop {
+            tokio::select! {
+                status = child.wait() => {
+                    let status = status.wrap_err("waiting for command")?;
+                    while let Some(line) = remaining_line(&mut output).await {
+                        writeln!(writer, "{}", line).wrap_err("printing command output")?;
+                    }
+                    return Ok(exec::exit_code(status));
+                }
+                _ = sleep_until(next_poll) => break,
+                line = exec::next_line(&mut output) => match line {
+                    Some(line) => writeln!(writer, "{}", line).wrap_err("printing command output")?,
+                    None => output = None,
+                },
+                // nothing has been tailed yet, so there is nothing to summarise
+                signal = interrupts.recv() => return Ok(signal.exit_code()),
+            }
+        }
+    };
+
+    let mut config = opts.tail_config(since, &stack_info);
+    config.deadline = deadline;
+    config.should_exit_on_completion = false;
+    let mut tail = Tail::new(config, clients, &mut *writer);
+    // the command's output is printed as it arrives, while the stacks are
+    // polled every few seconds
+    let mut next_poll = Instant::now();
+    let status = loop {
+        if Instant::now() >= next_poll {
+            if !tail.poll_once().await? {
+                break None;
+            }
+            next_poll = Instant::now() + Duration::from_secs(5);
+        }
+        tokio::select! {
+            status = child.wait() => break Some(status.wrap_err("waiting for command")?),
+            _ = sleep_until(next_poll) => {}
+            line = exec::next_line(&mut output) => match line {
+                Some(line) => tail.print_command_output(&line).await?,
+                None => output = None,
+            },
+            signal = interrupts.recv() => {
+                if !tail.interrupted(signal, &mut interrupts).await? {
+                    break None;
+                }
+            }
+        }
+    };
+
+    // timed out or detached while the command was still running
+    let Some(status) = status else {
+        tail.finish().await;
+        return Ok(exit_code(tail.outcome()));
+    };
 
+    while let Some(line) = remaining_line(&mut output).await {
+        tail.print_command_output(&line).await?;
+    }
+    // pick up the events from the end of the deployment
+    tail.poll_once().await?;
+    tail.finish().await;
+
+    let code = exec::exit_code(status);
+    if code != 0 {
+        return Ok(code);
+    }
+    Ok(exit_code(tail.outcome()))
+}
+
+// output the command printed before it exited, giving up on output held open
+// by processes it left running
+async fn remaining_line(output: &mut Option<UnboundedReceiver<String>>) -> Option<String> {
+    let rx = output.as_mut()?;
+    tokio::time::timeout(Duration::from_secs(1), rx.recv())
+        .await
+        .ok()
+        .flatten()
+}
+
+// the exit code for the outcome of a deployment, explaining why it failed
+fn exit_code(outcome: Outcome) -> i32 {
+    match outcome {
+        Outcome::Succeeded | Outcome::Interrupted(_) => {}
+        Outcome::RolledBack => eprintln!("Error: deployment rolled back"),
+        Outcome::Failed => eprintln!("Error: deployment failed"),
+        Outcome::TimedOut => eprintln!("Error: timed out waiting for the deployment"),
+    }
+    outcome.exit_code()
+}
+
+fn exit_with(outcome: Outcome) -> Result<()> {
+    match exit_code(outcome) {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
+
+// tail the stack of a change set that is executing, or report why it is not
+async fn run_change_set(
+    opts: &Opts,
+    location: Location,
+    outcome: ChangeSetOutcome,
+    writer: &mut Writer<'_>,
+) -> Result<()> {
+    match outcome {
+        ChangeSetOutcome::Executing { stack_name, since } => {
+            let targets = vec![StackTarget::new(location, stack_name)];
+            let since = opts.since.unwrap_or(since);
+            let outcome = run_tail(opts, &targets, since, writer).await?;
+            exit_with(outcome)
+        }
+        ChangeSetOutcome::NoChanges(reason) => {
+            println!("No changes to deploy: {}", reason);
+            Ok(())
+        }
+        ChangeSetOutcome::Failed(reason) => {
+            eprintln!("Error: change set failed: {}", reason);
+            std::process::exit(1);
+        }
+        ChangeSetOutcome::Declined => {
+            eprintln!("Change set not executed");
+            std::process::exit(1);
+        }
+    }
+}
+
+// tail the stacks until they finish deploying, building new clients whenever
+// the credentials expire
+async fn run_tail(
+    opts: &Opts,
+    targets: &[StackTarget],
+    since: DateTime<Utc>,
+    writer: &mut Writer<'_>,
+) -> Result<Outcome> {
+    // Ctrl-C shows what is going on rather than stopping cftail mid-line
+    let mut interrupts = Interrupts::new()?;
+    // the clock starts once tailing does, rather than while waiting for the
+    // deployment to be confirmed or its change set to be executed
+    let deadline = opts.deadline()?;
     loop {
-        let client = create_client(&opts.endpoint_url).await;
-        let stack_info = build_stack_list(&client, &opts.stack_names, opts.nested)
+        let clients = create_clients(&opts.endpoint_url, targets).await;
+        let stack_info = build_stack_list(&clients, targets, opts.nested)
             .await
-            .expect("building stack list");
+            .wrap_err("building stack list")?;
 
-        let config = TailConfig {
-            since,
-            stack_info: &stack_info,
-            show_separators: !opts.no_show_separators,
-            show_notifications: !opts.no_show_notifications,
-            show_outputs: !opts.no_show_outputs,
-            show_resource_types: !opts.no_show_resource_types,
-            sound: opts.sound.clone(),
-            should_exit_on_completion: !opts.no_exit_on_completion,
-        };
+        let mut config = opts.tail_config(since, &stack_info);
+        config.deadline = deadline;
 
-        let mut tail = Tail::new(config, Arc::new(client), &mut writer);
+        let mut tail = Tail::new(config, clients, &mut *writer);
 
         tracing::info!("prefetching tasks");
-        match tail.prefetch().await {
+        match tail.prefetch_or_stop(&mut interrupts).await {
             Ok(_) => {}
             Err(e) => match e.downcast_ref::<Error<()>>() {
                 Some(Error::NoCredentials) => {
@@ -194,11 +1160,12 @@ async fn main() -> eyre::Result<()> {
         }
 
         tracing::debug!("starting poll loop");
-        match tail.poll().await {
+        match tail.poll(&mut interrupts).await {
             Ok(_) => {
+                tail.finish().await;
                 tracing::info!("exiting from tail successfully");
                 // found our exit early condition
-                return Ok(());
+                return Ok(tail.outcome());
             }
             Err(e) => match e.downcast_ref::<Error<()>>() {
                 Some(Error::CredentialsExpired) => {
@@ -227,3 +1194,57 @@ async fn main() -> eyre::Result<()> {
         tracing::trace!("building another client");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_config() {
+        let config: Config = toml::from_str(
+            r#"
+            nested = true
+            no-show-separators = true
+            timeout = "30m"
+            cancel-on-timeout = true
+            webhook = ["https://example.com/hook"]
+            on-failure = "./page-oncall.sh"
+
+            [report]
+            junit = "junit.xml"
+            "#,
+        )
+        .unwrap();
+
+        let mut opts = Opts::from_iter(&["cftail", "app"]);
+        opts.apply_config(&config).unwrap();
+        assert!(opts.nested);
+        assert!(opts.no_show_separators);
+        assert!(opts.cancel_on_timeout);
+        assert_eq!(opts.timeout, Some(Duration::from_secs(30 * 60)));
+        assert_eq!(opts.webhook, vec!["https://example.com/hook"]);
+        assert_eq!(opts.on_failure.as_deref(), Some("./page-oncall.sh"));
+        assert_eq!(opts.report, vec!["junit", "junit.xml"]);
+
+        // the command line overrides the file both ways
+        let mut opts = Opts::from_iter(&[
+            "cftail",
+            "--no-nested",
+            "--show-separators",
+            "--no-cancel-on-timeout",
+            "app",
+        ]);
+        opts.apply_config(&config).unwrap();
+        assert!(!opts.nested);
+        assert!(!opts.no_show_separators);
+        assert!(!opts.cancel_on_timeout);
+
+        // the last of a pair of flags wins
+        let mut opts = Opts::from_iter(&["cftail", "--nested", "--no-nested", "app"]);
+        opts.apply_config(&Config::default()).unwrap();
+        assert!(!opts.nested);
+
+        let mut opts = Opts::from_iter(&["cftail", "--cancel-on-timeout", "app"]);
+        assert!(opts.apply_config(&Config::default()).is_err());
+    }
+}
@@ -6,7 +6,7 @@ use eyre::{Context, Result};
 use std::collections::HashSet;
 
 pub(crate) async fn fetch_nested_stack_names(
-    client: &impl AwsCloudFormationClient,
+    client: &(impl AwsCloudFormationClient + ?Sized),
     root_stack_name: impl Into<String>,
 ) -> Result<HashSet<String>> {
     let root_stack_name = root_stack_name.into();
@@ -44,7 +44,7 @@ pub(crate) async fn fetch_nested_stack_names(
 }
 
 pub(crate) async fn fetch_stack_resources(
-    client: &impl AwsCloudFormationClient,
+    client: &(impl AwsCloudFormationClient + ?Sized),
     name: impl Into<String>,
 ) -> Result<Vec<StackResource>> {
     let name = name.into();
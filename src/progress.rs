@@ -0,0 +1,245 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+
+// width of the bar itself, in characters
+const BAR_WIDTH: usize = 30;
+
+// resource statuses that a resource stays in until the next operation
+fn is_terminal(status: &str) -> bool {
+    status.ends_with("_COMPLETE") || status.ends_with("_FAILED") || status.ends_with("_SKIPPED")
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+/// Resource in progress that is expected to finish last, based on how long
+/// it has taken before
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SlowestResource {
+    pub(crate) logical_id: String,
+    pub(crate) resource_type: String,
+    pub(crate) expected: Duration,
+    pub(crate) elapsed: Duration,
+}
+
+impl SlowestResource {
+    fn remaining(&self) -> Duration {
+        (self.expected - self.elapsed).max(Duration::zero())
+    }
+}
+
+/// Progress through the resources of a stack operation
+#[derive(Debug, Clone)]
+pub(crate) struct Progress {
+    started: DateTime<Utc>,
+    // id of the stack under deployment
+    stack_id: String,
+    // ids of its nested stacks that have been seen so far
+    nested: HashSet<String>,
+    /// Number of resources in the templates, once they have been fetched
+    pub(crate) total: Option<usize>,
+    /// Whether the templates could not be fetched to count the resources, in
+    /// which case there is no progress to show
+    pub(crate) count_failed: bool,
+    // (stack id, logical id) of each resource that has finished
+    finished: HashSet<(String, String)>,
+}
+
+impl Progress {
+    pub(crate) fn new(started: DateTime<Utc>, stack_id: impl Into<String>) -> Self {
+        Self {
+            started,
+            stack_id: stack_id.into(),
+            nested: HashSet::new(),
+            total: None,
+            count_failed: false,
+            finished: HashSet::new(),
+        }
+    }
+
+    /// Progress through several operations at once, e.g. on stacks in
+    /// different regions, as a single operation
+    pub(crate) fn combine<'a>(all: impl IntoIterator<Item = &'a Progress>) -> Option<Progress> {
+        let mut all = all.into_iter();
+        let mut combined = all.next()?.clone();
+        for progress in all {
+            combined.started = combined.started.min(progress.started);
+            combined.total = combined.total.zip(progress.total).map(|(a, b)| a + b);
+            combined.finished.extend(progress.finished.iter().cloned());
+        }
+        Some(combined)
+    }
+
+    /// When the operation started
+    pub(crate) fn started(&self) -> DateTime<Utc> {
+        self.started
+    }
+
+    /// Stack under deployment, followed by its nested stacks
+    pub(crate) fn stack_ids(&self) -> Vec<String> {
+        let mut nested: Vec<String> = self.nested.iter().cloned().collect();
+        nested.sort();
+        std::iter::once(self.stack_id.clone())
+            .chain(nested)
+            .collect()
+    }
+
+    /// Whether the resources of this stack are part of the operation
+    pub(crate) fn includes(&self, stack_id: &str) -> bool {
+        stack_id == self.stack_id || self.nested.contains(stack_id)
+    }
+
+    /// Add a nested stack to the operation, whose resources then need to be
+    /// counted too
+    pub(crate) fn add_nested(&mut self, stack_id: &str) {
+        if !self.includes(stack_id) {
+            self.nested.insert(stack_id.to_string());
+            self.total = None;
+        }
+    }
+
+    /// Record the latest status of a resource
+    pub(crate) fn record(&mut self, stack_id: &str, logical_id: &str, status: &str) {
+        let key = (stack_id.to_string(), logical_id.to_string());
+        if is_terminal(status) {
+            self.finished.insert(key);
+        } else {
+            self.finished.remove(&key);
+        }
+    }
+
+    pub(crate) fn finished(&self) -> usize {
+        match self.total {
+            Some(total) => self.finished.len().min(total),
+            None => self.finished.len(),
+        }
+    }
+
+    /// Estimated time remaining, assuming the remaining resources take as long
+    /// on average as the finished ones
+    pub(crate) fn eta(&self, now: DateTime<Utc>) -> Option<Duration> {
+        let total = self.total?;
+        let finished = self.finished();
+        if finished == 0 {
+            return None;
+        }
+        let elapsed = now - self.started;
+        Some(elapsed * (total - finished) as i32 / finished as i32)
+    }
+
+    /// Single line describing the progress, e.g.
+    /// `[#######-------] 42/200 resources | 3m12s elapsed | ~4m05s remaining`
+    ///
+    /// When the slowest resource in progress has been seen before, the time
+    /// remaining is how long that resource usually takes to finish.
+    pub(crate) fn render(&self, now: DateTime<Utc>, slowest: Option<&SlowestResource>) -> String {
+        let finished = self.finished();
+        let elapsed = format_duration(now - self.started);
+        let total = match self.total {
+            Some(total) => total,
+            None => return format!("{} resources finished | {} elapsed", finished, elapsed),
+        };
+
+        let filled = match total {
+            0 => BAR_WIDTH,
+            total => finished * BAR_WIDTH / total,
+        };
+        let mut line = format!(
+            "[{}{}] {}/{} resources | {} elapsed",
+            "#".repeat(filled),
+            "-".repeat(BAR_WIDTH - filled),
+            finished,
+            total,
+            elapsed
+        );
+        match slowest {
+            Some(slowest) => line.push_str(&format!(
+                " | ~{} remaining | {} ({}) usually takes {}, {} elapsed",
+                format_duration(slowest.remaining()),
+                slowest.logical_id,
+                slowest.resource_type,
+                format_duration(slowest.expected),
+                format_duration(slowest.elapsed)
+            )),
+            None => {
+                if let Some(eta) = self.eta(now) {
+                    line.push_str(&format!(" | ~{} remaining", format_duration(eta)));
+                }
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_progress() {
+        let started = Utc.timestamp_opt(0, 0).unwrap();
+        let mut progress = Progress::new(started, "stack");
+        progress.total = Some(4);
+        progress.record("stack", "Bucket", "CREATE_IN_PROGRESS");
+        progress.record("stack", "Queue", "CREATE_COMPLETE");
+        assert_eq!(progress.finished(), 1);
+
+        let now = started + Duration::seconds(60);
+        assert_eq!(progress.eta(now), Some(Duration::seconds(180)));
+        assert_eq!(
+            progress.render(now, None),
+            "[#######-----------------------] 1/4 resources | 1m00s elapsed | ~3m00s remaining"
+        );
+
+        let slowest = SlowestResource {
+            logical_id: "Cdn".to_string(),
+            resource_type: "AWS::CloudFront::Distribution".to_string(),
+            expected: Duration::minutes(6),
+            elapsed: Duration::minutes(2),
+        };
+        assert!(progress.render(now, Some(&slowest)).ends_with(
+            "~4m00s remaining | Cdn (AWS::CloudFront::Distribution) usually takes 6m00s, 2m00s elapsed"
+        ));
+
+        // resources that are updated again during a rollback are no longer finished
+        progress.record("stack", "Queue", "UPDATE_IN_PROGRESS");
+        assert_eq!(progress.finished(), 0);
+        assert_eq!(progress.eta(now), None);
+
+        // nested stacks are counted once they are seen
+        assert!(!progress.includes("nested"));
+        progress.add_nested("nested");
+        assert!(progress.includes("nested"));
+        assert_eq!(progress.total, None);
+        assert_eq!(progress.stack_ids(), vec!["stack", "nested"]);
+    }
+
+    #[test]
+    fn test_combine() {
+        let started = Utc.timestamp_opt(0, 0).unwrap();
+        let mut first = Progress::new(started + Duration::seconds(30), "first");
+        first.total = Some(2);
+        first.record("first", "Bucket", "UPDATE_COMPLETE");
+        let mut second = Progress::new(started, "second");
+        second.total = Some(2);
+        second.record("second", "Bucket", "UPDATE_COMPLETE");
+
+        let combined = Progress::combine(vec![&first, &second]).unwrap();
+        assert_eq!(combined.started(), started);
+        assert_eq!(combined.total, Some(4));
+        assert_eq!(combined.finished(), 2);
+
+        // the total is only known once every template has been counted
+        second.total = None;
+        let combined = Progress::combine(vec![&first, &second]).unwrap();
+        assert_eq!(combined.total, None);
+        assert!(Progress::combine(Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+use std::fmt::Write;
+
+use super::{status, timestamp, Report};
+use crate::progress::format_duration;
+
+const STYLE: &str = r#"
+body { font-family: -apple-system, "Segoe UI", Helvetica, Arial, sans-serif; margin: 2em; color: #1f2328; }
+h1 { margin-bottom: 0.2em; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 1em; }
+th, td { border: 1px solid #d0d7de; padding: 4px 8px; text-align: left; vertical-align: top; }
+th { background: #f6f8fa; cursor: pointer; user-select: none; }
+th.sorted-asc::after { content: " \25B2"; }
+th.sorted-desc::after { content: " \25BC"; }
+.summary span { margin-right: 2em; }
+.complete { color: #1a7f37; }
+.failed { color: #cf222e; }
+.in-progress { color: #0969da; }
+details { margin-bottom: 0.5em; }
+summary { cursor: pointer; font-weight: 600; }
+.timeline { position: relative; }
+.row { display: flex; align-items: center; height: 22px; }
+.label { width: 240px; flex-shrink: 0; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; font-size: 0.9em; }
+.track { position: relative; flex-grow: 1; height: 16px; background: #f6f8fa; }
+.bar { position: absolute; height: 100%; background: #54aeff; border-radius: 2px; cursor: pointer; min-width: 2px; }
+.bar.failed { background: #ff8182; }
+.bar.in-progress { background: #d8b9ff; }
+#filter { width: 30em; padding: 4px; margin-bottom: 0.5em; }
+"#;
+
+const SCRIPT: &str = r#"
+const table = document.getElementById("events");
+const rows = Array.from(table.tBodies[0].rows);
+const filter = document.getElementById("filter");
+function applyFilter() {
+  const terms = filter.value.toLowerCase().split(/\s+/).filter(t => t);
+  for (const row of rows) {
+    const text = row.textContent.toLowerCase();
+    row.hidden = !terms.every(t => text.includes(t));
+  }
+}
+filter.addEventListener("input", applyFilter);
+table.tHead.querySelectorAll("th").forEach((th, column) => {
+  th.addEventListener("click", () => {
+    const ascending = !th.classList.contains("sorted-asc");
+    table.tHead.querySelectorAll("th").forEach(h => h.classList.remove("sorted-asc", "sorted-desc"));
+    th.classList.add(ascending ? "sorted-asc" : "sorted-desc");
+    const key = row => row.cells[column].dataset.sort ?? row.cells[column].textContent;
+    rows.sort((a, b) => key(a).localeCompare(key(b), undefined, { numeric: true }) * (ascending ? 1 : -1));
+    rows.forEach(row => table.tBodies[0].appendChild(row));
+  });
+});
+document.querySelectorAll(".bar").forEach(bar => {
+  bar.addEventListener("click", () => {
+    filter.value = bar.dataset.resource;
+    applyFilter();
+    table.scrollIntoView({ behavior: "smooth" });
+  });
+});
+"#;
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// css class for the colour of a status
+fn status_class(status: &str) -> &'static str {
+    if status.ends_with("_FAILED") {
+        "failed"
+    } else if status.ends_with("_IN_PROGRESS") {
+        "in-progress"
+    } else {
+        "complete"
+    }
+}
+
+/// Single HTML page for the operation that works offline, with a sortable and
+/// filterable table of events and a timeline for each stack
+pub(crate) fn render(report: &Report) -> String {
+    let mut out = String::new();
+    let spans = report.spans();
+    let total_millis = report.duration().num_milliseconds().max(1) as f64;
+    let title = format!("Deployment of {}", report.stack_name);
+
+    // writing to a string cannot fail
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>",
+        escape(&title),
+        STYLE
+    );
+    let _ = writeln!(out, "<h1>{}</h1>", escape(&title));
+    let _ = writeln!(
+        out,
+        "<p class=\"summary\"><span>Outcome: <strong class=\"{}\">{}</strong></span><span>Duration: {}</span><span>{} to {}</span><span>{} resources changed</span></p>",
+        if report.status.contains("ROLLBACK") {
+            "failed"
+        } else {
+            status_class(&report.status)
+        },
+        escape(&report.status),
+        format_duration(report.duration()),
+        report.started.format("%Y-%m-%d %H:%M:%S UTC"),
+        report.finished.format("%Y-%m-%d %H:%M:%S UTC"),
+        spans.len()
+    );
+
+    let failures = report.failures();
+    if !failures.is_empty() {
+        let _ = writeln!(out, "<h2>Failures</h2>");
+        for failure in &failures {
+            let _ = writeln!(
+                out,
+                "<details><summary class=\"failed\">{} ({}) {}</summary>\n<p>{}</p>\n<p>Stack {} at {}</p>\n</details>",
+                escape(&failure.logical_id),
+                escape(&failure.resource_type),
+                escape(&failure.status),
+                escape(&failure.reason),
+                escape(&failure.stack_name),
+                failure.timestamp.format("%H:%M:%S")
+            );
+        }
+    }
+
+    if !report.outputs.is_empty() {
+        let _ = writeln!(
+            out,
+            "<h2>Outputs</h2>\n<table>\n<thead><tr><th>Name</th><th>Value</th><th>Description</th></tr></thead>\n<tbody>"
+        );
+        for output in &report.outputs {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(output.output_key().unwrap_or_default()),
+                escape(output.output_value().unwrap_or_default()),
+                escape(output.description().unwrap_or_default())
+            );
+        }
+        let _ = writeln!(out, "</tbody>\n</table>");
+    }
+
+    if !spans.is_empty() {
+        let _ = writeln!(out, "<h2>Timeline</h2>");
+        // a collapsible timeline for each stack, in the order they started changing
+        let mut stacks: Vec<&str> = Vec::new();
+        for span in &spans {
+            if !stacks.contains(&span.stack_name.as_str()) {
+                stacks.push(&span.stack_name);
+            }
+        }
+        for stack in stacks {
+            let _ = writeln!(
+                out,
+                "<details open><summary>{}</summary>\n<div class=\"timeline\">",
+                escape(stack)
+            );
+            for span in spans.iter().filter(|s| s.stack_name == stack) {
+                let finished = span.finished.unwrap_or(report.finished);
+                let left = (span.started - report.started).num_milliseconds() as f64 / total_millis
+                    * 100.0;
+                let width =
+                    (finished - span.started).num_milliseconds() as f64 / total_millis * 100.0;
+                let _ = writeln!(
+                    out,
+                    "<div class=\"row\"><div class=\"label\" title=\"{id}\">{id}</div><div class=\"track\"><div class=\"bar {class}\" style=\"left: {left:.2}%; width: {width:.2}%\" data-resource=\"{id}\" title=\"{id} ({type_}) {status} after {duration}\"></div></div></div>",
+                    id = escape(&span.logical_id),
+                    class = status_class(&span.status),
+                    left = left.clamp(0.0, 100.0),
+                    width = width.clamp(0.0, 100.0),
+                    type_ = escape(&span.resource_type),
+                    status = escape(&span.status),
+                    duration = format_duration(finished - span.started)
+                );
+            }
+            let _ = writeln!(out, "</div>\n</details>");
+        }
+    }
+
+    let _ = writeln!(
+        out,
+        "<h2>Events</h2>\n<input id=\"filter\" type=\"search\" placeholder=\"Filter events\">\n<table id=\"events\">\n<thead><tr><th>Time</th><th>Stack</th><th>Resource</th><th>Type</th><th>Status</th><th>Reason</th></tr></thead>\n<tbody>"
+    );
+    for event in &report.events {
+        let Some(time) = timestamp(event) else {
+            continue;
+        };
+        let status = status(event);
+        let _ = writeln!(
+            out,
+            "<tr><td data-sort=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"{}\">{}</td><td>{}</td></tr>",
+            time.timestamp_millis(),
+            time.format("%H:%M:%S"),
+            escape(event.stack_name().unwrap_or_default()),
+            escape(event.logical_resource_id().unwrap_or_default()),
+            escape(event.resource_type().unwrap_or_default()),
+            status_class(status),
+            escape(status),
+            escape(event.resource_status_reason().unwrap_or_default())
+        );
+    }
+    let _ = writeln!(
+        out,
+        "</tbody>\n</table>\n<script>{}</script>\n</body>\n</html>",
+        SCRIPT
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::tests::event;
+    use aws_sdk_cloudformation::types::{Output, ResourceStatus};
+    use chrono::{Duration, TimeZone, Utc};
+
+    // update that failed on `Function` with a reason that needs escaping, and
+    // rolled back
+    fn failed_update() -> Report {
+        let stack = "AWS::CloudFormation::Stack";
+        let function = "AWS::Lambda::Function";
+        let started = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        Report {
+            stack_name: "my-stack".to_string(),
+            status: "UPDATE_ROLLBACK_COMPLETE".to_string(),
+            started,
+            finished: started + Duration::seconds(100),
+            events: vec![
+                event("my-stack", stack, ResourceStatus::UpdateInProgress, 0, None),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateInProgress,
+                    5,
+                    None,
+                ),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateFailed,
+                    35,
+                    Some("Runtime <nodejs8.10> is not supported"),
+                ),
+                event(
+                    "my-stack",
+                    stack,
+                    ResourceStatus::UpdateRollbackInProgress,
+                    40,
+                    Some("The following resource(s) failed to update: [Function]."),
+                ),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateInProgress,
+                    45,
+                    None,
+                ),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateComplete,
+                    95,
+                    None,
+                ),
+                event(
+                    "my-stack",
+                    stack,
+                    ResourceStatus::UpdateRollbackComplete,
+                    100,
+                    None,
+                ),
+            ],
+            outputs: vec![Output::builder()
+                .output_key("Query")
+                .output_value("a=1&b=<2>")
+                .description("Say \"hi\"")
+                .build()],
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let html = render(&failed_update());
+        assert!(html.contains("<title>Deployment of my-stack</title>"));
+        // a rollback is a failure, even though the stack is complete
+        assert!(html.contains(
+            "<span>Outcome: <strong class=\"failed\">UPDATE_ROLLBACK_COMPLETE</strong></span><span>Duration: 1m40s</span><span>2020-09-13 12:26:40 UTC to 2020-09-13 12:28:20 UTC</span><span>1 resources changed</span>"
+        ));
+
+        assert!(html.contains(
+            "<details><summary class=\"failed\">Function (AWS::Lambda::Function) UPDATE_FAILED</summary>\n<p>Runtime &lt;nodejs8.10&gt; is not supported</p>\n<p>Stack my-stack at 12:27:15</p>\n</details>"
+        ));
+        assert!(html.contains(
+            "<tr><td>Query</td><td>a=1&amp;b=&lt;2&gt;</td><td>Say &quot;hi&quot;</td></tr>"
+        ));
+
+        assert!(html.contains("<details open><summary>my-stack</summary>"));
+        assert!(html.contains(
+            "<div class=\"bar complete\" style=\"left: 5.00%; width: 90.00%\" data-resource=\"Function\" title=\"Function (AWS::Lambda::Function) UPDATE_COMPLETE after 1m30s\">"
+        ));
+
+        // a row for every event, sortable by time
+        assert_eq!(html.matches("<tr><td data-sort=").count(), 7);
+        assert!(html.contains(
+            "<tr><td data-sort=\"1600000035000\">12:27:15</td><td>my-stack</td><td>Function</td><td>AWS::Lambda::Function</td><td class=\"failed\">UPDATE_FAILED</td><td>Runtime &lt;nodejs8.10&gt; is not supported</td></tr>"
+        ));
+        assert!(html.contains(
+            "<td class=\"in-progress\">UPDATE_ROLLBACK_IN_PROGRESS</td><td>The following resource(s) failed to update: [Function].</td>"
+        ));
+    }
+}
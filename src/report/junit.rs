@@ -0,0 +1,235 @@
+use chrono::Duration;
+use std::fmt::Write;
+
+use super::{Failure, Report, ResourceSpan};
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}", duration.num_milliseconds().max(0) as f64 / 1000.0)
+}
+
+// test case for a resource, along with the first time it failed if it did
+struct TestCase<'r> {
+    span: &'r ResourceSpan,
+    failure: Option<&'r Failure>,
+}
+
+/// JUnit XML with a test suite for each stack and a test case for each of
+/// its resources, so that CI servers can show failed resources
+///
+/// Resources that were only cancelled because another resource failed are
+/// skipped rather than failed.
+pub(crate) fn render(report: &Report) -> String {
+    let spans = report.spans();
+    let failures = report.failures();
+    let cases: Vec<TestCase> = spans
+        .iter()
+        .map(|span| TestCase {
+            span,
+            failure: failures
+                .iter()
+                .find(|f| f.stack_name == span.stack_name && f.logical_id == span.logical_id),
+        })
+        .collect();
+    let failed = |case: &&TestCase| case.failure.is_some_and(|f| !f.is_cancelled());
+    let skipped = |case: &&TestCase| case.failure.is_some_and(|f| f.is_cancelled());
+
+    let mut out = String::new();
+    // writing to a string cannot fail
+    let _ = writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        out,
+        r#"<testsuites name="{}" tests="{}" failures="{}" skipped="{}" time="{}">"#,
+        escape(&report.stack_name),
+        cases.len(),
+        cases.iter().filter(failed).count(),
+        cases.iter().filter(skipped).count(),
+        seconds(report.duration())
+    );
+
+    // a suite for each stack, in the order they started changing
+    let mut stacks: Vec<&str> = Vec::new();
+    for span in &spans {
+        if !stacks.contains(&span.stack_name.as_str()) {
+            stacks.push(&span.stack_name);
+        }
+    }
+    for stack in stacks {
+        let suite: Vec<&TestCase> = cases
+            .iter()
+            .filter(|c| c.span.stack_name == stack)
+            .collect();
+        let started = suite
+            .iter()
+            .map(|c| c.span.started)
+            .min()
+            .unwrap_or(report.started);
+        let finished = suite
+            .iter()
+            .map(|c| c.span.finished.unwrap_or(report.finished))
+            .max()
+            .unwrap_or(report.finished);
+        let _ = writeln!(
+            out,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" errors="0" skipped="{}" time="{}" timestamp="{}">"#,
+            escape(stack),
+            suite.len(),
+            suite.iter().copied().filter(failed).count(),
+            suite.iter().copied().filter(skipped).count(),
+            seconds(finished - started),
+            started.format("%Y-%m-%dT%H:%M:%S")
+        );
+        for case in suite {
+            let span = case.span;
+            let _ = write!(
+                out,
+                r#"    <testcase classname="{}" name="{} ({})" time="{}""#,
+                escape(stack),
+                escape(&span.logical_id),
+                escape(&span.resource_type),
+                seconds(span.finished.unwrap_or(report.finished) - span.started)
+            );
+            match case.failure {
+                Some(failure) if failure.is_cancelled() => {
+                    let _ = writeln!(
+                        out,
+                        ">\n      <skipped message=\"{}\"/>\n    </testcase>",
+                        escape(&failure.reason)
+                    );
+                }
+                Some(failure) => {
+                    let _ = writeln!(
+                        out,
+                        ">\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>",
+                        escape(&failure.reason),
+                        escape(&failure.status),
+                        escape(&failure.reason)
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "/>");
+                }
+            }
+        }
+        let _ = writeln!(out, "  </testsuite>");
+    }
+    let _ = writeln!(out, "</testsuites>");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::tests::event;
+    use aws_sdk_cloudformation::types::{ResourceStatus, StackEvent};
+    use aws_smithy_types::DateTime as SmithyDateTime;
+    use chrono::{TimeZone, Utc};
+
+    fn nested_event(
+        logical_id: &str,
+        status: ResourceStatus,
+        secs: i64,
+        reason: &str,
+    ) -> StackEvent {
+        StackEvent::builder()
+            .stack_id("nested-id")
+            .stack_name("my-stack-Network")
+            .logical_resource_id(logical_id)
+            .resource_type("AWS::EC2::VPC")
+            .resource_status(status)
+            .resource_status_reason(reason)
+            .timestamp(SmithyDateTime::from_secs(1_600_000_000 + secs))
+            .build()
+    }
+
+    // update where `Table` changed, `Function` failed, and `Queue` and `Vpc`
+    // in a nested stack were cancelled because of it
+    fn failed_update() -> Report {
+        let function = "AWS::Lambda::Function";
+        let queue = "AWS::SQS::Queue";
+        let table = "AWS::DynamoDB::Table";
+        let started = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        Report {
+            stack_name: "my-stack".to_string(),
+            status: "UPDATE_ROLLBACK_IN_PROGRESS".to_string(),
+            started,
+            finished: started + Duration::seconds(40),
+            events: vec![
+                event("Table", table, ResourceStatus::UpdateInProgress, 2, None),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateInProgress,
+                    5,
+                    None,
+                ),
+                event("Queue", queue, ResourceStatus::UpdateInProgress, 6, None),
+                event("Table", table, ResourceStatus::UpdateComplete, 8, None),
+                nested_event("Vpc", ResourceStatus::UpdateInProgress, 10, ""),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateFailed,
+                    35,
+                    Some("Invalid runtime"),
+                ),
+                event(
+                    "Queue",
+                    queue,
+                    ResourceStatus::UpdateFailed,
+                    36,
+                    Some("Resource update cancelled"),
+                ),
+                nested_event(
+                    "Vpc",
+                    ResourceStatus::UpdateFailed,
+                    37,
+                    "Resource creation cancelled",
+                ),
+            ],
+            outputs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let junit = render(&failed_update());
+        assert!(junit.contains(
+            r#"<testsuites name="my-stack" tests="4" failures="1" skipped="2" time="40.000">"#
+        ));
+        // a suite for each stack, in the order they started changing
+        let root = junit.find(r#"<testsuite name="my-stack" "#).unwrap();
+        let nested = junit
+            .find(r#"<testsuite name="my-stack-Network" "#)
+            .unwrap();
+        assert!(root < nested);
+        assert!(junit.contains(
+            r#"<testsuite name="my-stack" tests="3" failures="1" errors="0" skipped="1" time="34.000" timestamp="2020-09-13T12:26:42">"#
+        ));
+        assert!(junit.contains(
+            r#"<testsuite name="my-stack-Network" tests="1" failures="0" errors="0" skipped="1" time="27.000" timestamp="2020-09-13T12:26:50">"#
+        ));
+
+        assert!(junit.contains(
+            "    <testcase classname=\"my-stack\" name=\"Table (AWS::DynamoDB::Table)\" time=\"6.000\"/>\n"
+        ));
+        // cancelled resources are skipped rather than failed
+        assert!(junit.contains(
+            "    <testcase classname=\"my-stack\" name=\"Queue (AWS::SQS::Queue)\" time=\"30.000\">\n      <skipped message=\"Resource update cancelled\"/>\n    </testcase>"
+        ));
+        assert!(junit.contains(
+            "    <testcase classname=\"my-stack-Network\" name=\"Vpc (AWS::EC2::VPC)\" time=\"27.000\">\n      <skipped message=\"Resource creation cancelled\"/>"
+        ));
+        assert!(junit.contains(
+            "    <testcase classname=\"my-stack\" name=\"Function (AWS::Lambda::Function)\" time=\"30.000\">\n      <failure message=\"Invalid runtime\" type=\"UPDATE_FAILED\">Invalid runtime</failure>\n    </testcase>"
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+use super::Report;
+use crate::progress::format_duration;
+
+// slowest resources to list
+const SLOWEST_RESOURCES: usize = 10;
+
+const MERMAID_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// keep values from breaking out of their table cell
+fn cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+// mermaid task names cannot contain the characters that separate task fields
+fn task_name(value: &str) -> String {
+    value.replace([':', ';', '#'], "_")
+}
+
+fn mermaid_date(timestamp: DateTime<Utc>) -> String {
+    timestamp.format(MERMAID_DATE_FORMAT).to_string()
+}
+
+/// Markdown summary of the operation, with a Mermaid Gantt chart of when each
+/// resource changed
+pub(crate) fn render(report: &Report) -> String {
+    let mut out = String::new();
+    let spans = report.spans();
+
+    // writing to a string cannot fail
+    let _ = writeln!(out, "# Deployment of {}\n", report.stack_name);
+    let _ = writeln!(out, "- **Outcome:** {}", report.status);
+    let _ = writeln!(
+        out,
+        "- **Duration:** {} ({} to {})",
+        format_duration(report.duration()),
+        report.started.format("%Y-%m-%d %H:%M:%S UTC"),
+        report.finished.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    let _ = writeln!(out, "- **Resources changed:** {}", spans.len());
+
+    let mut slowest: Vec<_> = spans.iter().filter(|s| s.duration().is_some()).collect();
+    slowest.sort_by_key(|s| std::cmp::Reverse(s.duration()));
+    if !slowest.is_empty() {
+        let _ = writeln!(out, "\n## Slowest resources\n");
+        let _ = writeln!(out, "| Resource | Type | Stack | Duration | Status |");
+        let _ = writeln!(out, "| --- | --- | --- | --- | --- |");
+        for span in slowest.into_iter().take(SLOWEST_RESOURCES) {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} |",
+                cell(&span.logical_id),
+                cell(&span.resource_type),
+                cell(&span.stack_name),
+                span.duration().map(format_duration).unwrap_or_default(),
+                span.status
+            );
+        }
+    }
+
+    let failures = report.failures();
+    if !failures.is_empty() {
+        let _ = writeln!(out, "\n## Failures\n");
+        let _ = writeln!(out, "| Resource | Type | Status | Reason |");
+        let _ = writeln!(out, "| --- | --- | --- | --- |");
+        for failure in failures {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                cell(&failure.logical_id),
+                cell(&failure.resource_type),
+                failure.status,
+                cell(&failure.reason)
+            );
+        }
+    }
+
+    if !report.outputs.is_empty() {
+        let _ = writeln!(out, "\n## Outputs\n");
+        let _ = writeln!(out, "| Name | Value | Description |");
+        let _ = writeln!(out, "| --- | --- | --- |");
+        for output in &report.outputs {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} |",
+                cell(output.output_key().unwrap_or_default()),
+                cell(output.output_value().unwrap_or_default()),
+                cell(output.description().unwrap_or_default())
+            );
+        }
+    }
+
+    if !spans.is_empty() {
+        let _ = writeln!(out, "\n## Timeline\n");
+        let _ = writeln!(out, "```mermaid\ngantt");
+        let _ = writeln!(out, "    title {}", task_name(&report.stack_name));
+        let _ = writeln!(out, "    dateFormat YYYY-MM-DD HH:mm:ss");
+        let _ = writeln!(out, "    axisFormat %H:%M:%S");
+        // a section for each stack, in the order they started changing
+        let mut stacks: Vec<&str> = Vec::new();
+        for span in &spans {
+            if !stacks.contains(&span.stack_name.as_str()) {
+                stacks.push(&span.stack_name);
+            }
+        }
+        for stack in stacks {
+            let _ = writeln!(out, "    section {}", task_name(stack));
+            for span in spans.iter().filter(|s| s.stack_name == stack) {
+                let tag = if span.status.ends_with("_FAILED") {
+                    "crit"
+                } else if span.finished.is_some() {
+                    "done"
+                } else {
+                    "active"
+                };
+                let _ = writeln!(
+                    out,
+                    "    {} :{}, {}, {}",
+                    task_name(&span.logical_id),
+                    tag,
+                    mermaid_date(span.started),
+                    mermaid_date(span.finished.unwrap_or(report.finished))
+                );
+            }
+        }
+        let _ = writeln!(out, "```");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::tests::event;
+    use aws_sdk_cloudformation::types::{Output, ResourceStatus, StackEvent};
+    use chrono::{Duration, TimeZone};
+
+    fn report(status: &str, secs: i64, events: Vec<StackEvent>) -> Report {
+        let started = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        Report {
+            stack_name: "my-stack".to_string(),
+            status: status.to_string(),
+            started,
+            finished: started + Duration::seconds(secs),
+            events,
+            outputs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let stack = "AWS::CloudFormation::Stack";
+        let mut report = report(
+            "CREATE_COMPLETE",
+            90,
+            vec![
+                event("my-stack", stack, ResourceStatus::CreateInProgress, 0, None),
+                event(
+                    "Role",
+                    "AWS::IAM::Role",
+                    ResourceStatus::CreateInProgress,
+                    2,
+                    None,
+                ),
+                event(
+                    "Bucket",
+                    "AWS::S3::Bucket",
+                    ResourceStatus::CreateInProgress,
+                    3,
+                    None,
+                ),
+                event(
+                    "Role",
+                    "AWS::IAM::Role",
+                    ResourceStatus::CreateComplete,
+                    22,
+                    None,
+                ),
+                event(
+                    "Bucket",
+                    "AWS::S3::Bucket",
+                    ResourceStatus::CreateComplete,
+                    83,
+                    None,
+                ),
+                event("my-stack", stack, ResourceStatus::CreateComplete, 90, None),
+            ],
+        );
+        report.outputs.push(
+            Output::builder()
+                .output_key("Filter")
+                .output_value("a|b")
+                .description("first\nsecond")
+                .build(),
+        );
+
+        let markdown = render(&report);
+        assert!(markdown.contains(
+            "- **Outcome:** CREATE_COMPLETE\n- **Duration:** 1m30s (2020-09-13 12:26:40 UTC to 2020-09-13 12:28:10 UTC)\n- **Resources changed:** 2\n"
+        ));
+        // slowest first
+        assert!(markdown.contains(
+            "| Bucket | AWS::S3::Bucket | my-stack | 1m20s | CREATE_COMPLETE |\n| Role | AWS::IAM::Role | my-stack | 20s | CREATE_COMPLETE |\n"
+        ));
+        assert!(!markdown.contains("## Failures"));
+        // values cannot break out of their cells
+        assert!(markdown.contains("| Filter | a\\|b | first second |\n"));
+        assert!(markdown.contains(
+            "    section my-stack\n    Role :done, 2020-09-13 12:26:42, 2020-09-13 12:27:02\n    Bucket :done, 2020-09-13 12:26:43, 2020-09-13 12:28:03\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_failures() {
+        let stack = "AWS::CloudFormation::Stack";
+        let report = report(
+            "ROLLBACK_IN_PROGRESS",
+            30,
+            vec![
+                event("my-stack", stack, ResourceStatus::CreateInProgress, 0, None),
+                event(
+                    "Bucket",
+                    "AWS::S3::Bucket",
+                    ResourceStatus::CreateInProgress,
+                    3,
+                    None,
+                ),
+                event(
+                    "Queue",
+                    "AWS::SQS::Queue",
+                    ResourceStatus::CreateInProgress,
+                    4,
+                    None,
+                ),
+                event(
+                    "Bucket",
+                    "AWS::S3::Bucket",
+                    ResourceStatus::CreateFailed,
+                    10,
+                    Some("my-bucket already exists"),
+                ),
+                event(
+                    "my-stack",
+                    stack,
+                    ResourceStatus::RollbackInProgress,
+                    12,
+                    Some("The following resource(s) failed to create: [Bucket]."),
+                ),
+            ],
+        );
+
+        let markdown = render(&report);
+        // the stack failing is not a resource failure
+        assert!(markdown.contains(
+            "## Failures\n\n| Resource | Type | Status | Reason |\n| --- | --- | --- | --- |\n| Bucket | AWS::S3::Bucket | CREATE_FAILED | my-bucket already exists |\n\n"
+        ));
+        assert!(markdown.contains("    Bucket :crit, 2020-09-13 12:26:43, 2020-09-13 12:26:50\n"));
+        // still in progress when the report was written
+        assert!(markdown.contains("    Queue :active, 2020-09-13 12:26:44, 2020-09-13 12:27:10\n"));
+    }
+}
@@ -0,0 +1,323 @@
+use aws_sdk_cloudformation::types::{Output, StackEvent};
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::{DateTime, Duration, Utc};
+use eyre::{Result, WrapErr};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+mod html;
+mod junit;
+mod markdown;
+
+/// Format to write a report on a finished operation in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ReportFormat {
+    Markdown,
+    Html,
+    Junit,
+}
+
+impl FromStr for ReportFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "junit" => Ok(Self::Junit),
+            _ => eyre::bail!(
+                "unknown report format {:?}, expected markdown, html or junit",
+                s
+            ),
+        }
+    }
+}
+
+/// Pair up the values of `--report <format> <file>`
+pub(crate) fn parse_reports(values: &[String]) -> Result<Vec<(ReportFormat, PathBuf)>> {
+    values
+        .chunks(2)
+        .map(|pair| match pair {
+            [format, path] => Ok((format.parse()?, PathBuf::from(path))),
+            _ => eyre::bail!("--report needs a format and a file"),
+        })
+        .collect()
+}
+
+/// Time a resource spent changing during the operation
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResourceSpan {
+    pub(crate) stack_name: String,
+    pub(crate) logical_id: String,
+    pub(crate) resource_type: String,
+    /// Last status of the resource
+    pub(crate) status: String,
+    pub(crate) started: DateTime<Utc>,
+    /// When the resource reached its last status, if that is a terminal one
+    pub(crate) finished: Option<DateTime<Utc>>,
+}
+
+impl ResourceSpan {
+    pub(crate) fn duration(&self) -> Option<Duration> {
+        Some(self.finished? - self.started)
+    }
+}
+
+/// Resource that failed during the operation
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Failure {
+    pub(crate) stack_name: String,
+    pub(crate) logical_id: String,
+    pub(crate) resource_type: String,
+    pub(crate) status: String,
+    pub(crate) reason: String,
+    pub(crate) timestamp: DateTime<Utc>,
+}
+
+impl Failure {
+    /// Whether the resource was only cancelled because another resource failed
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.reason.ends_with(" cancelled")
+    }
+}
+
+/// Everything that happened during an operation on a stack
+#[derive(Debug, Clone)]
+pub(crate) struct Report {
+    pub(crate) stack_name: String,
+    /// Final status of the stack
+    pub(crate) status: String,
+    pub(crate) started: DateTime<Utc>,
+    pub(crate) finished: DateTime<Utc>,
+    /// Events of the stack and its nested stacks, oldest first
+    pub(crate) events: Vec<StackEvent>,
+    pub(crate) outputs: Vec<Output>,
+}
+
+fn timestamp(event: &StackEvent) -> Option<DateTime<Utc>> {
+    event.timestamp()?.to_chrono_utc().ok()
+}
+
+fn status(event: &StackEvent) -> &str {
+    event
+        .resource_status()
+        .map(|s| s.as_str())
+        .unwrap_or_default()
+}
+
+// events on resources rather than on the stacks themselves
+fn resource_events(events: &[StackEvent]) -> impl Iterator<Item = &StackEvent> {
+    events
+        .iter()
+        .filter(|e| e.logical_resource_id().is_some() && e.logical_resource_id() != e.stack_name())
+}
+
+impl Report {
+    pub(crate) fn duration(&self) -> Duration {
+        self.finished - self.started
+    }
+
+    /// Each resource that changed, in the order they started changing
+    pub(crate) fn spans(&self) -> Vec<ResourceSpan> {
+        let mut spans: Vec<ResourceSpan> = Vec::new();
+        let mut indices: HashMap<(&str, &str), usize> = HashMap::new();
+        for event in resource_events(&self.events) {
+            let (Some(stack_id), Some(logical_id), Some(timestamp)) = (
+                event.stack_id(),
+                event.logical_resource_id(),
+                timestamp(event),
+            ) else {
+                continue;
+            };
+            let status = status(event);
+            let index = *indices.entry((stack_id, logical_id)).or_insert_with(|| {
+                spans.push(ResourceSpan {
+                    stack_name: event.stack_name().unwrap_or_default().to_string(),
+                    logical_id: logical_id.to_string(),
+                    resource_type: event.resource_type().unwrap_or_default().to_string(),
+                    status: status.to_string(),
+                    started: timestamp,
+                    finished: None,
+                });
+                spans.len() - 1
+            });
+            let span = &mut spans[index];
+            span.status = status.to_string();
+            span.finished = if status.ends_with("_IN_PROGRESS") {
+                None
+            } else {
+                Some(timestamp)
+            };
+        }
+        spans
+    }
+
+    /// Resources that failed, in the order they failed
+    pub(crate) fn failures(&self) -> Vec<Failure> {
+        resource_events(&self.events)
+            .filter(|e| status(e).ends_with("_FAILED"))
+            .filter_map(|event| {
+                Some(Failure {
+                    stack_name: event.stack_name()?.to_string(),
+                    logical_id: event.logical_resource_id()?.to_string(),
+                    resource_type: event.resource_type().unwrap_or_default().to_string(),
+                    status: status(event).to_string(),
+                    reason: event
+                        .resource_status_reason()
+                        .unwrap_or_default()
+                        .to_string(),
+                    timestamp: timestamp(event)?,
+                })
+            })
+            .collect()
+    }
+
+    pub(crate) fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => markdown::render(self),
+            ReportFormat::Html => html::render(self),
+            ReportFormat::Junit => junit::render(self),
+        }
+    }
+
+    pub(crate) fn write(&self, format: ReportFormat, path: &Path) -> Result<()> {
+        std::fs::write(path, self.render(format))
+            .wrap_err_with(|| format!("writing report to {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use aws_sdk_cloudformation::types::ResourceStatus;
+    use aws_smithy_types::DateTime as SmithyDateTime;
+    use chrono::TimeZone;
+
+    pub(crate) fn event(
+        logical_id: &str,
+        resource_type: &str,
+        status: ResourceStatus,
+        secs: i64,
+        reason: Option<&str>,
+    ) -> StackEvent {
+        StackEvent::builder()
+            .stack_id("stack-id")
+            .stack_name("my-stack")
+            .logical_resource_id(logical_id)
+            .resource_type(resource_type)
+            .resource_status(status)
+            .set_resource_status_reason(reason.map(String::from))
+            .timestamp(SmithyDateTime::from_secs(1_600_000_000 + secs))
+            .build()
+    }
+
+    /// Update that failed on `Function`, cancelling `Queue`, and rolled back
+    pub(crate) fn failed_update() -> Report {
+        let stack = "AWS::CloudFormation::Stack";
+        let function = "AWS::Lambda::Function";
+        let queue = "AWS::SQS::Queue";
+        let started = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        Report {
+            stack_name: "my-stack".to_string(),
+            status: "UPDATE_ROLLBACK_COMPLETE".to_string(),
+            started,
+            finished: started + Duration::seconds(100),
+            events: vec![
+                event("my-stack", stack, ResourceStatus::UpdateInProgress, 0, None),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateInProgress,
+                    5,
+                    None,
+                ),
+                event("Queue", queue, ResourceStatus::UpdateInProgress, 6, None),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateFailed,
+                    35,
+                    Some("Invalid runtime"),
+                ),
+                event(
+                    "Queue",
+                    queue,
+                    ResourceStatus::UpdateFailed,
+                    36,
+                    Some("Resource update cancelled"),
+                ),
+                event(
+                    "my-stack",
+                    stack,
+                    ResourceStatus::UpdateRollbackInProgress,
+                    40,
+                    Some("The following resource(s) failed to update: [Function]."),
+                ),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateInProgress,
+                    45,
+                    None,
+                ),
+                event(
+                    "Function",
+                    function,
+                    ResourceStatus::UpdateComplete,
+                    95,
+                    None,
+                ),
+                event(
+                    "my-stack",
+                    stack,
+                    ResourceStatus::UpdateRollbackComplete,
+                    100,
+                    None,
+                ),
+            ],
+            outputs: vec![Output::builder()
+                .output_key("Url")
+                .output_value("https://example.com")
+                .build()],
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut report = failed_update();
+        report.events.push(event(
+            "Alias",
+            "AWS::Lambda::Alias",
+            ResourceStatus::UpdateInProgress,
+            98,
+            None,
+        ));
+        let spans = report.spans();
+        // the stack itself is not a resource
+        assert_eq!(spans.len(), 3);
+
+        // a resource that is rolled back spans from when it first changed
+        assert_eq!(spans[0].logical_id, "Function");
+        assert_eq!(spans[0].status, "UPDATE_COMPLETE");
+        assert_eq!(spans[0].duration(), Some(Duration::seconds(90)));
+
+        assert_eq!(spans[1].logical_id, "Queue");
+        assert_eq!(spans[1].status, "UPDATE_FAILED");
+        assert_eq!(spans[1].duration(), Some(Duration::seconds(30)));
+
+        assert_eq!(spans[2].logical_id, "Alias");
+        assert_eq!(spans[2].duration(), None);
+    }
+
+    #[test]
+    fn test_failures() {
+        let failures = failed_update().failures();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].logical_id, "Function");
+        assert_eq!(failures[0].reason, "Invalid runtime");
+        assert!(!failures[0].is_cancelled());
+        assert_eq!(failures[1].logical_id, "Queue");
+        assert!(failures[1].is_cancelled());
+    }
+}
@@ -0,0 +1,726 @@
+use aws_sdk_cloudformation::operation::describe_stack_set_operation::DescribeStackSetOperationInput;
+use aws_sdk_cloudformation::operation::list_stack_instances::ListStackInstancesInput;
+use aws_sdk_cloudformation::operation::list_stack_set_operation_results::ListStackSetOperationResultsInput;
+use aws_sdk_cloudformation::operation::list_stack_set_operations::ListStackSetOperationsInput;
+use aws_sdk_cloudformation::types::{
+    StackSetOperation, StackSetOperationResultStatus, StackSetOperationResultSummary,
+    StackSetOperationStatus,
+};
+use aws_smithy_types_convert::date_time::DateTimeExt;
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result};
+use std::collections::{BTreeSet, HashMap};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+use crate::aws::Fetcher;
+use crate::stacks::{Credentials, Location, StackTarget};
+
+fn operation_status_color(status: &StackSetOperationStatus) -> Color {
+    match status {
+        StackSetOperationStatus::Succeeded => Color::Green,
+        StackSetOperationStatus::Failed
+        | StackSetOperationStatus::Stopping
+        | StackSetOperationStatus::Stopped => Color::Red,
+        _ => Color::Blue,
+    }
+}
+
+fn result_status_color(status: &StackSetOperationResultStatus) -> Color {
+    match status {
+        StackSetOperationResultStatus::Succeeded => Color::Green,
+        StackSetOperationResultStatus::Failed | StackSetOperationResultStatus::Cancelled => {
+            Color::Red
+        }
+        _ => Color::Blue,
+    }
+}
+
+// name of the stack with the given id, which is an arn ending in
+// `stack/<name>/<uuid>`
+fn stack_name(stack_id: &str) -> &str {
+    stack_id.split('/').nth(1).unwrap_or(stack_id)
+}
+
+/// Role assumed in each instance account to read its stack events
+pub(crate) struct InstanceRole {
+    /// Partition of the accounts, e.g. `aws` or `aws-cn`
+    pub(crate) partition: String,
+    pub(crate) name: String,
+}
+
+impl InstanceRole {
+    fn arn(&self, account: &str) -> String {
+        format!("arn:{}:iam::{}:role/{}", self.partition, account, self.name)
+    }
+}
+
+pub(crate) fn is_operation_finished(status: &StackSetOperationStatus) -> bool {
+    matches!(
+        status,
+        StackSetOperationStatus::Succeeded
+            | StackSetOperationStatus::Failed
+            | StackSetOperationStatus::Stopped
+    )
+}
+
+/// Watches the progress of a StackSet operation in each account and region
+pub(crate) struct StackSetWatcher {
+    client: Fetcher,
+    stack_set_name: String,
+    operation_id: String,
+    operation_status: Option<StackSetOperationStatus>,
+    // last status printed for each (account, region)
+    statuses: HashMap<(String, String), StackSetOperationResultStatus>,
+    // stack of each (account, region) when the instances were last listed
+    instances: HashMap<(String, String), StackTarget>,
+    // whether the results have changed since the instances were last listed
+    instances_stale: bool,
+}
+
+impl StackSetWatcher {
+    /// Watch `operation_id`, or the most recent operation on the StackSet if not given
+    pub(crate) async fn new(
+        client: Fetcher,
+        stack_set_name: impl Into<String>,
+        operation_id: Option<String>,
+    ) -> Result<Self> {
+        let stack_set_name = stack_set_name.into();
+        let operation_id = match operation_id {
+            Some(id) => id,
+            None => latest_operation_id(&client, &stack_set_name).await?,
+        };
+        tracing::debug!(%stack_set_name, %operation_id, "watching stack set operation");
+
+        Ok(Self {
+            client,
+            stack_set_name,
+            operation_id,
+            operation_status: None,
+            statuses: HashMap::new(),
+            instances: HashMap::new(),
+            instances_stale: true,
+        })
+    }
+
+    pub(crate) async fn operation(&self) -> Result<StackSetOperation> {
+        let input = DescribeStackSetOperationInput::builder()
+            .stack_set_name(&self.stack_set_name)
+            .operation_id(&self.operation_id)
+            .build()
+            .wrap_err("building describe stack set operation input")?;
+        let res = self
+            .client
+            .describe_stack_set_operation(input)
+            .await
+            .wrap_err("describing stack set operation")?;
+        res.stack_set_operation
+            .ok_or_else(|| eyre::eyre!("no stack set operation {} found", self.operation_id))
+    }
+
+    /// When the operation was started, to fetch instance stack events from
+    pub(crate) async fn start_time(&self) -> Result<DateTime<Utc>> {
+        let operation = self.operation().await?;
+        let created = operation
+            .creation_timestamp()
+            .ok_or_else(|| eyre::eyre!("stack set operation has no creation time"))?;
+        created
+            .to_chrono_utc()
+            .wrap_err("converting operation creation time")
+    }
+
+    /// The stacks backing each stack instance that has been created so far
+    ///
+    /// If `role` is given, it is assumed in each instance account to read the
+    /// stack events, otherwise the default credentials are used. The instances
+    /// are only listed again once the operation results change, or while an
+    /// instance is running without a stack.
+    pub(crate) async fn instance_targets(
+        &mut self,
+        role: Option<&InstanceRole>,
+    ) -> Result<Vec<StackTarget>> {
+        let awaiting_stack = self.statuses.iter().any(|(key, status)| {
+            *status == StackSetOperationResultStatus::Running && !self.instances.contains_key(key)
+        });
+        if self.instances_stale || awaiting_stack {
+            self.instances = self.list_instances(role).await?;
+            self.instances_stale = false;
+        }
+        let targets: BTreeSet<_> = self.instances.values().cloned().collect();
+        Ok(targets.into_iter().collect())
+    }
+
+    async fn list_instances(
+        &self,
+        role: Option<&InstanceRole>,
+    ) -> Result<HashMap<(String, String), StackTarget>> {
+        let mut instances = HashMap::new();
+        let mut next_token = None;
+        loop {
+            let input = ListStackInstancesInput::builder()
+                .stack_set_name(&self.stack_set_name)
+                .set_next_token(next_token)
+                .build()
+                .wrap_err("building list stack instances input")?;
+            let res = self
+                .client
+                .list_stack_instances(input)
+                .await
+                .wrap_err("listing stack instances")?;
+
+            for summary in res.summaries() {
+                let (Some(stack_id), Some(account), Some(region)) =
+                    (summary.stack_id(), summary.account(), summary.region())
+                else {
+                    continue;
+                };
+                let location = Location {
+                    credentials: role.map(|role| Credentials::AssumeRole(role.arn(account))),
+                    region: Some(region.to_string()),
+                };
+                // named like the stacks given on the command line, so that
+                // their events are recognised as those of a tailed stack
+                instances.insert(
+                    (account.to_string(), region.to_string()),
+                    StackTarget::new(location, stack_name(stack_id)),
+                );
+            }
+
+            match res.next_token {
+                Some(token) => next_token = Some(token),
+                None => break,
+            }
+        }
+        Ok(instances)
+    }
+
+    /// Print any changes in the operation progress, returning the final
+    /// status of the operation once it has finished
+    pub(crate) async fn poll_step(
+        &mut self,
+        writer: &mut impl WriteColor,
+    ) -> Result<Option<StackSetOperationStatus>> {
+        let operation = self.operation().await?;
+        for result in self.fetch_results().await? {
+            self.print_result(writer, &result)?;
+        }
+
+        let status = operation
+            .status()
+            .cloned()
+            .ok_or_else(|| eyre::eyre!("stack set operation has no status"))?;
+        if self.operation_status.as_ref() != Some(&status) {
+            let action = operation
+                .action()
+                .map(|a| a.as_str().to_string())
+                .unwrap_or_default();
+            write!(
+                writer,
+                "{}: {} - {} {} | ",
+                Utc::now(),
+                self.stack_set_name,
+                action,
+                self.operation_id
+            )
+            .wrap_err("printing operation")?;
+            writer
+                .set_color(ColorSpec::new().set_fg(Some(operation_status_color(&status))))
+                .wrap_err("setting color")?;
+            write!(writer, "{}", status.as_str()).wrap_err("printing operation status")?;
+            writer.reset().wrap_err("resetting color")?;
+            match operation.status_reason() {
+                Some(reason) => writeln!(writer, " ({})", reason),
+                None => writeln!(writer),
+            }
+            .wrap_err("printing operation status reason")?;
+            self.operation_status = Some(status.clone());
+        }
+
+        if is_operation_finished(&status) {
+            Ok(Some(status))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn fetch_results(&self) -> Result<Vec<StackSetOperationResultSummary>> {
+        let mut results = Vec::new();
+        let mut next_token = None;
+        loop {
+            let input = ListStackSetOperationResultsInput::builder()
+                .stack_set_name(&self.stack_set_name)
+                .operation_id(&self.operation_id)
+                .set_next_token(next_token)
+                .build()
+                .wrap_err("building list stack set operation results input")?;
+            let res = self
+                .client
+                .list_stack_set_operation_results(input)
+                .await
+                .wrap_err("listing stack set operation results")?;
+            results.extend(res.summaries().iter().cloned());
+            match res.next_token {
+                Some(token) => next_token = Some(token),
+                None => break,
+            }
+        }
+        results.sort_by(|a, b| (a.account(), a.region()).cmp(&(b.account(), b.region())));
+        Ok(results)
+    }
+
+    // print the result for an account and region, if its status has changed
+    fn print_result(
+        &mut self,
+        writer: &mut impl WriteColor,
+        result: &StackSetOperationResultSummary,
+    ) -> Result<()> {
+        let (Some(account), Some(region), Some(status)) =
+            (result.account(), result.region(), result.status())
+        else {
+            return Ok(());
+        };
+
+        let key = (account.to_string(), region.to_string());
+        if self.statuses.get(&key) == Some(status) {
+            return Ok(());
+        }
+
+        write!(
+            writer,
+            "{}: {} | {} | {} | ",
+            Utc::now(),
+            self.stack_set_name,
+            account,
+            region
+        )
+        .wrap_err("printing stack instance")?;
+        writer
+            .set_color(ColorSpec::new().set_fg(Some(result_status_color(status))))
+            .wrap_err("setting color")?;
+        write!(writer, "{}", status.as_str()).wrap_err("printing instance status")?;
+        writer.reset().wrap_err("resetting color")?;
+        match result.status_reason() {
+            Some(reason) => writeln!(writer, " ({})", reason),
+            None => writeln!(writer),
+        }
+        .wrap_err("printing instance status reason")?;
+
+        self.statuses.insert(key, status.clone());
+        self.instances_stale = true;
+        Ok(())
+    }
+}
+
+async fn latest_operation_id(client: &Fetcher, stack_set_name: &str) -> Result<String> {
+    let mut latest: Option<(f64, String)> = None;
+    let mut next_token = None;
+    loop {
+        let input = ListStackSetOperationsInput::builder()
+            .stack_set_name(stack_set_name)
+            .set_next_token(next_token)
+            .build()
+            .wrap_err("building list stack set operations input")?;
+        let res = client
+            .list_stack_set_operations(input)
+            .await
+            .wrap_err("listing stack set operations")?;
+
+        for summary in res.summaries() {
+            let (Some(created), Some(id)) = (summary.creation_timestamp(), summary.operation_id())
+            else {
+                continue;
+            };
+            let created = created.as_secs_f64();
+            if latest.as_ref().map(|(t, _)| created > *t).unwrap_or(true) {
+                latest = Some((created, id.to_string()));
+            }
+        }
+
+        match res.next_token {
+            Some(token) => next_token = Some(token),
+            None => break,
+        }
+    }
+
+    latest
+        .map(|(_, id)| id)
+        .ok_or_else(|| eyre::eyre!("no operations found for stack set {}", stack_set_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use aws_sdk_cloudformation::{
+        config::http::HttpResponse,
+        error::SdkError,
+        operation::{
+            cancel_update_stack::{
+                CancelUpdateStackError, CancelUpdateStackInput, CancelUpdateStackOutput,
+            },
+            continue_update_rollback::{
+                ContinueUpdateRollbackError, ContinueUpdateRollbackInput,
+                ContinueUpdateRollbackOutput,
+            },
+            create_change_set::{
+                CreateChangeSetError, CreateChangeSetInput, CreateChangeSetOutput,
+            },
+            describe_change_set::{
+                DescribeChangeSetError, DescribeChangeSetInput, DescribeChangeSetOutput,
+            },
+            describe_stack_events::{
+                DescribeStackEventsError, DescribeStackEventsInput, DescribeStackEventsOutput,
+            },
+            describe_stack_resources::{
+                DescribeStackResourcesError, DescribeStackResourcesInput,
+                DescribeStackResourcesOutput,
+            },
+            describe_stack_set_operation::{
+                DescribeStackSetOperationError, DescribeStackSetOperationOutput,
+            },
+            describe_stacks::{DescribeStacksError, DescribeStacksInput, DescribeStacksOutput},
+            execute_change_set::{
+                ExecuteChangeSetError, ExecuteChangeSetInput, ExecuteChangeSetOutput,
+            },
+            get_template::{GetTemplateError, GetTemplateInput, GetTemplateOutput},
+            list_stack_instances::{ListStackInstancesError, ListStackInstancesOutput},
+            list_stack_set_operation_results::{
+                ListStackSetOperationResultsError, ListStackSetOperationResultsOutput,
+            },
+            list_stack_set_operations::{
+                ListStackSetOperationsError, ListStackSetOperationsOutput,
+            },
+        },
+        types::{StackInstanceSummary, StackSetOperationAction, StackSetOperationSummary},
+    };
+    use aws_smithy_types::DateTime;
+    use std::sync::{Arc, Mutex};
+    use termcolor::NoColor;
+
+    #[derive(Debug, Default)]
+    struct MockClient {
+        // creation time of each operation on the stack set
+        operations: Vec<(String, i64)>,
+        // current status of the operation being watched
+        status: Mutex<Option<StackSetOperationStatus>>,
+        // result of the operation in each (account, region)
+        results: Mutex<Vec<(String, String, StackSetOperationResultStatus)>>,
+        // stack id of each instance (account, region), if it has one yet
+        instances: Mutex<Vec<(String, String, Option<String>)>>,
+        // number of times the instances have been listed
+        instance_listings: Mutex<usize>,
+    }
+
+    impl MockClient {
+        fn set_result(&self, account: &str, region: &str, status: StackSetOperationResultStatus) {
+            let mut results = self.results.lock().unwrap();
+            results.retain(|(a, r, _)| !(a == account && r == region));
+            results.push((account.to_string(), region.to_string(), status));
+        }
+
+        fn add_instance(&self, account: &str, region: &str, stack_name: Option<&str>) {
+            let stack_id = stack_name.map(|name| {
+                format!(
+                    "arn:aws-cn:cloudformation:{}:{}:stack/{}/1",
+                    region, account, name
+                )
+            });
+            self.instances.lock().unwrap().push((
+                account.to_string(),
+                region.to_string(),
+                stack_id,
+            ));
+        }
+    }
+
+    #[async_trait]
+    impl crate::aws::AwsCloudFormationClient for MockClient {
+        async fn describe_stacks(
+            &self,
+            _input: DescribeStacksInput,
+        ) -> std::result::Result<DescribeStacksOutput, SdkError<DescribeStacksError, HttpResponse>>
+        {
+            todo!()
+        }
+
+        async fn describe_stack_events(
+            &self,
+            _input: DescribeStackEventsInput,
+        ) -> std::result::Result<
+            DescribeStackEventsOutput,
+            SdkError<DescribeStackEventsError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn describe_stack_resources(
+            &self,
+            _input: DescribeStackResourcesInput,
+        ) -> std::result::Result<
+            DescribeStackResourcesOutput,
+            SdkError<DescribeStackResourcesError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn describe_stack_set_operation(
+            &self,
+            input: DescribeStackSetOperationInput,
+        ) -> std::result::Result<
+            DescribeStackSetOperationOutput,
+            SdkError<DescribeStackSetOperationError, HttpResponse>,
+        > {
+            let operation = StackSetOperation::builder()
+                .operation_id(input.operation_id().unwrap())
+                .action(StackSetOperationAction::Update)
+                .set_status(self.status.lock().unwrap().clone())
+                .creation_timestamp(DateTime::from_secs(100))
+                .build();
+            Ok(DescribeStackSetOperationOutput::builder()
+                .stack_set_operation(operation)
+                .build())
+        }
+
+        async fn list_stack_set_operations(
+            &self,
+            _input: ListStackSetOperationsInput,
+        ) -> std::result::Result<
+            ListStackSetOperationsOutput,
+            SdkError<ListStackSetOperationsError, HttpResponse>,
+        > {
+            let summaries = self
+                .operations
+                .iter()
+                .map(|(id, created)| {
+                    StackSetOperationSummary::builder()
+                        .operation_id(id)
+                        .creation_timestamp(DateTime::from_secs(*created))
+                        .build()
+                })
+                .collect();
+            Ok(ListStackSetOperationsOutput::builder()
+                .set_summaries(Some(summaries))
+                .build())
+        }
+
+        async fn list_stack_set_operation_results(
+            &self,
+            _input: ListStackSetOperationResultsInput,
+        ) -> std::result::Result<
+            ListStackSetOperationResultsOutput,
+            SdkError<ListStackSetOperationResultsError, HttpResponse>,
+        > {
+            let summaries = self
+                .results
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(account, region, status)| {
+                    StackSetOperationResultSummary::builder()
+                        .account(account)
+                        .region(region)
+                        .status(status.clone())
+                        .build()
+                })
+                .collect();
+            Ok(ListStackSetOperationResultsOutput::builder()
+                .set_summaries(Some(summaries))
+                .build())
+        }
+
+        async fn list_stack_instances(
+            &self,
+            _input: ListStackInstancesInput,
+        ) -> std::result::Result<
+            ListStackInstancesOutput,
+            SdkError<ListStackInstancesError, HttpResponse>,
+        > {
+            *self.instance_listings.lock().unwrap() += 1;
+            let summaries = self
+                .instances
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(account, region, stack_id)| {
+                    StackInstanceSummary::builder()
+                        .account(account)
+                        .region(region)
+                        .set_stack_id(stack_id.clone())
+                        .build()
+                })
+                .collect();
+            Ok(ListStackInstancesOutput::builder()
+                .set_summaries(Some(summaries))
+                .build())
+        }
+
+        async fn describe_change_set(
+            &self,
+            _input: DescribeChangeSetInput,
+        ) -> std::result::Result<
+            DescribeChangeSetOutput,
+            SdkError<DescribeChangeSetError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn create_change_set(
+            &self,
+            _input: CreateChangeSetInput,
+        ) -> std::result::Result<CreateChangeSetOutput, SdkError<CreateChangeSetError, HttpResponse>>
+        {
+            todo!()
+        }
+
+        async fn execute_change_set(
+            &self,
+            _input: ExecuteChangeSetInput,
+        ) -> std::result::Result<
+            ExecuteChangeSetOutput,
+            SdkError<ExecuteChangeSetError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn get_template(
+            &self,
+            _input: GetTemplateInput,
+        ) -> std::result::Result<GetTemplateOutput, SdkError<GetTemplateError, HttpResponse>>
+        {
+            todo!()
+        }
+
+        async fn cancel_update_stack(
+            &self,
+            _input: CancelUpdateStackInput,
+        ) -> std::result::Result<
+            CancelUpdateStackOutput,
+            SdkError<CancelUpdateStackError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn continue_update_rollback(
+            &self,
+            _input: ContinueUpdateRollbackInput,
+        ) -> std::result::Result<
+            ContinueUpdateRollbackOutput,
+            SdkError<ContinueUpdateRollbackError, HttpResponse>,
+        > {
+            todo!()
+        }
+    }
+
+    async fn watcher(client: &Arc<MockClient>) -> StackSetWatcher {
+        StackSetWatcher::new(client.clone(), "my-set", Some("op-1".to_string()))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_latest_operation() {
+        let client = Arc::new(MockClient {
+            operations: vec![
+                ("op-1".to_string(), 100),
+                ("op-3".to_string(), 300),
+                ("op-2".to_string(), 200),
+            ],
+            ..MockClient::default()
+        });
+        let watcher = StackSetWatcher::new(client, "my-set", None).await.unwrap();
+        assert_eq!(watcher.operation_id, "op-3");
+    }
+
+    #[tokio::test]
+    async fn test_poll_step() {
+        use StackSetOperationResultStatus::{Pending, Running, Succeeded};
+
+        let client = Arc::new(MockClient::default());
+        *client.status.lock().unwrap() = Some(StackSetOperationStatus::Running);
+        client.set_result("111111111111", "eu-west-1", Running);
+        client.set_result("111111111111", "us-east-1", Pending);
+        let mut watcher = watcher(&client).await;
+        let mut writer = NoColor::new(Vec::new());
+
+        assert_eq!(watcher.poll_step(&mut writer).await.unwrap(), None);
+        // nothing is printed again until something changes
+        assert_eq!(watcher.poll_step(&mut writer).await.unwrap(), None);
+        client.set_result("111111111111", "eu-west-1", Succeeded);
+        client.set_result("111111111111", "us-east-1", Succeeded);
+        *client.status.lock().unwrap() = Some(StackSetOperationStatus::Succeeded);
+        assert_eq!(
+            watcher.poll_step(&mut writer).await.unwrap(),
+            Some(StackSetOperationStatus::Succeeded)
+        );
+
+        let buf = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<_> = buf
+            .lines()
+            .map(|line| line.split_once(": ").unwrap().1)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "my-set | 111111111111 | eu-west-1 | RUNNING",
+                "my-set | 111111111111 | us-east-1 | PENDING",
+                "my-set - UPDATE op-1 | RUNNING",
+                "my-set | 111111111111 | eu-west-1 | SUCCEEDED",
+                "my-set | 111111111111 | us-east-1 | SUCCEEDED",
+                "my-set - UPDATE op-1 | SUCCEEDED",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_instance_targets() {
+        use StackSetOperationResultStatus::{Pending, Running};
+
+        let client = Arc::new(MockClient::default());
+        *client.status.lock().unwrap() = Some(StackSetOperationStatus::Running);
+        client.set_result("111111111111", "eu-west-1", Running);
+        client.set_result("222222222222", "eu-west-1", Pending);
+        client.add_instance("111111111111", "eu-west-1", Some("StackSet-my-set-a"));
+        client.add_instance("222222222222", "eu-west-1", None);
+        let mut watcher = watcher(&client).await;
+        let mut writer = NoColor::new(Vec::new());
+        watcher.poll_step(&mut writer).await.unwrap();
+
+        let role = InstanceRole {
+            partition: "aws-cn".to_string(),
+            name: "reader".to_string(),
+        };
+        let targets = watcher.instance_targets(Some(&role)).await.unwrap();
+        // instances are tailed by stack name, with the role in their account
+        assert_eq!(
+            targets,
+            vec![StackTarget::new(
+                Location {
+                    credentials: Some(Credentials::AssumeRole(
+                        "arn:aws-cn:iam::111111111111:role/reader".to_string()
+                    )),
+                    region: Some("eu-west-1".to_string()),
+                },
+                "StackSet-my-set-a"
+            )]
+        );
+
+        // the instances are not listed again until the results change
+        watcher.instance_targets(Some(&role)).await.unwrap();
+        assert_eq!(*client.instance_listings.lock().unwrap(), 1);
+
+        client.set_result("222222222222", "eu-west-1", Running);
+        watcher.poll_step(&mut writer).await.unwrap();
+        let targets = watcher.instance_targets(None).await.unwrap();
+        assert_eq!(*client.instance_listings.lock().unwrap(), 2);
+        assert_eq!(targets.len(), 1);
+
+        // the running instance is listed until its stack shows up
+        client.instances.lock().unwrap().pop();
+        client.add_instance("222222222222", "eu-west-1", Some("StackSet-my-set-b"));
+        let targets = watcher.instance_targets(None).await.unwrap();
+        assert_eq!(*client.instance_listings.lock().unwrap(), 3);
+        let names: Vec<_> = targets.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["StackSet-my-set-a", "StackSet-my-set-b"]);
+        watcher.instance_targets(None).await.unwrap();
+        assert_eq!(*client.instance_listings.lock().unwrap(), 3);
+    }
+}
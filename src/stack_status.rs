@@ -62,7 +62,7 @@ impl TryFrom<&str> for StackStatus {
             "IMPORT_ROLLBACK_FAILED" => Ok(ImportRollbackFailed),
             "IMPORT_ROLLBACK_COMPLETE" => Ok(ImportRollbackComplete),
             "DELETE_SKIPPED" => Ok(DeleteSkipped),
-            _ => unreachable!("{}", value),
+            _ => Err(Error::Other(format!("unknown stack status {}", value))),
         }
     }
 }
@@ -119,4 +119,51 @@ impl StackStatus {
                 | Self::ImportRollbackComplete
         )
     }
+
+    // the stack is stuck until it is fixed by hand, e.g. by continuing the
+    // rollback or deleting it again. Creates and updates only fail without
+    // rolling back when rollback is disabled
+    pub(crate) fn is_failed(&self) -> bool {
+        matches!(
+            self,
+            Self::CreateFailed
+                | Self::UpdateFailed
+                | Self::RollbackFailed
+                | Self::UpdateRollbackFailed
+                | Self::ImportRollbackFailed
+                | Self::DeleteFailed
+        )
+    }
+
+    // no more events will arrive until the next operation starts
+    pub(crate) fn is_terminal(&self) -> bool {
+        self.is_complete() || self.is_failed()
+    }
+
+    // complete, but only after rolling back the deployment
+    pub(crate) fn is_rolled_back(&self) -> bool {
+        matches!(
+            self,
+            Self::RollbackComplete | Self::UpdateRollbackComplete | Self::ImportRollbackComplete
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_terminal() {
+        let status = |s| StackStatus::try_from(s).unwrap();
+        // deployments with rollback disabled stop at the failure
+        assert!(status("CREATE_FAILED").is_failed());
+        assert!(status("UPDATE_FAILED").is_terminal());
+        assert!(status("UPDATE_ROLLBACK_FAILED").is_failed());
+        assert!(status("UPDATE_ROLLBACK_COMPLETE").is_rolled_back());
+        assert!(!status("UPDATE_ROLLBACK_IN_PROGRESS").is_terminal());
+        assert!(!status("UPDATE_COMPLETE").is_failed());
+        // statuses added to CloudFormation later are not known
+        assert!(StackStatus::try_from("UPDATE_PAUSED").is_err());
+    }
 }
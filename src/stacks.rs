@@ -1,26 +1,239 @@
-use crate::aws::AwsCloudFormationClient;
+use crate::aws::Clients;
 use eyre::{Context, Result};
-use std::{collections::HashSet, iter::FromIterator};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Source of credentials for talking to a stack
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum Credentials {
+    /// Named profile from the shared AWS config
+    Profile(String),
+    /// IAM role to assume, starting from the default credentials
+    AssumeRole(String),
+}
+
+impl Credentials {
+    // profile name, or the account id for assumed roles
+    pub(crate) fn label(&self) -> &str {
+        match self {
+            Credentials::Profile(name) => name,
+            Credentials::AssumeRole(arn) => arn.split(':').nth(4).unwrap_or(arn),
+        }
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::Profile(name) => f.write_str(name),
+            Credentials::AssumeRole(arn) => f.write_str(arn),
+        }
+    }
+}
+
+/// Where a stack lives, used to pick the client that talks to it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Location {
+    pub(crate) credentials: Option<Credentials>,
+    pub(crate) region: Option<String>,
+}
+
+impl Location {
+    // profile name or account id of the credentials, when the account alias
+    // has not been looked up
+    pub(crate) fn account_label(&self) -> Option<&str> {
+        self.credentials.as_ref().map(Credentials::label)
+    }
+
+    // label of the region to show next to events, if given explicitly
+    pub(crate) fn region_label(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+}
+
+// partitions and areas that region names start with, followed by a direction
+// and a number, e.g. `eu-west-1` or `us-gov-east-1`
+const REGION_PREFIXES: &[&str] = &[
+    "af", "ap", "ca", "cn", "eu", "il", "me", "mx", "sa", "us", "us-gov", "us-iso", "us-isob",
+    "us-isof", "eu-isoe",
+];
+const REGION_DIRECTIONS: &[&str] = &[
+    "central",
+    "north",
+    "south",
+    "east",
+    "west",
+    "northeast",
+    "northwest",
+    "southeast",
+    "southwest",
+];
+
+fn is_region(s: &str) -> bool {
+    let Some((rest, number)) = s.rsplit_once('-') else {
+        return false;
+    };
+    let Some((prefix, direction)) = rest.rsplit_once('-') else {
+        return false;
+    };
+    !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit())
+        && REGION_PREFIXES.contains(&prefix)
+        && REGION_DIRECTIONS.contains(&direction)
+}
+
+// shaped like a region name, e.g. a misspelt one, though it may be a profile
+fn looks_like_region(s: &str) -> bool {
+    let parts: Vec<_> = s.split('-').collect();
+    parts.len() >= 3
+        && parts[0].len() == 2
+        && parts[0].chars().all(|c| c.is_ascii_lowercase())
+        && parts[parts.len() - 1].chars().all(|c| c.is_ascii_digit())
+        && parts[1..parts.len() - 1]
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+/// Check that `region` is the name of an AWS region
+pub(crate) fn check_region(region: &str) -> Result<()> {
+    if !is_region(region) {
+        eyre::bail!(
+            "unknown region {:?}, expected a region name such as eu-west-1",
+            region
+        );
+    }
+    Ok(())
+}
+
+/// A stack to tail, optionally qualified by the credentials and region to use
+///
+/// The accepted forms are `stack-name`, `region:stack-name`, `profile:stack-name`,
+/// `profile:region:stack-name`, and the same with an IAM role arn in place of the profile.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct StackTarget {
+    pub(crate) location: Location,
+    pub(crate) name: String,
+}
+
+impl StackTarget {
+    pub(crate) fn new(location: Location, name: impl Into<String>) -> Self {
+        Self {
+            location,
+            name: name.into(),
+        }
+    }
+}
+
+impl FromStr for StackTarget {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut location = Location::default();
+        let mut parts: Vec<&str> = s.split(':').collect();
+
+        if parts[0] == "arn" {
+            // stack arns contain colons but already include the region
+            if parts.get(2) != Some(&"iam") {
+                return Ok(Self::new(location, s));
+            }
+            if parts.len() < 7 {
+                eyre::bail!("invalid stack target {:?}, expected role-arn:stack-name", s);
+            }
+            location.credentials = Some(Credentials::AssumeRole(parts[..6].join(":")));
+            parts.drain(..6);
+        }
+
+        let (name, prefix) = parts.split_last().expect("split always yields one part");
+        if name.is_empty() || prefix.iter().any(|p| p.is_empty()) {
+            eyre::bail!("invalid stack target {:?}", s);
+        }
+
+        match prefix {
+            [] => {}
+            [region] if is_region(region) => location.region = Some(region.to_string()),
+            [profile] if location.credentials.is_none() => {
+                // profiles may be named like regions, e.g. `qa-env-2`
+                if looks_like_region(profile) {
+                    tracing::warn!(%profile, "unknown region, using it as a profile");
+                }
+                location.credentials = Some(Credentials::Profile(profile.to_string()))
+            }
+            [profile, region] if location.credentials.is_none() && is_region(region) => {
+                location.credentials = Some(Credentials::Profile(profile.to_string()));
+                location.region = Some(region.to_string());
+            }
+            [.., region] if !is_region(region) => {
+                check_region(region).wrap_err_with(|| format!("invalid stack target {:?}", s))?
+            }
+            _ => eyre::bail!(
+                "invalid stack target {:?}, expected [profile:][region:]stack-name",
+                s
+            ),
+        }
+
+        Ok(Self::new(location, *name))
+    }
+}
+
+impl fmt::Display for StackTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(credentials) = &self.location.credentials {
+            write!(f, "{}:", credentials)?;
+        }
+        if let Some(region) = &self.location.region {
+            write!(f, "{}:", region)?;
+        }
+        f.write_str(&self.name)
+    }
+}
+
+/// Expand targets without an explicit region into one target per region in `regions`
+pub(crate) fn expand_regions(targets: &[StackTarget], regions: &[String]) -> Vec<StackTarget> {
+    if regions.is_empty() {
+        return targets.to_vec();
+    }
+
+    let mut expanded = Vec::new();
+    for target in targets {
+        if target.location.region.is_some() {
+            expanded.push(target.clone());
+            continue;
+        }
+
+        for region in regions {
+            let mut location = target.location.clone();
+            location.region = Some(region.clone());
+            expanded.push(StackTarget::new(location, target.name.clone()));
+        }
+    }
+    expanded
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct StackInfo {
-    pub(crate) names: HashSet<String>,
+    pub(crate) names: HashSet<StackTarget>,
     pub(crate) original_names: HashSet<String>,
 }
 
 pub(crate) async fn build_stack_list(
-    client: &impl AwsCloudFormationClient,
-    stacks: &[String],
+    clients: &Clients,
+    stacks: &[StackTarget],
     nested: bool,
 ) -> Result<StackInfo> {
-    let original_names = HashSet::from_iter(stacks.iter().cloned());
+    let original_names = stacks.iter().map(|s| s.name.clone()).collect();
     if nested {
         let mut names = HashSet::new();
         for stack in stacks {
-            let nested = crate::nested_stacks::fetch_nested_stack_names(client, stack)
+            let client = clients.get(&stack.location)?;
+            let nested = crate::nested_stacks::fetch_nested_stack_names(&**client, &stack.name)
                 .await
                 .wrap_err("fetching nested stack names")?;
-            names.extend(nested.iter().cloned());
+            names.extend(
+                nested
+                    .into_iter()
+                    .map(|name| StackTarget::new(stack.location.clone(), name)),
+            );
         }
 
         Ok(StackInfo {
@@ -28,10 +241,96 @@ pub(crate) async fn build_stack_list(
             original_names,
         })
     } else {
-        let names = HashSet::from_iter(stacks.iter().cloned());
+        let names = stacks.iter().cloned().collect();
         Ok(StackInfo {
             names,
             original_names,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stack_target() {
+        let target: StackTarget = "eu-west-1:my-stack".parse().unwrap();
+        assert_eq!(
+            target,
+            StackTarget::new(
+                Location {
+                    credentials: None,
+                    region: Some("eu-west-1".to_string()),
+                },
+                "my-stack"
+            )
+        );
+
+        let target: StackTarget = "my-stack".parse().unwrap();
+        assert_eq!(target, StackTarget::new(Location::default(), "my-stack"));
+
+        assert!(":my-stack".parse::<StackTarget>().is_err());
+
+        let target: StackTarget = "us-gov-west-1:my-stack".parse().unwrap();
+        assert_eq!(target.location.region_label(), Some("us-gov-west-1"));
+
+        // names shaped like regions are profiles unless they are known regions
+        let target: StackTarget = "qa-env-2:my-stack".parse().unwrap();
+        assert_eq!(
+            target.location.credentials,
+            Some(Credentials::Profile("qa-env-2".to_string()))
+        );
+        assert_eq!(target.location.region, None);
+        let err = "prod:eu-wset-1:my-stack"
+            .parse::<StackTarget>()
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown region \"eu-wset-1\""));
+        assert!("prod:eu-west:my-stack".parse::<StackTarget>().is_err());
+        assert!(check_region("ap-southeast-2").is_ok());
+        assert!(check_region("europe-1").is_err());
+    }
+
+    #[test]
+    fn test_parse_stack_target_credentials() {
+        let target: StackTarget = "prod:my-stack".parse().unwrap();
+        assert_eq!(
+            target.location.credentials,
+            Some(Credentials::Profile("prod".to_string()))
+        );
+        assert_eq!(target.location.region, None);
+
+        let target: StackTarget = "prod:us-east-1:my-stack".parse().unwrap();
+        assert_eq!(target.location.account_label(), Some("prod"));
+        assert_eq!(target.location.region_label(), Some("us-east-1"));
+        assert_eq!(target.to_string(), "prod:us-east-1:my-stack");
+
+        let arn = "arn:aws:iam::123456789012:role/deployer";
+        let target: StackTarget = format!("{}:eu-west-1:my-stack", arn).parse().unwrap();
+        assert_eq!(
+            target.location.credentials,
+            Some(Credentials::AssumeRole(arn.to_string()))
+        );
+        assert_eq!(target.location.account_label(), Some("123456789012"));
+        assert_eq!(target.location.region_label(), Some("eu-west-1"));
+        assert_eq!(target.name, "my-stack");
+
+        let stack_arn = "arn:aws:cloudformation:us-east-1:123456789012:stack/my-stack/abc";
+        let target: StackTarget = stack_arn.parse().unwrap();
+        assert_eq!(target, StackTarget::new(Location::default(), stack_arn));
+    }
+
+    #[test]
+    fn test_expand_regions() {
+        let targets = vec![
+            "a".parse().unwrap(),
+            "us-east-1:b".parse::<StackTarget>().unwrap(),
+        ];
+        let regions = vec!["eu-west-1".to_string(), "eu-west-2".to_string()];
+        let expanded: Vec<String> = expand_regions(&targets, &regions)
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(expanded, vec!["eu-west-1:a", "eu-west-2:a", "us-east-1:b"]);
+    }
+}
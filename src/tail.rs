@@ -1,59 +1,143 @@
 use aws_sdk_cloudformation::error::SdkError;
+use aws_sdk_cloudformation::operation::cancel_update_stack::CancelUpdateStackInput;
+use aws_sdk_cloudformation::operation::continue_update_rollback::ContinueUpdateRollbackInput;
 use aws_sdk_cloudformation::operation::describe_stack_events::DescribeStackEventsInput;
 use aws_sdk_cloudformation::operation::describe_stacks::DescribeStacksInput;
-use aws_sdk_cloudformation::types::StackEvent;
+use aws_sdk_cloudformation::operation::get_template::GetTemplateInput;
+use aws_sdk_cloudformation::types::{StackEvent, TemplateStage};
+use aws_smithy_types::error::metadata::ProvideErrorMetadata;
 use aws_smithy_types_convert::date_time::DateTimeExt;
 use chrono::{DateTime, Utc};
 use eyre::{Context, Result};
 use futures::future::join_all;
 use notify_rust::Notification;
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt::Debug;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{self, AtomicBool};
 use std::sync::Arc;
 use std::time::Duration;
 use term_table::{row::Row, Table, TableStyle};
 use termcolor::{Color, ColorSpec, WriteColor};
 use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
 use tokio::time::sleep;
 use tracing::Instrument;
 
+use crate::aws::Clients;
+use crate::ci::CiPlatform;
+use crate::durations::{self, Durations};
 use crate::error::Error;
-use crate::stacks::StackInfo;
+use crate::history::{History, Operation};
+use crate::hooks::Hooks;
+use crate::interrupt::{self, Choice, Interrupts, Options, Signal};
+use crate::progress::{format_duration, Progress, SlowestResource};
+use crate::report::{Failure, Report, ReportFormat};
+use crate::stack_status::StackStatus;
+use crate::stacks::{Location, StackInfo, StackTarget};
+use crate::template::{ConstructPaths, Template};
+use crate::trace::Trace;
+use crate::webhook::{Payload, Webhook, WebhookEvent};
+
+/// A stack event along with the location it was fetched from
+#[derive(Debug, Clone)]
+pub(crate) struct LocatedEvent {
+    pub(crate) location: Location,
+    pub(crate) event: StackEvent,
+}
 
-fn event_sort_key(a: &StackEvent, b: &StackEvent) -> std::cmp::Ordering {
-    let a_timestamp = a.timestamp.as_ref().unwrap().as_secs_f64();
-    let b_timestamp = b.timestamp.as_ref().unwrap().as_secs_f64();
+fn event_sort_key(a: &LocatedEvent, b: &LocatedEvent) -> std::cmp::Ordering {
+    let a_timestamp = a.event.timestamp.as_ref().unwrap().as_secs_f64();
+    let b_timestamp = b.event.timestamp.as_ref().unwrap().as_secs_f64();
 
     a_timestamp.partial_cmp(&b_timestamp).unwrap()
 }
 
+/// What a desktop notification says about a stack
+#[derive(Debug, Clone, PartialEq)]
+struct Notice {
+    summary: String,
+    body: String,
+    /// Failures and warnings are shown as critical, with the failure sound
+    failure: bool,
+}
+
+impl Notice {
+    // the stack finished deploying, with the first resource that failed if it
+    // rolled back
+    fn finished(
+        stack_name: &str,
+        status: &str,
+        duration: Option<chrono::Duration>,
+        first_failure: Option<&Failure>,
+    ) -> Self {
+        let failure = status.contains("ROLLBACK") || status.ends_with("_FAILED");
+        let mut body = status.to_string();
+        if let Some(duration) = duration {
+            body.push_str(&format!(" after {}", format_duration(duration)));
+        }
+        if let (true, Some(first_failure)) = (failure, first_failure) {
+            body.push_str(&format!(
+                "\n{}: {}",
+                first_failure.logical_id, first_failure.reason
+            ));
+        }
+        Self {
+            summary: if failure {
+                format!("{} failed to deploy", stack_name)
+            } else {
+                format!("{} deployed", stack_name)
+            },
+            body,
+            failure,
+        }
+    }
+
+    fn rollback_started(stack_name: &str, reason: Option<&str>) -> Self {
+        Self {
+            summary: format!("{} is rolling back", stack_name),
+            body: reason.unwrap_or("rollback started").to_string(),
+            failure: true,
+        }
+    }
+}
+
 #[cfg(target_os = "macos")]
-fn notify(sound: impl AsRef<str>) -> Result<()> {
-    let sound_name = sound.as_ref();
+fn notify(notice: &Notice, sound: &str) -> Result<()> {
     Notification::new()
-        .summary("Deploy finished")
-        .body("deploy finished")
+        .summary(&notice.summary)
+        .body(&notice.body)
         .appname("cftail")
-        .sound_name(sound_name)
+        .sound_name(sound)
         .show()?;
     Ok(())
 }
 
-// We can customise this further on linux, but I don't have a copy available
+// the sound is passed as the freedesktop `sound-name` hint
 #[cfg(target_os = "linux")]
-fn notify(_sound: impl AsRef<str>) -> Result<()> {
+fn notify(notice: &Notice, sound: &str) -> Result<()> {
+    use notify_rust::Urgency;
+
+    let (icon, urgency) = if notice.failure {
+        ("dialog-error", Urgency::Critical)
+    } else {
+        ("dialog-information", Urgency::Normal)
+    };
     Notification::new()
-        .summary("Deploy finished")
-        .body("deploy finished")
+        .summary(&notice.summary)
+        .body(&notice.body)
         .appname("cftail")
+        .icon(icon)
+        .urgency(urgency)
+        .sound_name(sound)
         .show()?;
     Ok(())
 }
 
 #[cfg(not(any(target_os = "macos", target_os = "linux")))]
-fn notify(_sound: impl AsRef<str>) -> Result<()> {
+fn notify(_notice: &Notice, _sound: &str) -> Result<()> {
     Ok(())
 }
 
@@ -65,10 +149,79 @@ pub(crate) struct TailConfig<'a> {
     pub(crate) show_notifications: bool,
     pub(crate) show_outputs: bool,
     pub(crate) show_resource_types: bool,
+    pub(crate) show_construct_paths: bool,
+    pub(crate) show_progress: bool,
+    /// Warn when no events have arrived for this long while resources are in
+    /// progress
+    pub(crate) stuck_after: Option<chrono::Duration>,
+    /// Database to store every event and finished operation in
+    pub(crate) history_path: Option<PathBuf>,
+    /// File to write a Chrome trace of the deployment to
+    pub(crate) trace_out: Option<PathBuf>,
+    /// Reports to write each time a stack finishes deploying
+    pub(crate) reports: Vec<(ReportFormat, PathBuf)>,
+    /// CI service to produce native output for
+    pub(crate) ci: Option<CiPlatform>,
+    /// Where to post the result of each deployment
+    pub(crate) webhook: Option<Webhook>,
+    /// Commands to run on each event and the result of each deployment
+    pub(crate) hooks: Hooks,
+    /// Local templates of the stacks, keyed by stack name
+    pub(crate) templates: HashMap<String, Template>,
     pub(crate) sound: String,
+    /// Sound to play when a stack fails or starts rolling back
+    pub(crate) failure_sound: String,
+    /// Stop tailing if the stacks have not finished deploying by this time
+    pub(crate) deadline: Option<DateTime<Utc>>,
+    /// Cancel updates in progress at the deadline, and tail the rollback
+    pub(crate) cancel_on_timeout: bool,
     pub(crate) should_exit_on_completion: bool,
 }
 
+/// How the deployments of the tailed stacks finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    Succeeded,
+    RolledBack,
+    /// Left in a failed state, e.g. when the rollback or delete failed
+    Failed,
+    TimedOut,
+    /// Stopped tailing before the stacks finished deploying
+    Interrupted(Signal),
+}
+
+impl Outcome {
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Outcome::Succeeded => 0,
+            Outcome::RolledBack => 2,
+            Outcome::TimedOut => 3,
+            Outcome::Failed => 4,
+            Outcome::Interrupted(signal) => signal.exit_code(),
+        }
+    }
+}
+
+// a resource operation that has not finished yet
+#[derive(Debug, Clone)]
+struct ResourceStart {
+    stack_name: String,
+    resource_type: String,
+    action: String,
+    started: DateTime<Utc>,
+    // whether it has been reported as taking much longer than usual
+    warned_slow: bool,
+}
+
+// the latest status of one of the tailed stacks
+#[derive(Debug, Clone)]
+struct StackState {
+    location: Location,
+    stack_name: String,
+    status: String,
+    timestamp: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Copy)]
 enum TailMode {
     None,
@@ -77,31 +230,137 @@ enum TailMode {
 }
 
 pub(crate) struct Tail<'a, W> {
-    fetcher: Arc<dyn crate::aws::AwsCloudFormationClient + Sync + Send>,
+    clients: Clients,
     writer: &'a mut W,
     config: TailConfig<'a>,
     mode: TailMode,
     should_quit: Arc<AtomicBool>,
+    // latest outcome of each stack that has finished deploying
+    outcomes: HashMap<String, Outcome>,
+    // start of the latest deployment of each stack
+    deployment_starts: HashMap<String, DateTime<Utc>>,
+    // construct paths of the resources in each stack, keyed by the stack id
+    // and the deployment that the template was fetched for
+    construct_paths: HashMap<(String, Option<DateTime<Utc>>), ConstructPaths>,
+    // progress through the current operation on each stack, keyed by stack
+    // id, and whether it is on screen
+    progress: HashMap<String, Progress>,
+    progress_drawn: bool,
+    // resources in progress, keyed by stack id and logical id
+    resource_starts: HashMap<(String, String), ResourceStart>,
+    history: Option<History>,
+    // how long resources have taken to deploy before, from the history and
+    // updated as they finish
+    durations: Option<Durations>,
+    trace: Option<Trace>,
+    // events of the current operation on each stack, including those of its
+    // nested stacks, keyed by stack id, for the reports
+    report_events: HashMap<String, Vec<StackEvent>>,
+    // id of the stack that each nested stack is reported with
+    report_roots: HashMap<String, String>,
+    // title of the open CI log group of each stack, keyed by stack id
+    ci_groups: HashMap<String, String>,
+    // resources that failed in the current operation on each stack,
+    // including those of its nested stacks, keyed by stack id
+    failures: HashMap<String, Vec<Failure>>,
+    // time of the last event when the deployment was reported as stuck, so
+    // that each quiet period is only reported once
+    stuck_warned_at: Option<DateTime<Utc>>,
+    // latest status of each of the stacks being tailed, keyed by stack id
+    stack_states: HashMap<String, StackState>,
+    // whether any of the stacks has finished deploying, so that cftail can
+    // quit once the rest have too
+    finishing: bool,
+    timed_out: bool,
+    // signal that tailing was stopped by
+    detached: Option<Signal>,
+    // webhooks being sent and result hooks running
+    background: JoinSet<()>,
+    // the latest `--on-event` hook, which the next one waits for
+    event_hook: Option<JoinHandle<()>>,
 }
 
 impl<'a, W> Tail<'a, W>
 where
     W: WriteColor + Debug,
 {
-    pub(crate) fn new(
-        config: TailConfig<'a>,
-        fetcher: Arc<dyn crate::aws::AwsCloudFormationClient + Sync + Send>,
-        writer: &'a mut W,
-    ) -> Self {
+    pub(crate) fn new(config: TailConfig<'a>, clients: Clients, writer: &'a mut W) -> Self {
+        let history = config.history_path.as_ref().and_then(|path| {
+            History::open(path)
+                .map_err(|e| tracing::warn!(err = %e, "opening history"))
+                .ok()
+        });
+        let durations = history.as_ref().and_then(|history| {
+            history
+                .durations()
+                .map_err(|e| tracing::warn!(err = %e, "loading resource durations"))
+                .ok()
+        });
+        let trace = config.trace_out.clone().map(Trace::new);
         Self {
             config,
-            fetcher,
+            clients,
             writer,
             mode: TailMode::None,
             should_quit: Arc::new(AtomicBool::new(false)),
+            outcomes: HashMap::new(),
+            deployment_starts: HashMap::new(),
+            construct_paths: HashMap::new(),
+            progress: HashMap::new(),
+            progress_drawn: false,
+            resource_starts: HashMap::new(),
+            history,
+            durations,
+            trace,
+            report_events: HashMap::new(),
+            report_roots: HashMap::new(),
+            ci_groups: HashMap::new(),
+            failures: HashMap::new(),
+            stuck_warned_at: None,
+            stack_states: HashMap::new(),
+            finishing: false,
+            timed_out: false,
+            detached: None,
+            background: JoinSet::new(),
+            event_hook: None,
         }
     }
 
+    /// Whether the deadline passed, or any of the stacks failed or rolled back the last time it finished deploying
+    pub(crate) fn outcome(&self) -> Outcome {
+        if let Some(signal) = self.detached {
+            Outcome::Interrupted(signal)
+        } else if self.timed_out {
+            Outcome::TimedOut
+        } else if self.outcomes.values().any(|o| *o == Outcome::Failed) {
+            Outcome::Failed
+        } else if self.outcomes.values().any(|o| *o == Outcome::RolledBack) {
+            Outcome::RolledBack
+        } else {
+            Outcome::Succeeded
+        }
+    }
+
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        self.writer
+    }
+
+    /// Time of the latest event that has been shown
+    pub(crate) fn since(&self) -> DateTime<Utc> {
+        self.config.since
+    }
+
+    /// Print a line of output from the `--exec` command, moving the progress
+    /// line below it
+    pub(crate) async fn print_command_output(&mut self, line: &str) -> Result<()> {
+        self.clear_progress()?;
+        writeln!(self.writer, "{}", line).wrap_err("printing command output")?;
+        if let TailMode::Tail = self.mode {
+            self.draw_progress().await?;
+        }
+        Ok(())
+    }
+
     // Fetch all of the events since the beginning of time, so that we can ensure all
     // of the events are sorted.
     #[tracing::instrument(skip(self))]
@@ -122,8 +381,7 @@ where
 
         let mut latest_time = self.config.since;
         for e in &all_events {
-            // let timestamp = crate::utils::parse_event_datetime(e.timestamp.as_str())?;
-            let timestamp = e.timestamp().unwrap().to_chrono_utc().unwrap();
+            let timestamp = e.event.timestamp().unwrap().to_chrono_utc().unwrap();
             self.print_event(e).await.expect("printing");
             tracing::trace!(latest_time = ?latest_time, timestamp = ?timestamp, "later timestamp");
             if timestamp > latest_time {
@@ -134,43 +392,220 @@ where
         }
         tracing::trace!(latest_time = ?latest_time, "setting config.since");
         self.config.since = latest_time;
+        self.save_trace();
+        self.record_events(&all_events);
+        self.draw_progress().await
+    }
+
+    /// Prefetch the events, giving up if interrupted part way through since
+    /// there is nothing to summarise yet
+    pub(crate) async fn prefetch_or_stop(&mut self, interrupts: &mut Interrupts) -> Result<()> {
+        let signal = tokio::select! {
+            res = self.prefetch() => return res,
+            signal = interrupts.recv() => signal,
+        };
+        self.clear_progress()?;
+        self.writer.reset().wrap_err("resetting color")?;
+        writeln!(self.writer, "\nInterrupted while fetching past events")
+            .wrap_err("printing interruption")?;
+        self.detached = Some(signal);
         Ok(())
     }
 
-    #[tracing::instrument(skip(self))]
-    pub(crate) async fn poll(&mut self) -> Result<()> {
+    #[tracing::instrument(skip(self, interrupts))]
+    pub(crate) async fn poll(&mut self, interrupts: &mut Interrupts) -> Result<()> {
         tracing::debug!(start_time = ?self.config.since, "showing logs from now");
-        self.mode = TailMode::Tail;
-
         loop {
-            match self.poll_step().await {
-                Ok(true) => {
-                    if self.config.should_exit_on_completion {
+            if !self.poll_once().await? {
+                return Ok(());
+            }
+
+            tracing::trace!("sleeping");
+            tokio::select! {
+                _ = sleep(Duration::from_secs(5)) => {}
+                signal = interrupts.recv() => {
+                    if !self.interrupted(signal, interrupts).await? {
                         return Ok(());
                     }
                 }
-                Ok(false) => {}
-                Err(e) => {
-                    match e.downcast::<Error<()>>() {
-                        Ok(e) => match e {
-                            Error::CredentialsExpired => {
-                                // We have to surface this back up to the main
-                                // function, as this will create a new client and
-                                // try again
-                                return Err(e).wrap_err("expired credentials");
-                            }
-                            _ => {
-                                tracing::warn!(err = %e, "unhandled error");
-                            }
-                        },
-                        Err(e) => tracing::error!(err = %e, "unhandled error"),
-                    }
+            }
+        }
+    }
+
+    /// Show any new events and check the deadline, returning whether to keep
+    /// tailing
+    pub(crate) async fn poll_once(&mut self) -> Result<bool> {
+        if self.detached.is_some() {
+            return Ok(false);
+        }
+        self.mode = TailMode::Tail;
+        match self.poll_step().await {
+            Ok(true) => {
+                if self.config.should_exit_on_completion || self.timed_out {
+                    return Ok(false);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => {
+                match e.downcast::<Error<()>>() {
+                    Ok(e) => match e {
+                        Error::CredentialsExpired => {
+                            // We have to surface this back up to the main
+                            // function, as this will create a new client and
+                            // try again
+                            return Err(e).wrap_err("expired credentials");
+                        }
+                        _ => {
+                            tracing::warn!(err = %e, "unhandled error");
+                        }
+                    },
+                    Err(e) => tracing::error!(err = %e, "unhandled error"),
                 }
             }
+        }
 
-            tracing::trace!("sleeping");
-            sleep(Duration::from_secs(5)).await;
+        let past_deadline = self.config.deadline.is_some_and(|d| Utc::now() >= d);
+        if past_deadline && !self.timed_out {
+            self.timed_out = true;
+            return self.time_out().await;
+        }
+        Ok(true)
+    }
+
+    /// Print what has happened so far, and ask whether to cancel the update,
+    /// retry a failed rollback or detach. Returns whether to keep tailing
+    pub(crate) async fn interrupted(
+        &mut self,
+        signal: Signal,
+        interrupts: &mut Interrupts,
+    ) -> Result<bool> {
+        let options = self.summarise(Utc::now()).await?;
+        let choice = match signal {
+            Signal::Interrupt if options.in_progress || options.can_continue_rollback => {
+                interrupt::prompt(
+                    self.writer,
+                    interrupts,
+                    options.can_cancel,
+                    options.can_continue_rollback,
+                )
+                .await?
+            }
+            _ => Choice::Detach,
+        };
+        self.act_on(signal, choice).await
+    }
+
+    // print what has happened so far, returning what can be done about the
+    // deployment going by the current status of the stacks
+    async fn summarise(&mut self, now: DateTime<Utc>) -> Result<Options> {
+        self.clear_progress()?;
+        self.writer.reset().wrap_err("resetting color")?;
+        writeln!(self.writer).wrap_err("printing summary")?;
+
+        let (in_progress, rollback_failed): (Vec<_>, Vec<_>) = self
+            .stacks_in(|status| {
+                status.ends_with("_IN_PROGRESS") || status == "UPDATE_ROLLBACK_FAILED"
+            })
+            .await
+            .into_iter()
+            .partition(|(_, state)| state.status.ends_with("_IN_PROGRESS"));
+        let can_cancel = in_progress
+            .iter()
+            .any(|(_, state)| state.status == "UPDATE_IN_PROGRESS");
+        let can_continue_rollback = !rollback_failed.is_empty();
+        self.print_summary(now, &in_progress)?;
+        Ok(Options {
+            in_progress: !in_progress.is_empty(),
+            can_cancel,
+            can_continue_rollback,
+        })
+    }
+
+    // do what was chosen once interrupted, returning whether to keep tailing
+    async fn act_on(&mut self, signal: Signal, choice: Choice) -> Result<bool> {
+        match choice {
+            Choice::CancelUpdate => {
+                self.cancel_updates().await?;
+            }
+            Choice::ContinueRollback => {
+                self.continue_rollbacks().await?;
+            }
+            Choice::Watch => {}
+            Choice::Detach => {
+                self.detached = Some(signal);
+                self.save_trace();
+                self.writer.flush().wrap_err("flushing output")?;
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // the resources in progress and failures of the current operation, or the
+    // status of the stacks if cftail started part way through it
+    fn print_summary(
+        &mut self,
+        now: DateTime<Utc>,
+        in_progress: &[(String, StackState)],
+    ) -> Result<()> {
+        match self.progress.values().map(Progress::started).min() {
+            Some(started) => writeln!(
+                self.writer,
+                "Interrupted {} into the deployment",
+                format_duration(now - started)
+            ),
+            None if !in_progress.is_empty() => {
+                let states: Vec<String> = in_progress
+                    .iter()
+                    .map(|(_, state)| format!("{} is in {}", state.stack_name, state.status))
+                    .collect();
+                writeln!(self.writer, "Interrupted while {}", states.join(", "))
+            }
+            None => {
+                writeln!(self.writer, "Interrupted, no deployment in progress")
+                    .wrap_err("printing summary")?;
+                return Ok(());
+            }
         }
+        .wrap_err("printing summary")?;
+
+        let mut starts: Vec<_> = self.resource_starts.iter().collect();
+        starts.sort_by_key(|(_, start)| start.started);
+        if !starts.is_empty() {
+            writeln!(self.writer, "In progress:").wrap_err("printing summary")?;
+        }
+        for ((_, logical_id), start) in starts {
+            writeln!(
+                self.writer,
+                "  {} - {} ({}) for {}",
+                start.stack_name,
+                logical_id,
+                start.resource_type,
+                format_duration(now - start.started)
+            )
+            .wrap_err("printing summary")?;
+        }
+
+        let mut failures: Vec<_> = self.failures.values().flatten().cloned().collect();
+        failures.sort_by_key(|failure| failure.timestamp);
+        if !failures.is_empty() {
+            writeln!(self.writer, "Failed:").wrap_err("printing summary")?;
+        }
+        for failure in &failures {
+            let mut spec = ColorSpec::new();
+            spec.set_fg(Some(Color::Red));
+            write!(
+                self.writer,
+                "  {} - {} ({}) ",
+                failure.stack_name, failure.logical_id, failure.resource_type
+            )
+            .wrap_err("printing summary")?;
+            self.writer.set_color(&spec).wrap_err("setting color")?;
+            write!(self.writer, "{}", failure.status).wrap_err("printing summary")?;
+            self.writer.reset().wrap_err("resetting color")?;
+            writeln!(self.writer, ": {}", failure.reason).wrap_err("printing summary")?;
+        }
+        Ok(())
     }
 
     #[tracing::instrument(skip(self))]
@@ -180,12 +615,17 @@ where
             .await?;
         if all_events.is_empty() {
             tracing::debug!("no events found");
-            return Ok(false);
+            self.warn_if_stuck(Utc::now())?;
+            self.draw_progress().await?;
+            return self.should_quit().await;
         }
+        // stored first, so that the durations of an operation that finishes
+        // in this batch are worked out from all of its events
+        self.record_events(&all_events);
 
         let mut latest_time = self.config.since;
         for event in &all_events {
-            let timestamp = event.timestamp().unwrap().to_chrono_utc().unwrap();
+            let timestamp = event.event.timestamp().unwrap().to_chrono_utc().unwrap();
             self.print_event(event).await.expect("printing");
             tracing::trace!(latest_time = ?latest_time, timestamp = ?timestamp, "later timestamp");
             if timestamp > latest_time {
@@ -197,16 +637,105 @@ where
 
         tracing::trace!(latest_time = ?latest_time, "setting config.since");
         self.config.since = latest_time;
+        self.save_trace();
+        self.warn_if_stuck(Utc::now())?;
+        self.draw_progress().await?;
 
-        if self.should_quit.load(atomic::Ordering::SeqCst) {
-            return Ok(true);
+        self.should_quit().await
+    }
+
+    // whether to stop tailing, once every stack has finished deploying
+    async fn should_quit(&mut self) -> Result<bool> {
+        let wants_quit = self.config.should_exit_on_completion || self.timed_out;
+        if self.finishing && wants_quit && self.all_finished().await? {
+            // signal to the main process that we should quit
+            self.should_quit.store(true, atomic::Ordering::SeqCst);
         }
+        Ok(self.should_quit.load(atomic::Ordering::SeqCst))
+    }
 
-        Ok(false)
+    // the stacks that were asked for, without their nested stacks
+    fn original_targets(&self) -> Vec<StackTarget> {
+        let mut targets: Vec<_> = self
+            .config
+            .stack_info
+            .names
+            .iter()
+            .filter(|target| self.config.stack_info.original_names.contains(&target.name))
+            .cloned()
+            .collect();
+        targets.sort();
+        targets
     }
 
-    #[tracing::instrument(skip(self, event))]
-    async fn print_event(&mut self, event: &StackEvent) -> Result<()> {
+    // whether every stack has finished deploying, going by its latest event,
+    // or by its current status if it has had none. Stacks that do not exist
+    // count as finished
+    async fn all_finished(&self) -> Result<bool> {
+        for target in self.original_targets() {
+            let latest = self
+                .stack_states
+                .values()
+                .filter(|state| {
+                    state.location == target.location && state.stack_name == target.name
+                })
+                .max_by_key(|state| state.timestamp);
+            let status = match latest {
+                Some(state) => state.status.clone(),
+                None => match self.live_state(&target).await? {
+                    Some((_, state)) => state.status,
+                    None => continue,
+                },
+            };
+            // statuses cftail does not know are taken to be in progress
+            let is_terminal = StackStatus::try_from(status.as_str()).is_ok_and(|s| s.is_terminal());
+            if !is_terminal {
+                tracing::debug!(%target, %status, "stack still deploying");
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // the id and current status of a stack, if it exists
+    async fn live_state(&self, target: &StackTarget) -> Result<Option<(String, StackState)>> {
+        let input = DescribeStacksInput::builder()
+            .stack_name(&target.name)
+            .build()
+            .wrap_err("building describe stacks input")?;
+        let res = match self
+            .clients
+            .get(&target.location)?
+            .describe_stacks(input)
+            .await
+        {
+            Ok(res) => res,
+            Err(e) if e.message().is_some_and(|m| m.contains("does not exist")) => return Ok(None),
+            Err(e) => return Err(e).wrap_err_with(|| format!("describing stack {}", target)),
+        };
+        let Some(stack) = res.stacks().first() else {
+            return Ok(None);
+        };
+        let state = StackState {
+            location: target.location.clone(),
+            stack_name: target.name.clone(),
+            status: stack
+                .stack_status()
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            timestamp: stack
+                .last_updated_time()
+                .or(stack.creation_time())
+                .and_then(|t| t.to_chrono_utc().ok())
+                .unwrap_or_else(Utc::now),
+        };
+        let stack_id = stack.stack_id().unwrap_or(&target.name).to_string();
+        Ok(Some((stack_id, state)))
+    }
+
+    #[tracing::instrument(skip(self, located))]
+    async fn print_event(&mut self, located: &LocatedEvent) -> Result<()> {
+        let event = &located.event;
         let resource_name = event
             .logical_resource_id
             .as_ref()
@@ -219,19 +748,176 @@ where
         let timestamp = event.timestamp().unwrap().to_chrono_utc().unwrap();
         let status_reason = event.resource_status_reason.as_ref();
         let resource_type = event.resource_type.clone().unwrap_or("???".to_string());
+        let stack_id = event.stack_id().unwrap_or(stack_name);
+
+        self.clear_progress()?;
+
+        // group the events of each operation on CI
+        if let Some(ci) = self.config.ci {
+            let starts_operation = self
+                .config
+                .stack_info
+                .original_names
+                .contains(resource_name)
+                && matches!(
+                    status.as_str(),
+                    "CREATE_IN_PROGRESS"
+                        | "UPDATE_IN_PROGRESS"
+                        | "IMPORT_IN_PROGRESS"
+                        | "DELETE_IN_PROGRESS"
+                );
+            if starts_operation && !self.ci_groups.contains_key(stack_id) {
+                ci.start_group(self.writer, stack_name)?;
+                self.ci_groups
+                    .insert(stack_id.to_string(), stack_name.to_string());
+            }
+        }
 
         // timestamp
         write!(self.writer, "{timestamp}: ", timestamp = timestamp)
             .wrap_err("printing timestamp")?;
 
-        // stack name and resource name, yellow if the resource name is the stack name, otherwise
-        // in white
-        if self
+        // account and region, if the stack was tailed with explicit ones
+        for label in [
+            self.clients.account_label(&located.location),
+            located.location.region_label(),
+        ]
+        .iter()
+        .flatten()
+        {
+            let mut spec = ColorSpec::new();
+            spec.set_fg(Some(Color::Cyan));
+            self.writer.set_color(&spec).wrap_err("setting color")?;
+            write!(self.writer, "{label}").wrap_err("printing location")?;
+            self.writer.reset().wrap_err("resetting color")?;
+            write!(self.writer, " | ").wrap_err("writing separator")?;
+        }
+
+        let is_stack_event = resource_name == stack_name;
+        let is_original_stack = self
             .config
             .stack_info
             .original_names
-            .contains(resource_name)
+            .contains(resource_name);
+        if is_stack_event
+            && matches!(
+                status.as_str(),
+                "CREATE_IN_PROGRESS"
+                    | "UPDATE_IN_PROGRESS"
+                    | "IMPORT_IN_PROGRESS"
+                    | "DELETE_IN_PROGRESS"
+            )
         {
+            // the template may change with each operation
+            self.deployment_starts
+                .insert(stack_id.to_string(), timestamp);
+            if is_original_stack {
+                self.report_events.remove(stack_id);
+            }
+        }
+        if is_original_stack {
+            self.stack_states.insert(
+                stack_id.to_string(),
+                StackState {
+                    location: located.location.clone(),
+                    stack_name: stack_name.to_string(),
+                    status: status.as_str().to_string(),
+                    timestamp,
+                },
+            );
+        }
+        if is_original_stack && status.as_str().ends_with("_IN_PROGRESS") {
+            let is_rollback = status.as_str().contains("ROLLBACK");
+            let is_cleanup = status.as_str().contains("CLEANUP");
+            if !self.progress.contains_key(stack_id) && !is_rollback && !is_cleanup {
+                self.progress
+                    .insert(stack_id.to_string(), Progress::new(timestamp, stack_id));
+                self.failures.remove(stack_id);
+            }
+        } else if !is_stack_event {
+            let nested_stack = event
+                .physical_resource_id()
+                .filter(|_| resource_type == "AWS::CloudFormation::Stack");
+            let tailed = nested_stack.is_some_and(|id| {
+                let name = id.split('/').nth(1).unwrap_or(id);
+                self.config.stack_info.names.iter().any(|t| t.name == name)
+            });
+            let progress = self.progress.values_mut().find(|p| p.includes(stack_id));
+            if let Some(progress) = progress {
+                // nested stacks are counted by their own resources, when they are tailed
+                match nested_stack {
+                    Some(id) if tailed && !id.is_empty() => progress.add_nested(id),
+                    Some(_) => {}
+                    None => progress.record(stack_id, resource_name, status.as_str()),
+                }
+            }
+        }
+        if !is_stack_event && status.as_str().ends_with("_FAILED") {
+            let operation = self.operation_stack_id(stack_id);
+            self.failures.entry(operation).or_default().push(Failure {
+                stack_name: stack_name.to_string(),
+                logical_id: resource_name.to_string(),
+                resource_type: resource_type.clone(),
+                status: status.as_str().to_string(),
+                reason: status_reason.cloned().unwrap_or_default(),
+                timestamp,
+            });
+        }
+        if self.collects_reports() {
+            // nested stacks are reported along with the stack they belong to
+            let root = self
+                .report_roots
+                .get(stack_id)
+                .cloned()
+                .unwrap_or_else(|| stack_id.to_string());
+            if let Some(nested) = event
+                .physical_resource_id()
+                .filter(|id| !is_stack_event && !id.is_empty())
+                .filter(|_| resource_type == "AWS::CloudFormation::Stack")
+            {
+                self.report_roots.insert(nested.to_string(), root.clone());
+            }
+            self.report_events
+                .entry(root)
+                .or_default()
+                .push(event.clone());
+        }
+        let expected = if is_stack_event {
+            None
+        } else {
+            self.track_duration(
+                stack_id,
+                stack_name,
+                resource_name,
+                &resource_type,
+                status.as_str(),
+                timestamp,
+            )
+        };
+        if let Some(trace) = self.trace.as_mut() {
+            trace.record(
+                stack_id,
+                stack_name,
+                resource_name,
+                &resource_type,
+                status.as_str(),
+                timestamp,
+            );
+        }
+        let construct_path = if self.config.show_construct_paths && !is_stack_event {
+            self.construct_path(&located.location, stack_id, resource_name)
+                .await
+        } else {
+            None
+        };
+        let name = match construct_path {
+            Some(path) => format!("{} ({})", resource_name, path),
+            None => resource_name.to_string(),
+        };
+
+        // stack name and resource name, yellow if the resource name is the stack name, otherwise
+        // in white
+        if is_original_stack {
             let mut spec = ColorSpec::new();
             spec.set_fg(Some(Color::Yellow));
             self.writer.set_color(&spec).wrap_err("setting color")?;
@@ -239,7 +925,7 @@ where
                 self.writer,
                 "{stack_name} - {name}",
                 stack_name = stack_name,
-                name = resource_name
+                name = name
             )
             .wrap_err("printing resource name")?;
             self.writer.reset().wrap_err("resetting colour")?;
@@ -248,7 +934,7 @@ where
                 self.writer,
                 "{stack_name} - {name}",
                 stack_name = stack_name,
-                name = resource_name
+                name = name
             )
             .wrap_err("printing resource name")?;
         }
@@ -267,63 +953,877 @@ where
 
         write!(self.writer, " | ").wrap_err("writing separator")?;
 
-        let stack_status = crate::stack_status::StackStatus::try_from(status.as_str())
-            .expect("unhandled stack status");
-        if let Some(spec) = stack_status.color_spec() {
+        // statuses cftail does not know are shown without colour, and never
+        // taken for the end of the operation
+        let stack_status = StackStatus::try_from(status.as_str()).ok();
+        if let Some(spec) = stack_status.as_ref().and_then(StackStatus::color_spec) {
             self.writer.set_color(&spec).wrap_err("setting color")?;
         }
 
         write!(self.writer, "{}", status).expect("printing status");
         self.writer.reset().wrap_err("resetting colour")?;
 
+        if let Some(expected) = expected {
+            write!(self.writer, " (usually {})", format_duration(expected))
+                .wrap_err("printing expected duration")?;
+        }
+
+        let finished =
+            stack_status.as_ref().is_some_and(StackStatus::is_terminal) && is_original_stack;
+        let failed = stack_status.as_ref().is_some_and(StackStatus::is_failed);
         if let Some(reason) = status_reason {
             writeln!(self.writer, " ({reason})", reason = reason)
                 .wrap_err("printing failure reason")?;
-        } else if stack_status.is_complete()
-            && self
-                .config
-                .stack_info
-                .original_names
-                .contains(resource_name)
-        {
-            // the stack has finished deploying
+        } else if finished && !failed {
             writeln!(self.writer, " 🎉✨🤘").wrap_err("printing finished line")?;
-            // if let TailMode::Tail = self.mode {
+        } else {
+            writeln!(self.writer).wrap_err("printing end of event")?;
+        }
+
+        if finished {
+            // the stack has finished deploying
+            let outcome = if failed {
+                Outcome::Failed
+            } else if stack_status
+                .as_ref()
+                .is_some_and(StackStatus::is_rolled_back)
+            {
+                Outcome::RolledBack
+            } else {
+                Outcome::Succeeded
+            };
+            if let (Some(ci), Some(title)) = (self.config.ci, self.ci_groups.remove(stack_id)) {
+                ci.end_group(self.writer, &title)?;
+                let verb = match outcome {
+                    Outcome::Failed => Some("failed"),
+                    Outcome::RolledBack => Some("rolled back"),
+                    _ => None,
+                };
+                if let Some(verb) = verb {
+                    ci.error(
+                        self.writer,
+                        &format!("{} {}", stack_name, verb),
+                        &format!("Stack {} finished with {}", stack_name, status.as_str()),
+                    )?;
+                }
+            }
+            self.outcomes.insert(stack_id.to_string(), outcome);
+            self.progress.remove(stack_id);
+            // operations from before cftail started are in the events, but
+            // the stack has moved on since so there is nothing to record
+            if let TailMode::Tail = self.mode {
+                if let Err(e) = self.record_operation(&located.location, event).await {
+                    tracing::warn!(err = %e, "recording operation in history");
+                }
+                if let Err(e) = self.report_operation(&located.location, event).await {
+                    tracing::warn!(err = %e, "reporting on operation");
+                }
+            }
             if self.config.show_outputs {
-                self.print_stack_outputs(event.stack_name().unwrap())
+                self.print_stack_outputs(&located.location, event.stack_name().unwrap())
                     .await?;
             }
-            // }
             if self.config.show_separators {
                 self.print_separator().wrap_err("printing separator")?;
             }
             if self.config.show_notifications {
                 if let TailMode::Tail = self.mode {
-                    notify(&self.config.sound).wrap_err("showing notification")?;
+                    let duration = self
+                        .deployment_starts
+                        .get(stack_id)
+                        .map(|started| timestamp - *started);
+                    let notice = Notice::finished(
+                        stack_name,
+                        status.as_str(),
+                        duration,
+                        // resources cancelled because of another failure are not the cause
+                        self.failures
+                            .get(stack_id)
+                            .and_then(|failures| failures.iter().find(|f| !f.is_cancelled())),
+                    );
+                    self.show_notification(&notice)?;
+                }
+            }
+
+            self.finishing = true;
+        }
+
+        if is_original_stack
+            && status.as_str().ends_with("ROLLBACK_IN_PROGRESS")
+            && self.config.show_notifications
+        {
+            if let TailMode::Tail = self.mode {
+                let notice =
+                    Notice::rollback_started(stack_name, status_reason.map(String::as_str));
+                self.show_notification(&notice)?;
+            }
+        }
+
+        // the result of the operation is announced once it finishes, so a
+        // failure part way through is only announced when it rolls back
+        if is_original_stack
+            && status.as_str().ends_with("ROLLBACK_IN_PROGRESS")
+            && self.config.webhook.as_ref().is_some_and(|w| w.on_rollback)
+        {
+            let kind = WebhookEvent::RollbackStarted;
+            if let Err(e) = self.announce_event(&located.location, event, kind).await {
+                tracing::warn!(err = %e, "announcing event");
+            }
+        }
+
+        if let TailMode::Tail = self.mode {
+            self.run_event_hook(event);
+        }
+
+        if status.as_str().ends_with("_FAILED") && !is_stack_event {
+            self.print_resource_definition(stack_name, resource_name)
+                .wrap_err("printing resource definition")?;
+            if let Some(ci) = self.config.ci {
+                ci.error(
+                    self.writer,
+                    &format!("{} ({}) {}", resource_name, resource_type, status.as_str()),
+                    status_reason.map(String::as_str).unwrap_or_default(),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn show_notification(&self, notice: &Notice) -> Result<()> {
+        let sound = if notice.failure {
+            &self.config.failure_sound
+        } else {
+            &self.config.sound
+        };
+        notify(notice, sound).wrap_err("showing notification")
+    }
+
+    // keep track of when each resource operation starts, recording how long it
+    // took once it completes. Returns how long the operation usually takes
+    // when it has just started.
+    fn track_duration(
+        &mut self,
+        stack_id: &str,
+        stack_name: &str,
+        logical_id: &str,
+        resource_type: &str,
+        status: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Option<chrono::Duration> {
+        let key = (stack_id.to_string(), logical_id.to_string());
+        let action = durations::action(status);
+        if status.ends_with("_IN_PROGRESS") {
+            if self.resource_starts.contains_key(&key) {
+                return None;
+            }
+            self.resource_starts.insert(
+                key,
+                ResourceStart {
+                    stack_name: stack_name.to_string(),
+                    resource_type: resource_type.to_string(),
+                    action: action.to_string(),
+                    started: timestamp,
+                    warned_slow: false,
+                },
+            );
+            let durations = self.durations.as_ref()?;
+            return durations.expected(stack_name, logical_id, resource_type, action);
+        }
+
+        let start = self.resource_starts.remove(&key)?;
+        if status.ends_with("_COMPLETE") && action == start.action {
+            if let Some(durations) = self.durations.as_mut() {
+                durations.record(
+                    stack_name,
+                    logical_id,
+                    resource_type,
+                    action,
+                    start.started,
+                    timestamp,
+                );
+            }
+        }
+        None
+    }
+
+    // id of the stack whose operation the events of a stack are part of,
+    // which for a nested stack is the stack under deployment
+    fn operation_stack_id(&self, stack_id: &str) -> String {
+        self.progress
+            .iter()
+            .find(|(_, progress)| progress.includes(stack_id))
+            .map(|(id, _)| id.clone())
+            .unwrap_or_else(|| stack_id.to_string())
+    }
+
+    // the resource in progress expected to finish last
+    fn slowest_resource(&self, now: DateTime<Utc>) -> Option<SlowestResource> {
+        let durations = self.durations.as_ref()?;
+        self.resource_starts
+            .iter()
+            .filter_map(|((_, logical_id), start)| {
+                let expected = durations.expected(
+                    &start.stack_name,
+                    logical_id,
+                    &start.resource_type,
+                    &start.action,
+                )?;
+                Some(SlowestResource {
+                    logical_id: logical_id.clone(),
+                    resource_type: start.resource_type.clone(),
+                    expected,
+                    elapsed: now - start.started,
+                })
+            })
+            .max_by_key(|slowest| slowest.expected - slowest.elapsed)
+    }
+
+    // the deadline passed before the stacks finished deploying. Cancels the
+    // updates in progress if asked to, returning whether there is a rollback
+    // to wait for
+    async fn time_out(&mut self) -> Result<bool> {
+        self.clear_progress()?;
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(Color::Red)).set_bold(true);
+        self.writer.set_color(&spec).wrap_err("setting color")?;
+        write!(
+            self.writer,
+            "Timed out waiting for the deployment to finish"
+        )
+        .wrap_err("printing timeout")?;
+        self.writer.reset().wrap_err("resetting color")?;
+        writeln!(self.writer).wrap_err("printing timeout")?;
+        if !self.config.cancel_on_timeout {
+            return Ok(false);
+        }
+        self.cancel_updates().await
+    }
+
+    // the tailed stacks whose current status matches, ordered by name. The
+    // status is fetched afresh as cftail may have started part way through a
+    // deployment, or missed its latest events
+    async fn stacks_in(&self, status: impl Fn(&str) -> bool) -> Vec<(String, StackState)> {
+        let mut states = Vec::new();
+        for target in self.original_targets() {
+            match self.live_state(&target).await {
+                Ok(Some((stack_id, state))) if status(&state.status) => {
+                    states.push((stack_id, state));
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(err = %e, %target, "fetching stack status"),
+            }
+        }
+        states.sort_by(|a, b| a.1.stack_name.cmp(&b.1.stack_name));
+        states
+    }
+
+    // cancel the updates in progress, returning whether any were cancelled
+    async fn cancel_updates(&mut self) -> Result<bool> {
+        let states = self
+            .stacks_in(|status| status.ends_with("_IN_PROGRESS"))
+            .await;
+        let mut cancelled = false;
+        for (stack_id, state) in states {
+            // only updates can be cancelled, and they roll back afterwards
+            if state.status != "UPDATE_IN_PROGRESS" {
+                writeln!(
+                    self.writer,
+                    "Cannot cancel {} while it is in {}",
+                    state.stack_name, state.status
+                )
+                .wrap_err("printing cancellation")?;
+                continue;
+            }
+            let client = self.clients.get(&state.location)?;
+            let input = CancelUpdateStackInput::builder()
+                .stack_name(&stack_id)
+                .build()
+                .wrap_err("building cancel update stack input")?;
+            match client.cancel_update_stack(input).await {
+                Ok(_) => {
+                    writeln!(self.writer, "Cancelled the update of {}", state.stack_name)
+                        .wrap_err("printing cancellation")?;
+                    cancelled = true;
+                }
+                Err(e) => {
+                    tracing::warn!(err = %e, stack = %state.stack_name, "cancelling update");
+                    writeln!(
+                        self.writer,
+                        "Could not cancel the update of {}: {}",
+                        state.stack_name, e
+                    )
+                    .wrap_err("printing cancellation")?;
+                }
+            }
+        }
+        Ok(cancelled)
+    }
+
+    // retry the rollbacks that failed, e.g. once whatever blocked them has
+    // been fixed by hand
+    async fn continue_rollbacks(&mut self) -> Result<()> {
+        let states = self
+            .stacks_in(|status| status == "UPDATE_ROLLBACK_FAILED")
+            .await;
+        for (stack_id, state) in states {
+            let client = self.clients.get(&state.location)?;
+            let input = ContinueUpdateRollbackInput::builder()
+                .stack_name(&stack_id)
+                .build()
+                .wrap_err("building continue update rollback input")?;
+            match client.continue_update_rollback(input).await {
+                Ok(_) => {
+                    writeln!(
+                        self.writer,
+                        "Continuing the rollback of {}",
+                        state.stack_name
+                    )
+                    .wrap_err("printing rollback")?;
+                }
+                Err(e) => {
+                    tracing::warn!(err = %e, stack = %state.stack_name, "continuing rollback");
+                    writeln!(
+                        self.writer,
+                        "Could not continue the rollback of {}: {}",
+                        state.stack_name, e
+                    )
+                    .wrap_err("printing rollback")?;
                 }
             }
+        }
+        Ok(())
+    }
+
+    // warn when the deployment has had no new events for a while, or a
+    // resource is taking much longer than it usually does
+    fn warn_if_stuck(&mut self, now: DateTime<Utc>) -> Result<()> {
+        if self.progress.is_empty() || self.resource_starts.is_empty() {
+            return Ok(());
+        }
+        let mut warnings = Vec::new();
+
+        let last_event = self.config.since;
+        let quiet = now - last_event;
+        let is_quiet = self
+            .config
+            .stuck_after
+            .is_some_and(|threshold| quiet >= threshold);
+        if is_quiet && self.stuck_warned_at != Some(last_event) {
+            self.stuck_warned_at = Some(last_event);
+            let mut starts: Vec<_> = self.resource_starts.iter().collect();
+            starts.sort_by_key(|(_, start)| start.started);
+            let resources: Vec<String> = starts
+                .iter()
+                .map(|((_, logical_id), start)| {
+                    format!(
+                        "{} ({}) for {}",
+                        logical_id,
+                        start.resource_type,
+                        format_duration(now - start.started)
+                    )
+                })
+                .collect();
+            warnings.push(format!(
+                "No new events for {}, still in progress: {}",
+                format_duration(quiet),
+                resources.join(", ")
+            ));
+        }
+
+        if let Some(durations) = self.durations.as_ref() {
+            for ((_, logical_id), start) in self.resource_starts.iter_mut() {
+                if start.warned_slow {
+                    continue;
+                }
+                let Some(expected) = durations.expected(
+                    &start.stack_name,
+                    logical_id,
+                    &start.resource_type,
+                    &start.action,
+                ) else {
+                    continue;
+                };
+                let elapsed = now - start.started;
+                if durations::is_unusually_slow(elapsed, expected) {
+                    start.warned_slow = true;
+                    warnings.push(format!(
+                        "{} ({}) has been in progress for {}, it usually takes {}",
+                        logical_id,
+                        start.resource_type,
+                        format_duration(elapsed),
+                        format_duration(expected)
+                    ));
+                }
+            }
+        }
+
+        if warnings.is_empty() {
+            return Ok(());
+        }
+        self.clear_progress()?;
+        for warning in &warnings {
+            tracing::warn!(%warning, "deployment may be stuck");
+            let mut spec = ColorSpec::new();
+            spec.set_fg(Some(Color::Yellow)).set_bold(true);
+            self.writer.set_color(&spec).wrap_err("setting color")?;
+            write!(self.writer, "⚠️  {}", warning).wrap_err("printing stuck warning")?;
+            self.writer.reset().wrap_err("resetting color")?;
+            writeln!(self.writer).wrap_err("printing stuck warning")?;
+        }
+        if self.config.show_notifications {
+            if let TailMode::Tail = self.mode {
+                let notice = Notice {
+                    summary: "Deployment may be stuck".to_string(),
+                    body: warnings.join("\n"),
+                    failure: true,
+                };
+                self.show_notification(&notice)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record_events(&mut self, events: &[LocatedEvent]) {
+        if let Some(history) = self.history.as_mut() {
+            if let Err(e) = history.record_events(events.iter().map(|e| &e.event)) {
+                tracing::warn!(err = %e, "recording events in history");
+            }
+        }
+    }
+
+    // store the operation that has just finished, along with the state of the
+    // stack at the end of it
+    #[tracing::instrument(skip(self, event))]
+    async fn record_operation(&self, location: &Location, event: &StackEvent) -> Result<()> {
+        if self.history.is_none() {
+            return Ok(());
+        }
+        let stack_id = event
+            .stack_id()
+            .ok_or_else(|| eyre::eyre!("event has no stack id"))?;
+        let finished = event
+            .timestamp()
+            .ok_or_else(|| eyre::eyre!("event has no timestamp"))?
+            .to_chrono_utc()
+            .wrap_err("converting event timestamp")?;
+        // an operation that was already under way when cftail started would
+        // be stored with only part of its events and duration
+        let Some(started) = self.deployment_starts.get(stack_id).cloned() else {
+            tracing::debug!(%stack_id, "not recording operation tailed part way through");
+            return Ok(());
+        };
+        let client = self.clients.get(location)?;
+
+        let input = DescribeStacksInput::builder()
+            .stack_name(stack_id)
+            .build()
+            .wrap_err("building describe stacks input")?;
+        let res = client
+            .describe_stacks(input)
+            .await
+            .wrap_err("describing stack")?;
+        let stack = res
+            .stacks()
+            .first()
+            .ok_or_else(|| eyre::eyre!("stack {} not found", stack_id))?;
+
+        let input = GetTemplateInput::builder()
+            .stack_name(stack_id)
+            .build()
+            .wrap_err("building get template input")?;
+        let template_hash = match client.get_template(input).await {
+            Ok(res) => res
+                .template_body()
+                .map(|body| format!("{:x}", Sha256::digest(body.as_bytes()))),
+            Err(e) => {
+                tracing::warn!(err = %e, "fetching template to hash");
+                None
+            }
+        };
+
+        let operation = Operation {
+            stack_id: stack_id.to_string(),
+            stack_name: stack.stack_name().unwrap_or_default().to_string(),
+            started,
+            finished,
+            status: event
+                .resource_status()
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            client_request_token: event.client_request_token().map(String::from),
+            parameters: stack
+                .parameters()
+                .iter()
+                .filter_map(|p| {
+                    let value = p.resolved_value().or(p.parameter_value());
+                    Some((p.parameter_key()?.to_string(), value?.to_string()))
+                })
+                .collect(),
+            outputs: stack
+                .outputs()
+                .iter()
+                .filter_map(|o| Some((o.output_key()?.to_string(), o.output_value()?.to_string())))
+                .collect(),
+            tags: stack
+                .tags()
+                .iter()
+                .filter_map(|t| Some((t.key()?.to_string(), t.value()?.to_string())))
+                .collect(),
+            template_hash,
+        };
+        match &self.history {
+            Some(history) => history.record_operation(&operation),
+            None => Ok(()),
+        }
+    }
+
+    // whether the events of each operation are needed for a report, including
+    // the job summary on CI
+    fn collects_reports(&self) -> bool {
+        !self.config.reports.is_empty()
+            || self.config.ci == Some(CiPlatform::Github)
+            || self.config.webhook.is_some()
+            || self.config.hooks.on_result()
+    }
+
+    // report on the operation that has just finished, in report files, the
+    // CI job summary and webhooks
+    #[tracing::instrument(skip(self, event))]
+    async fn report_operation(&mut self, location: &Location, event: &StackEvent) -> Result<()> {
+        if !self.collects_reports() {
+            return Ok(());
+        }
+        let report = self.build_report(location, event).await?;
+        for (format, path) in &self.config.reports {
+            report.write(
+                *format,
+                &self.report_path(path, location, &report.stack_name),
+            )?;
+        }
+        if let Some(ci) = self.config.ci {
+            ci.write_summary(&report.render(ReportFormat::Markdown))?;
+        }
+        let kind = if report.status.contains("ROLLBACK") || report.status.ends_with("_FAILED") {
+            WebhookEvent::Failure
+        } else {
+            WebhookEvent::Complete
+        };
+        self.announce(Payload::new(kind, &report)).await
+    }
+
+    // file to write the report on a stack to, with the stack name added to
+    // the name of the file when more than one stack is tailed, and the region
+    // too when the stack is tailed in more than one
+    fn report_path(&self, path: &Path, location: &Location, stack_name: &str) -> PathBuf {
+        let targets = self.original_targets();
+        if targets.len() <= 1 {
+            return path.to_path_buf();
+        }
+        let mut suffix = stack_name.to_string();
+        if targets.iter().filter(|t| t.name == stack_name).count() > 1 {
+            if let Some(region) = &location.region {
+                suffix = format!("{}-{}", suffix, region);
+            }
+        }
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_name = match path.extension() {
+            Some(extension) => format!("{}-{}.{}", stem, suffix, extension.to_string_lossy()),
+            None => format!("{}-{}", stem, suffix),
+        };
+        path.with_file_name(file_name)
+    }
+
+    // announce how an operation is going part way through it
+    #[tracing::instrument(skip(self, event))]
+    async fn announce_event(
+        &mut self,
+        location: &Location,
+        event: &StackEvent,
+        kind: WebhookEvent,
+    ) -> Result<()> {
+        if self.config.webhook.is_none() && !self.config.hooks.on_result() {
+            return Ok(());
+        }
+        let report = self.build_report(location, event).await?;
+        self.announce(Payload::new(kind, &report)).await
+    }
+
+    // send the result to webhooks and hooks, only for events that are
+    // happening now rather than ones from the past. Both run in the
+    // background so that tailing carries on while they do
+    async fn announce(&mut self, payload: Payload) -> Result<()> {
+        if !matches!(self.mode, TailMode::Tail) {
+            return Ok(());
+        }
+        if let Some(webhook) = self.config.webhook.clone() {
+            let payload = payload.clone();
+            self.background.spawn(async move {
+                if let Err(e) = webhook.send(&payload).await {
+                    tracing::warn!(err = %e, "sending webhook");
+                }
+            });
+        }
+        if self.config.hooks.on_result() {
+            let hooks = self.config.hooks.clone();
+            self.background.spawn(async move {
+                if let Err(e) = hooks.result(&payload).await {
+                    tracing::warn!(err = %e, "running result hook");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    // run the `--on-event` hook in the background, once the hooks of the
+    // earlier events have finished so that it sees the events in order
+    fn run_event_hook(&mut self, event: &StackEvent) {
+        if self.config.hooks.on_event.is_none() {
+            return;
+        }
+        let hooks = self.config.hooks.clone();
+        let event = event.clone();
+        let previous = self.event_hook.take();
+        self.event_hook = Some(tokio::spawn(async move {
+            if let Some(previous) = previous {
+                let _ = previous.await;
+            }
+            if let Err(e) = hooks.event(&event).await {
+                tracing::warn!(err = %e, "running event hook");
+            }
+        }));
+    }
+
+    /// Wait for the webhooks and hooks still running in the background, so
+    /// that none are lost when cftail exits
+    pub(crate) async fn finish(&mut self) {
+        if let Some(event_hook) = self.event_hook.take() {
+            if let Err(e) = event_hook.await {
+                tracing::warn!(err = %e, "event hook failed");
+            }
+        }
+        while let Some(res) = self.background.join_next().await {
+            if let Err(e) = res {
+                tracing::warn!(err = %e, "background task failed");
+            }
+        }
+    }
+
+    // everything that has happened in the current operation up to `event`
+    async fn build_report(&self, location: &Location, event: &StackEvent) -> Result<Report> {
+        let stack_id = event
+            .stack_id()
+            .ok_or_else(|| eyre::eyre!("event has no stack id"))?;
+        let stack_name = event
+            .stack_name()
+            .ok_or_else(|| eyre::eyre!("event has no stack name"))?;
+        let finished = event
+            .timestamp()
+            .ok_or_else(|| eyre::eyre!("event has no timestamp"))?
+            .to_chrono_utc()
+            .wrap_err("converting event timestamp")?;
+
+        let input = DescribeStacksInput::builder()
+            .stack_name(stack_id)
+            .build()
+            .wrap_err("building describe stacks input")?;
+        let res = self
+            .clients
+            .get(location)?
+            .describe_stacks(input)
+            .await
+            .wrap_err("describing stack")?;
+        let outputs = res
+            .stacks()
+            .first()
+            .map(|stack| stack.outputs().to_vec())
+            .unwrap_or_default();
+
+        Ok(Report {
+            stack_name: stack_name.to_string(),
+            status: event
+                .resource_status()
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            started: self
+                .deployment_starts
+                .get(stack_id)
+                .cloned()
+                .unwrap_or(finished),
+            finished,
+            events: self
+                .report_events
+                .get(stack_id)
+                .cloned()
+                .unwrap_or_default(),
+            outputs,
+        })
+    }
+
+    fn save_trace(&mut self) {
+        if let Some(trace) = self.trace.as_mut() {
+            if let Err(e) = trace.save() {
+                tracing::warn!(err = %e, "saving trace");
+            }
+        }
+    }
+
+    // draw the progress of the current operation on the last line, to be
+    // cleared before anything else is printed
+    async fn draw_progress(&mut self) -> Result<()> {
+        if !self.config.show_progress {
+            return Ok(());
+        }
+        let uncounted: Vec<String> = self
+            .progress
+            .iter()
+            .filter(|(_, progress)| progress.total.is_none() && !progress.count_failed)
+            .map(|(stack_id, _)| stack_id.clone())
+            .collect();
+        for stack_id in uncounted {
+            let count = self.resource_count(&self.progress[&stack_id]).await;
+            let progress = self.progress.get_mut(&stack_id).expect("progress of stack");
+            match count {
+                Ok(total) => progress.total = Some(total),
+                Err(e) => {
+                    // there is nothing to measure the progress against
+                    tracing::warn!(err = %e, %stack_id, "counting template resources");
+                    progress.count_failed = true;
+                }
+            }
+        }
+
+        // the operations on every stack make up a single bar
+        let combined = Progress::combine(self.progress.values().filter(|p| !p.count_failed));
+        let Some(progress) = combined else {
+            return self.clear_progress();
+        };
+        let now = Utc::now();
+        let slowest = self.slowest_resource(now);
+        let line = progress.render(now, slowest.as_ref());
+        self.clear_progress()?;
+        write!(self.writer, "{}", line).wrap_err("printing progress")?;
+        self.writer.flush().wrap_err("flushing progress")?;
+        self.progress_drawn = true;
+        Ok(())
+    }
+
+    fn clear_progress(&mut self) -> Result<()> {
+        if self.progress_drawn {
+            write!(self.writer, "\r\x1b[2K").wrap_err("clearing progress")?;
+            self.progress_drawn = false;
+        }
+        Ok(())
+    }
 
-            // signal to the main process that we should quit
-            self.should_quit.store(
-                self.config.should_exit_on_completion,
-                atomic::Ordering::SeqCst,
-            );
-        } else {
-            writeln!(self.writer).wrap_err("printing end of event")?;
+    // number of resources in the processed templates of the stack under
+    // deployment and the nested stacks of it that are tailed
+    #[tracing::instrument(skip(self, progress))]
+    async fn resource_count(&self, progress: &Progress) -> Result<usize> {
+        let stack_ids = progress.stack_ids();
+        let location = match self.stack_states.get(&stack_ids[0]) {
+            Some(state) => &state.location,
+            None => eyre::bail!("no location found for stack {}", stack_ids[0]),
+        };
+        let client = self.clients.get(location)?;
+        let mut total = 0;
+        for stack_id in &stack_ids {
+            let input = GetTemplateInput::builder()
+                .stack_name(stack_id)
+                .template_stage(TemplateStage::Processed)
+                .build()
+                .wrap_err("building get template input")?;
+            let res = client
+                .get_template(input)
+                .await
+                .wrap_err_with(|| format!("fetching template of {}", stack_id))?;
+            let template = Template::parse(res.template_body.unwrap_or_default())?;
+            total += template.resource_count();
         }
+        Ok(total)
+    }
+
+    // show where a failed resource is defined in the local template of its stack
+    fn print_resource_definition(&mut self, stack_name: &str, logical_id: &str) -> Result<()> {
+        let Some(template) = self.config.templates.get(stack_name) else {
+            return Ok(());
+        };
+        let Some(snippet) = template.resource_snippet(logical_id) else {
+            return Ok(());
+        };
 
+        let path = match &template.path {
+            Some(path) => path.display().to_string(),
+            None => "template".to_string(),
+        };
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(Color::Cyan));
+        self.writer.set_color(&spec)?;
+        writeln!(self.writer, "    --> {}:{}", path, snippet.line)?;
+        self.writer.reset()?;
+        for (number, line) in &snippet.lines {
+            writeln!(self.writer, "{:>7} | {}", number, line)?;
+        }
         Ok(())
     }
 
+    // construct path of a resource, fetching the template of the stack if it
+    // has not been seen for the current deployment
+    async fn construct_path(
+        &mut self,
+        location: &Location,
+        stack_id: &str,
+        logical_id: &str,
+    ) -> Option<String> {
+        let key = (
+            stack_id.to_string(),
+            self.deployment_starts.get(stack_id).cloned(),
+        );
+        if !self.construct_paths.contains_key(&key) {
+            let paths = self
+                .fetch_construct_paths(location, stack_id)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::warn!(err = %e, %stack_id, "fetching construct paths");
+                    HashMap::new()
+                });
+            self.construct_paths.insert(key.clone(), paths);
+        }
+        self.construct_paths[&key].get(logical_id).cloned()
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn fetch_construct_paths(
+        &self,
+        location: &Location,
+        stack_id: &str,
+    ) -> Result<ConstructPaths> {
+        let input = GetTemplateInput::builder()
+            .stack_name(stack_id)
+            .build()
+            .wrap_err("building get template input")?;
+        let res = self
+            .clients
+            .get(location)?
+            .get_template(input)
+            .await
+            .wrap_err("fetching template")?;
+        let template = Template::parse(res.template_body.unwrap_or_default())?;
+        Ok(template.construct_paths())
+    }
+
     // get the list of stack outputs that have been deployed and print to the output
     #[tracing::instrument(skip(self))]
-    async fn print_stack_outputs(&mut self, stack_name: &str) -> Result<()> {
+    async fn print_stack_outputs(&mut self, location: &Location, stack_name: &str) -> Result<()> {
         tracing::info!(%stack_name, "printing stack outputs");
         let input = DescribeStacksInput::builder()
             .stack_name(stack_name)
             .build()
             .wrap_err("building describe stacks input")?;
-        let res = self.fetcher.describe_stacks(input).await?;
+        let res = self.clients.get(location)?.describe_stacks(input).await?;
         let stacks = res.stacks();
         if stacks.len() != 1 {
             unreachable!(
@@ -346,6 +1846,15 @@ where
                 ]));
             }
             writeln!(self.writer, "{}", table.render()).unwrap();
+
+            if let Some(ci) = self.config.ci {
+                let outputs: Vec<(&str, &str)> = outputs
+                    .iter()
+                    .filter_map(|o| Some((o.output_key()?, o.output_value()?)))
+                    .collect();
+                ci.export_outputs(&outputs)
+                    .wrap_err("exporting outputs to CI")?;
+            }
         } else {
             tracing::debug!("no outputs found");
         }
@@ -365,18 +1874,19 @@ where
     #[tracing::instrument(skip(self, stacks))]
     async fn fetch_events(
         &mut self,
-        stacks: impl Iterator<Item = &String>,
+        stacks: impl Iterator<Item = &StackTarget>,
         since: DateTime<Utc>,
-    ) -> Result<Vec<StackEvent>> {
+    ) -> Result<Vec<LocatedEvent>> {
         let (tx, mut rx) = mpsc::channel(self.config.stack_info.names.len());
         let handles: Vec<_> = stacks
-            .map(|stack_name| {
-                tracing::debug!(name = ?stack_name, "fetching events for stack");
+            .map(|target| {
+                tracing::debug!(target = %target, "fetching events for stack");
                 let tx = tx.clone();
-                let fetcher = Arc::clone(&self.fetcher);
-                let stack_name = stack_name.clone();
+                let fetcher = Arc::clone(self.clients.get(&target.location)?);
+                let location = target.location.clone();
+                let stack_name = target.name.clone();
                 tracing::debug!("spawning task");
-                tokio::spawn(async move {
+                Ok(tokio::spawn(async move {
                     tracing::debug!("spawned task");
                     let mut next_token: Option<String> = None;
                     let mut all_events = Vec::new();
@@ -417,7 +1927,10 @@ where
 
                                     seen_event_ids.insert(event.event_id.clone());
 
-                                    all_events.push(event);
+                                    all_events.push(LocatedEvent {
+                                        location: location.clone(),
+                                        event,
+                                    });
                                 }
 
                                 match response.next_token {
@@ -427,12 +1940,9 @@ where
                             }
                             Err(e) => {
                                 tracing::warn!(error = ?e, "got failed response");
-                                match e {
-                                    SdkError::ServiceError(ref s) => {
-                                        tracing::error!(error = ?s, "service error");
-                                        return Err(Error::Client(e));
-                                    }
-                                    _ => {}
+                                if let SdkError::ServiceError(ref s) = e {
+                                    tracing::error!(error = ?s, "service error");
+                                    return Err(Error::Client(e));
                                 }
                             }
                         };
@@ -441,9 +1951,9 @@ where
                     let _ = tx.send(all_events).await;
 
                     Ok::<(), _>(())
-                })
+                }))
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
         for res in join_all(handles).await {
             let res = res?;
@@ -470,6 +1980,7 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
     use std::sync::Arc;
 
     use async_trait::async_trait;
@@ -477,6 +1988,19 @@ mod tests {
         config::http::HttpResponse,
         error::SdkError,
         operation::{
+            cancel_update_stack::{
+                CancelUpdateStackError, CancelUpdateStackInput, CancelUpdateStackOutput,
+            },
+            continue_update_rollback::{
+                ContinueUpdateRollbackError, ContinueUpdateRollbackInput,
+                ContinueUpdateRollbackOutput,
+            },
+            create_change_set::{
+                CreateChangeSetError, CreateChangeSetInput, CreateChangeSetOutput,
+            },
+            describe_change_set::{
+                DescribeChangeSetError, DescribeChangeSetInput, DescribeChangeSetOutput,
+            },
             describe_stack_events::{
                 DescribeStackEventsError, DescribeStackEventsInput, DescribeStackEventsOutput,
             },
@@ -484,17 +2008,41 @@ mod tests {
                 DescribeStackResourcesError, DescribeStackResourcesInput,
                 DescribeStackResourcesOutput,
             },
+            describe_stack_set_operation::{
+                DescribeStackSetOperationError, DescribeStackSetOperationInput,
+                DescribeStackSetOperationOutput,
+            },
             describe_stacks::{DescribeStacksError, DescribeStacksInput, DescribeStacksOutput},
+            execute_change_set::{
+                ExecuteChangeSetError, ExecuteChangeSetInput, ExecuteChangeSetOutput,
+            },
+            get_template::{GetTemplateError, GetTemplateInput, GetTemplateOutput},
+            list_stack_instances::{
+                ListStackInstancesError, ListStackInstancesInput, ListStackInstancesOutput,
+            },
+            list_stack_set_operation_results::{
+                ListStackSetOperationResultsError, ListStackSetOperationResultsInput,
+                ListStackSetOperationResultsOutput,
+            },
+            list_stack_set_operations::{
+                ListStackSetOperationsError, ListStackSetOperationsInput,
+                ListStackSetOperationsOutput,
+            },
         },
-        types::{ResourceStatus, StackEvent},
+        types::{ResourceStatus, Stack, StackEvent, StackStatus},
     };
     use aws_smithy_types::DateTime;
     use chrono::{TimeZone, Utc};
+    use std::sync::Mutex;
     use termcolor::{ColorSpec, WriteColor};
 
     use crate::{
-        stacks::StackInfo,
-        tail::{Tail, TailConfig},
+        aws::Clients,
+        hooks::Hooks,
+        interrupt::{Choice, Interrupts, Options, Signal},
+        report::Failure,
+        stacks::{Location, StackInfo, StackTarget},
+        tail::{Notice, Outcome, Tail, TailConfig},
     };
 
     #[derive(Debug, Default)]
@@ -527,16 +2075,119 @@ mod tests {
         }
     }
 
-    struct MockClient;
+    #[derive(Debug, Default)]
+    struct MockClient {
+        // events of each stack, newest first, in place of the default event
+        events: Mutex<HashMap<String, Vec<StackEvent>>>,
+        // current status of each stack
+        statuses: Mutex<HashMap<String, StackStatus>>,
+        // stacks whose update has been cancelled
+        cancelled: Mutex<Vec<String>>,
+        // processed template of each stack
+        templates: Mutex<HashMap<String, String>>,
+    }
+
+    fn stack_id(stack_name: &str) -> String {
+        format!(
+            "arn:aws:cloudformation:us-east-1:123456789012:stack/{}/1",
+            stack_name
+        )
+    }
+
+    // name of a stack given either by name or by id
+    fn stack_name(name_or_id: &str) -> &str {
+        name_or_id.split('/').nth(1).unwrap_or(name_or_id)
+    }
+
+    impl MockClient {
+        // add an event `seconds` after the epoch, which also sets the status
+        // of the stack for events of the stack itself
+        fn push(&self, stack_name: &str, logical_id: &str, status: &str, seconds: i64) {
+            let resource_type = if logical_id == stack_name {
+                "AWS::CloudFormation::Stack"
+            } else {
+                "AWS::SNS::Topic"
+            };
+            let event = StackEvent::builder().resource_type(resource_type);
+            self.push_event(stack_name, logical_id, status, seconds, event);
+        }
+
+        // add an event for the nested stack `nested` of a stack
+        fn push_nested(
+            &self,
+            stack_name: &str,
+            logical_id: &str,
+            nested: &str,
+            status: &str,
+            seconds: i64,
+        ) {
+            let event = StackEvent::builder()
+                .resource_type("AWS::CloudFormation::Stack")
+                .physical_resource_id(stack_id(nested));
+            self.push_event(stack_name, logical_id, status, seconds, event);
+        }
+
+        fn push_event(
+            &self,
+            stack_name: &str,
+            logical_id: &str,
+            status: &str,
+            seconds: i64,
+            event: aws_sdk_cloudformation::types::builders::StackEventBuilder,
+        ) {
+            let event = event
+                .event_id(uuid::Uuid::new_v4().to_string())
+                .stack_id(stack_id(stack_name))
+                .stack_name(stack_name)
+                .logical_resource_id(logical_id)
+                .resource_status(ResourceStatus::from(status))
+                .timestamp(DateTime::from_secs(seconds))
+                .build();
+            self.events
+                .lock()
+                .unwrap()
+                .entry(stack_name.to_string())
+                .or_default()
+                .insert(0, event);
+            if logical_id == stack_name {
+                self.set_status(stack_name, status);
+            }
+        }
+
+        // set the status of the stack, which has no events until some are
+        // pushed
+        fn set_status(&self, stack_name: &str, status: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .entry(stack_name.to_string())
+                .or_default();
+            self.statuses
+                .lock()
+                .unwrap()
+                .insert(stack_name.to_string(), StackStatus::from(status));
+        }
+    }
 
     #[async_trait]
     impl crate::aws::AwsCloudFormationClient for MockClient {
         async fn describe_stacks(
             &self,
-            _input: DescribeStacksInput,
+            input: DescribeStacksInput,
         ) -> std::result::Result<DescribeStacksOutput, SdkError<DescribeStacksError, HttpResponse>>
         {
-            todo!()
+            let stack_name = stack_name(input.stack_name().unwrap());
+            let stacks = self.statuses.lock().unwrap().get(stack_name).map(|status| {
+                Stack::builder()
+                    .stack_id(stack_id(stack_name))
+                    .stack_name(stack_name)
+                    .stack_status(status.clone())
+                    .creation_time(DateTime::from_secs(0))
+                    .build()
+            });
+            Ok(DescribeStacksOutput::builder()
+                .set_stacks(stacks.map(|stack| vec![stack]))
+                .build())
         }
 
         async fn describe_stack_events(
@@ -546,6 +2197,12 @@ mod tests {
             DescribeStackEventsOutput,
             SdkError<DescribeStackEventsError, HttpResponse>,
         > {
+            if let Some(events) = self.events.lock().unwrap().get(input.stack_name().unwrap()) {
+                return Ok(DescribeStackEventsOutput::builder()
+                    .set_stack_events(Some(events.clone()))
+                    .build());
+            }
+
             let stack_event = StackEvent::builder()
                 .event_id(uuid::Uuid::new_v4().to_string())
                 .timestamp(
@@ -577,6 +2234,108 @@ mod tests {
         > {
             todo!()
         }
+
+        async fn describe_stack_set_operation(
+            &self,
+            _input: DescribeStackSetOperationInput,
+        ) -> std::result::Result<
+            DescribeStackSetOperationOutput,
+            SdkError<DescribeStackSetOperationError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn list_stack_set_operations(
+            &self,
+            _input: ListStackSetOperationsInput,
+        ) -> std::result::Result<
+            ListStackSetOperationsOutput,
+            SdkError<ListStackSetOperationsError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn list_stack_set_operation_results(
+            &self,
+            _input: ListStackSetOperationResultsInput,
+        ) -> std::result::Result<
+            ListStackSetOperationResultsOutput,
+            SdkError<ListStackSetOperationResultsError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn list_stack_instances(
+            &self,
+            _input: ListStackInstancesInput,
+        ) -> std::result::Result<
+            ListStackInstancesOutput,
+            SdkError<ListStackInstancesError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn describe_change_set(
+            &self,
+            _input: DescribeChangeSetInput,
+        ) -> std::result::Result<
+            DescribeChangeSetOutput,
+            SdkError<DescribeChangeSetError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn create_change_set(
+            &self,
+            _input: CreateChangeSetInput,
+        ) -> std::result::Result<CreateChangeSetOutput, SdkError<CreateChangeSetError, HttpResponse>>
+        {
+            todo!()
+        }
+
+        async fn execute_change_set(
+            &self,
+            _input: ExecuteChangeSetInput,
+        ) -> std::result::Result<
+            ExecuteChangeSetOutput,
+            SdkError<ExecuteChangeSetError, HttpResponse>,
+        > {
+            todo!()
+        }
+
+        async fn get_template(
+            &self,
+            input: GetTemplateInput,
+        ) -> std::result::Result<GetTemplateOutput, SdkError<GetTemplateError, HttpResponse>>
+        {
+            let stack_name = stack_name(input.stack_name().unwrap());
+            let template = self.templates.lock().unwrap().get(stack_name).cloned();
+            Ok(GetTemplateOutput::builder()
+                .set_template_body(template)
+                .build())
+        }
+
+        async fn cancel_update_stack(
+            &self,
+            input: CancelUpdateStackInput,
+        ) -> std::result::Result<
+            CancelUpdateStackOutput,
+            SdkError<CancelUpdateStackError, HttpResponse>,
+        > {
+            let stack_name = stack_name(input.stack_name().unwrap());
+            self.cancelled.lock().unwrap().push(stack_name.to_string());
+            Ok(CancelUpdateStackOutput::builder().build())
+        }
+
+        async fn continue_update_rollback(
+            &self,
+            _input: ContinueUpdateRollbackInput,
+        ) -> std::result::Result<
+            ContinueUpdateRollbackOutput,
+            SdkError<ContinueUpdateRollbackError, HttpResponse>,
+        > {
+            todo!()
+        }
     }
 
     #[tokio::test]
@@ -584,10 +2343,10 @@ mod tests {
         tracing_subscriber::fmt::init();
         use std::collections::HashSet;
 
-        let client = MockClient {};
+        let client = MockClient::default();
         let stacks = {
             let mut stacks = HashSet::new();
-            stacks.insert(String::from("SampleStack"));
+            stacks.insert(StackTarget::new(Location::default(), "SampleStack"));
             stacks
         };
         let original_stack_names = {
@@ -600,17 +2359,33 @@ mod tests {
             names: stacks,
         };
         let config = TailConfig {
-            since: Utc.timestamp(0, 0),
+            since: Utc.timestamp_opt(0, 0).unwrap(),
             stack_info: &stack_info,
             show_separators: true,
             show_notifications: true,
             show_outputs: true,
             sound: "Ping".to_string(),
+            failure_sound: "Basso".to_string(),
+            deadline: None,
+            cancel_on_timeout: false,
             show_resource_types: true,
+            show_construct_paths: false,
+            show_progress: false,
+            stuck_after: None,
+            history_path: None,
+            trace_out: None,
+            reports: Vec::new(),
+            ci: None,
+            webhook: None,
+            hooks: Hooks::default(),
+            templates: HashMap::new(),
+            should_exit_on_completion: true,
         };
         let mut writer = StubWriter::default();
 
-        let mut tail = Tail::new(config, Arc::new(client), &mut writer);
+        let mut clients = Clients::default();
+        clients.insert(Location::default(), Arc::new(client));
+        let mut tail = Tail::new(config, clients, &mut writer);
 
         tail.prefetch().await.unwrap();
 
@@ -620,4 +2395,484 @@ mod tests {
             "2020-11-17 10:38:57.149 UTC: SampleStack - test-stack | stack | UPDATE_COMPLETE\n"
         );
     }
+
+    fn test_config(stack_info: &StackInfo) -> TailConfig<'_> {
+        TailConfig {
+            since: Utc.timestamp_opt(0, 0).unwrap(),
+            stack_info,
+            show_separators: false,
+            show_notifications: false,
+            show_outputs: false,
+            sound: "Ping".to_string(),
+            failure_sound: "Basso".to_string(),
+            deadline: None,
+            cancel_on_timeout: false,
+            show_resource_types: false,
+            show_construct_paths: false,
+            show_progress: false,
+            stuck_after: None,
+            history_path: None,
+            trace_out: None,
+            reports: Vec::new(),
+            ci: None,
+            webhook: None,
+            hooks: Hooks::default(),
+            templates: HashMap::new(),
+            should_exit_on_completion: true,
+        }
+    }
+
+    fn region(name: &str) -> Location {
+        Location {
+            credentials: None,
+            region: Some(name.to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quits_once_every_target_finishes() {
+        let eu = Arc::new(MockClient::default());
+        let us = Arc::new(MockClient::default());
+        eu.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        us.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        eu.push("app", "app", "UPDATE_COMPLETE", 20);
+
+        let targets = [
+            StackTarget::new(region("eu-west-1"), "app"),
+            StackTarget::new(region("us-east-1"), "app"),
+        ];
+        let stack_info = StackInfo {
+            names: targets.iter().cloned().collect(),
+            original_names: ["app".to_string()].iter().cloned().collect(),
+        };
+        let mut clients = Clients::default();
+        clients.insert(region("eu-west-1"), eu.clone());
+        clients.insert(region("us-east-1"), us.clone());
+        let mut writer = StubWriter::default();
+        let mut tail = Tail::new(test_config(&stack_info), clients, &mut writer);
+
+        tail.prefetch().await.unwrap();
+        // the stack in eu-west-1 has finished, but not the one in us-east-1
+        assert!(!tail.poll_step().await.unwrap());
+
+        us.push("app", "app", "UPDATE_COMPLETE", 30);
+        assert!(tail.poll_step().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_quits_when_rollback_disabled() {
+        let client = Arc::new(MockClient::default());
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("app", "AppTopic", "UPDATE_FAILED", 11);
+        client.push("app", "app", "UPDATE_FAILED", 12);
+
+        let stack_info = stack_info(&["app"]);
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, test_config(&stack_info), &mut writer);
+        tail.prefetch().await.unwrap();
+        assert!(tail.poll_step().await.unwrap());
+        assert_eq!(tail.outcome(), Outcome::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_status_is_in_progress() {
+        let client = Arc::new(MockClient::default());
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("app", "app", "UPDATE_PAUSED", 11);
+
+        let stack_info = stack_info(&["app"]);
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, test_config(&stack_info), &mut writer);
+        tail.prefetch().await.unwrap();
+        assert!(!tail.poll_step().await.unwrap());
+
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert!(buf.ends_with(" UTC: app - app | UPDATE_PAUSED\n"));
+    }
+
+    // a tail of the stacks in a single location, using the given client
+    fn single_location<'a>(
+        client: &Arc<MockClient>,
+        config: TailConfig<'a>,
+        writer: &'a mut StubWriter,
+    ) -> Tail<'a, StubWriter> {
+        let mut clients = Clients::default();
+        clients.insert(Location::default(), client.clone());
+        Tail::new(config, clients, writer)
+    }
+
+    fn stack_info(names: &[&str]) -> StackInfo {
+        StackInfo {
+            names: names
+                .iter()
+                .map(|name| StackTarget::new(Location::default(), *name))
+                .collect(),
+            original_names: names.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_counts_stack_under_deployment() {
+        let client = Arc::new(MockClient::default());
+        for (stack, template) in [
+            ("app", r#"{"Resources": {"Bucket": {"Type": "AWS::S3::Bucket"}, "Network": {"Type": "AWS::CloudFormation::Stack"}}}"#),
+            ("app-network", r#"{"Resources": {"Vpc": {"Type": "AWS::EC2::VPC"}, "Subnet": {"Type": "AWS::EC2::Subnet"}}}"#),
+            ("other", r#"{"Resources": {"Queue": {"Type": "AWS::SQS::Queue"}}}"#),
+        ]
+        .iter()
+        {
+            client
+                .templates
+                .lock()
+                .unwrap()
+                .insert(stack.to_string(), template.to_string());
+        }
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("other", "other", "UPDATE_IN_PROGRESS", 11);
+        client.push_nested("app", "Network", "app-network", "UPDATE_IN_PROGRESS", 11);
+        client.push("app-network", "Vpc", "UPDATE_COMPLETE", 12);
+        client.push("app", "Bucket", "UPDATE_COMPLETE", 13);
+        client.push("other", "Queue", "UPDATE_COMPLETE", 13);
+
+        let mut stack_info = stack_info(&["app", "app-network", "other"]);
+        stack_info.original_names.remove("app-network");
+        let mut config = test_config(&stack_info);
+        config.show_progress = true;
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+        tail.prefetch().await.unwrap();
+        tail.poll_step().await.unwrap();
+
+        // the nested stack is counted by its own resources, and the other
+        // stack not at all
+        let progress = &tail.progress[&stack_id("app")];
+        assert_eq!(progress.total, Some(3));
+        assert_eq!(progress.finished(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_progress_per_stack() {
+        let client = Arc::new(MockClient::default());
+        client.templates.lock().unwrap().insert(
+            "app".to_string(),
+            r#"{"Resources": {"AppTopic": {"Type": "AWS::SNS::Topic"}}}"#.to_string(),
+        );
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("db", "db", "UPDATE_IN_PROGRESS", 11);
+        client.push("db", "DbTopic", "UPDATE_FAILED", 12);
+        client.push("app", "AppTopic", "UPDATE_COMPLETE", 13);
+        client.push("app", "app", "UPDATE_COMPLETE", 14);
+
+        let stack_info = stack_info(&["app", "db"]);
+        let mut config = test_config(&stack_info);
+        config.show_progress = true;
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+        tail.prefetch().await.unwrap();
+
+        // the stack still deploying keeps its progress and failures
+        let ids: Vec<_> = tail.progress.keys().cloned().collect();
+        assert_eq!(ids, vec![stack_id("db")]);
+        assert_eq!(tail.failures[&stack_id("db")].len(), 1);
+        assert!(!tail.failures.contains_key(&stack_id("app")));
+        // the template of db cannot be fetched to count its resources, so
+        // no bar is drawn
+        assert!(tail.progress[&stack_id("db")].count_failed);
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert!(!buf.contains("resources"));
+    }
+
+    #[tokio::test]
+    async fn test_command_output_above_progress() {
+        let client = Arc::new(MockClient::default());
+        client.templates.lock().unwrap().insert(
+            "app".to_string(),
+            r#"{"Resources": {"AppTopic": {"Type": "AWS::SNS::Topic"}}}"#.to_string(),
+        );
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+
+        let stack_info = stack_info(&["app"]);
+        let mut config = test_config(&stack_info);
+        config.show_progress = true;
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+        tail.prefetch().await.unwrap();
+        assert!(tail.poll_once().await.unwrap());
+        tail.print_command_output("cdk | deploying").await.unwrap();
+
+        // the progress line is cleared, and drawn again below the output
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        let (before, after) = buf.split_once("cdk | deploying\n").unwrap();
+        assert!(before.contains("0/1 resources"));
+        assert!(before.ends_with("\r\x1b[2K"));
+        assert!(after.starts_with("[") && after.contains("0/1 resources"));
+    }
+
+    #[tokio::test]
+    async fn test_reports_per_stack() {
+        let client = Arc::new(MockClient::default());
+        // an operation that finished before cftail started
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 1);
+        client.push("app", "AppTopic", "UPDATE_COMPLETE", 2);
+        client.push("app", "app", "UPDATE_COMPLETE", 3);
+        client.push("db", "db", "UPDATE_IN_PROGRESS", 4);
+
+        let dir = std::env::temp_dir().join(format!("cftail-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stack_info = stack_info(&["app", "db"]);
+        let mut config = test_config(&stack_info);
+        config.reports = vec![(crate::report::ReportFormat::Markdown, dir.join("report.md"))];
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+        tail.prefetch().await.unwrap();
+        assert!(!dir.join("report-app.md").exists());
+
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("app", "AppTopic", "UPDATE_COMPLETE", 12);
+        client.push("db", "DbTopic", "UPDATE_COMPLETE", 13);
+        client.push("app", "app", "UPDATE_COMPLETE", 14);
+        client.push("db", "db", "UPDATE_COMPLETE", 15);
+        // both stacks have finished, so there is nothing left to tail
+        assert!(!tail.poll_once().await.unwrap());
+
+        // each stack gets a report of its own events
+        let app = std::fs::read_to_string(dir.join("report-app.md")).unwrap();
+        let db = std::fs::read_to_string(dir.join("report-db.md")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(app.contains("AppTopic") && !app.contains("DbTopic"));
+        assert!(db.contains("DbTopic") && !db.contains("AppTopic"));
+    }
+
+    #[tokio::test]
+    async fn test_ci_group_per_stack() {
+        let client = Arc::new(MockClient::default());
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("db", "db", "UPDATE_IN_PROGRESS", 11);
+        client.push("app", "AppTopic", "UPDATE_COMPLETE", 12);
+        client.push("db", "db", "UPDATE_COMPLETE", 13);
+        client.push("app", "app", "UPDATE_COMPLETE", 14);
+
+        let stack_info = stack_info(&["app", "db"]);
+        let mut config = test_config(&stack_info);
+        config.ci = Some(crate::ci::CiPlatform::Github);
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+        tail.prefetch().await.unwrap();
+        assert!(tail.poll_step().await.unwrap());
+
+        // both deployments get a group, even though they overlap
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert_eq!(buf.matches("::group::app\n").count(), 1);
+        assert_eq!(buf.matches("::group::db\n").count(), 1);
+        assert_eq!(buf.matches("::endgroup::\n").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_warns_once_when_stuck() {
+        let client = Arc::new(MockClient::default());
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("app", "Bucket", "UPDATE_IN_PROGRESS", 12);
+
+        let stack_info = stack_info(&["app"]);
+        let mut config = test_config(&stack_info);
+        config.stuck_after = Some(chrono::Duration::seconds(60));
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+        tail.prefetch().await.unwrap();
+
+        let last_event = Utc.timestamp_opt(12, 0).unwrap();
+        tail.warn_if_stuck(last_event + chrono::Duration::seconds(59))
+            .unwrap();
+        tail.warn_if_stuck(last_event + chrono::Duration::seconds(60))
+            .unwrap();
+        // still no new events, but the quiet period was already reported
+        tail.warn_if_stuck(last_event + chrono::Duration::seconds(120))
+            .unwrap();
+
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert_eq!(buf.matches("No new events for").count(), 1);
+        assert!(buf.contains(
+            "No new events for 1m00s, still in progress: Bucket (AWS::SNS::Topic) for 1m00s\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_updates() {
+        let client = Arc::new(MockClient::default());
+        // cftail started part way through both deployments, so has seen no
+        // events for either stack
+        client.set_status("app", "UPDATE_IN_PROGRESS");
+        client.set_status("db", "CREATE_IN_PROGRESS");
+        let stack_info = stack_info(&["app", "db"]);
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, test_config(&stack_info), &mut writer);
+
+        assert!(tail.cancel_updates().await.unwrap());
+        assert_eq!(*client.cancelled.lock().unwrap(), vec!["app".to_string()]);
+
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert_eq!(
+            buf,
+            "Cancelled the update of app\nCannot cancel db while it is in CREATE_IN_PROGRESS\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_poll_stops_at_deadline() {
+        let client = Arc::new(MockClient::default());
+        client.set_status("app", "UPDATE_IN_PROGRESS");
+        let stack_info = stack_info(&["app"]);
+        let mut config = test_config(&stack_info);
+        config.deadline = Some(Utc.timestamp_opt(0, 0).unwrap());
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+
+        let mut interrupts = Interrupts::new().unwrap();
+        tail.poll(&mut interrupts).await.unwrap();
+        assert_eq!(tail.outcome(), Outcome::TimedOut);
+        // updates are only cancelled when asked to
+        assert!(client.cancelled.lock().unwrap().is_empty());
+
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert_eq!(buf, "Timed out waiting for the deployment to finish\n");
+    }
+
+    #[tokio::test]
+    async fn test_time_out_cancels_updates() {
+        let client = Arc::new(MockClient::default());
+        client.set_status("app", "UPDATE_IN_PROGRESS");
+        let stack_info = stack_info(&["app"]);
+        let mut config = test_config(&stack_info);
+        config.deadline = Some(Utc.timestamp_opt(0, 0).unwrap());
+        config.cancel_on_timeout = true;
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+
+        // keeps tailing to wait for the rollback
+        assert!(tail.poll_once().await.unwrap());
+        assert_eq!(*client.cancelled.lock().unwrap(), vec!["app".to_string()]);
+
+        client.push("app", "app", "UPDATE_ROLLBACK_IN_PROGRESS", 10);
+        client.push("app", "app", "UPDATE_ROLLBACK_COMPLETE", 20);
+        assert!(!tail.poll_once().await.unwrap());
+        assert_eq!(tail.outcome(), Outcome::TimedOut);
+
+        let buf = std::str::from_utf8(&writer.buf).unwrap();
+        assert!(buf.starts_with(
+            "Timed out waiting for the deployment to finish\nCancelled the update of app\n"
+        ));
+        assert!(buf.contains("app - app | UPDATE_ROLLBACK_COMPLETE"));
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_summary_then_cancel() {
+        let client = Arc::new(MockClient::default());
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("app", "Topic", "UPDATE_IN_PROGRESS", 15);
+        let stack_info = stack_info(&["app"]);
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, test_config(&stack_info), &mut writer);
+        tail.prefetch().await.unwrap();
+        tail.writer_mut().buf.clear();
+
+        let options = tail
+            .summarise(Utc.timestamp_opt(75, 0).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            options,
+            Options {
+                in_progress: true,
+                can_cancel: true,
+                can_continue_rollback: false,
+            }
+        );
+        assert!(tail
+            .act_on(Signal::Interrupt, Choice::CancelUpdate)
+            .await
+            .unwrap());
+        assert_eq!(*client.cancelled.lock().unwrap(), vec!["app".to_string()]);
+
+        let buf = std::str::from_utf8(&tail.writer_mut().buf).unwrap();
+        assert_eq!(
+            buf,
+            "\nInterrupted 1m05s into the deployment\nIn progress:\n  app - Topic (AWS::SNS::Topic) for 1m00s\nCancelled the update of app\n"
+        );
+
+        // detaching stops tailing, exiting as if killed by the signal
+        assert!(!tail
+            .act_on(Signal::Interrupt, Choice::Detach)
+            .await
+            .unwrap());
+        assert_eq!(tail.outcome(), Outcome::Interrupted(Signal::Interrupt));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hooks_run_once_per_operation() {
+        let dir = std::env::temp_dir();
+        let failures = dir.join(format!("cftail-failures-{}", std::process::id()));
+        let events = dir.join(format!("cftail-events-{}", std::process::id()));
+        let client = Arc::new(MockClient::default());
+        client.push("app", "app", "UPDATE_IN_PROGRESS", 10);
+        client.push("app", "Topic", "UPDATE_FAILED", 20);
+        client.push("app", "app", "UPDATE_ROLLBACK_IN_PROGRESS", 30);
+        client.push("app", "app", "UPDATE_ROLLBACK_COMPLETE", 40);
+        let stack_info = stack_info(&["app"]);
+        let mut config = test_config(&stack_info);
+        config.hooks = Hooks {
+            on_failure: Some(format!("echo \"$CFTAIL_STATUS\" >> {}", failures.display())),
+            on_event: Some(format!("echo \"$CFTAIL_STATUS\" >> {}", events.display())),
+            ..Hooks::default()
+        };
+        let mut writer = StubWriter::default();
+        let mut tail = single_location(&client, config, &mut writer);
+
+        assert!(!tail.poll_once().await.unwrap());
+        tail.finish().await;
+
+        let failures_run = std::fs::read_to_string(&failures).unwrap();
+        let events_run = std::fs::read_to_string(&events).unwrap();
+        std::fs::remove_file(&failures).unwrap();
+        std::fs::remove_file(&events).unwrap();
+        assert_eq!(failures_run, "UPDATE_ROLLBACK_COMPLETE\n");
+        assert_eq!(
+            events_run,
+            "UPDATE_IN_PROGRESS\nUPDATE_FAILED\nUPDATE_ROLLBACK_IN_PROGRESS\nUPDATE_ROLLBACK_COMPLETE\n"
+        );
+    }
+
+    #[test]
+    fn test_notices() {
+        let notice = Notice::finished(
+            "my-stack",
+            "UPDATE_COMPLETE",
+            Some(chrono::Duration::seconds(75)),
+            None,
+        );
+        assert_eq!(notice.summary, "my-stack deployed");
+        assert_eq!(notice.body, "UPDATE_COMPLETE after 1m15s");
+        assert!(!notice.failure);
+
+        let failure = Failure {
+            stack_name: "my-stack".to_string(),
+            logical_id: "Function".to_string(),
+            resource_type: "AWS::Lambda::Function".to_string(),
+            status: "UPDATE_FAILED".to_string(),
+            reason: "Invalid runtime".to_string(),
+            timestamp: Utc.timestamp_opt(0, 0).unwrap(),
+        };
+        let notice = Notice::finished(
+            "my-stack",
+            "UPDATE_ROLLBACK_COMPLETE",
+            Some(chrono::Duration::seconds(100)),
+            Some(&failure),
+        );
+        assert_eq!(notice.summary, "my-stack failed to deploy");
+        assert_eq!(
+            notice.body,
+            "UPDATE_ROLLBACK_COMPLETE after 1m40s\nFunction: Invalid runtime"
+        );
+        assert!(notice.failure);
+    }
 }
@@ -0,0 +1,333 @@
+use eyre::{Result, WrapErr};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+use yaml_rust2::{Yaml, YamlLoader};
+
+// most lines of a resource's properties to show
+const SNIPPET_LINES: usize = 8;
+
+/// CDK construct path of each resource, keyed by logical id
+pub(crate) type ConstructPaths = HashMap<String, String>;
+
+/// A CloudFormation template, in either JSON or YAML format
+///
+/// JSON templates are parsed as YAML, and short form intrinsic functions such
+/// as `!Ref` are read as their plain values.
+#[derive(Debug, Clone)]
+pub(crate) struct Template {
+    /// File the template was read from, if it is local
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) body: String,
+    document: Yaml,
+    resource_lines: HashMap<String, ResourceLines>,
+}
+
+/// Where a resource is defined, along with the start of its properties
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResourceSnippet {
+    pub(crate) line: usize,
+    /// Line numbers and contents of the properties
+    pub(crate) lines: Vec<(usize, String)>,
+}
+
+// 1-indexed lines of a resource definition and its properties
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ResourceLines {
+    definition: usize,
+    properties: Option<usize>,
+}
+
+// mapping or sequence that is being parsed
+struct Frame {
+    // keys of the mappings that contain this one
+    path: Vec<String>,
+    is_mapping: bool,
+    key: Option<String>,
+    expecting_key: bool,
+}
+
+// finds the lines of each resource from the parser events, as the loaded
+// document does not keep track of them
+#[derive(Default)]
+struct ResourceLocator {
+    frames: Vec<Frame>,
+    resources: HashMap<String, ResourceLines>,
+}
+
+impl ResourceLocator {
+    // a complete value has been read in the current container
+    fn end_value(&mut self) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.expecting_key = frame.is_mapping;
+        }
+    }
+}
+
+impl MarkedEventReceiver for ResourceLocator {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::MappingStart(..) | Event::SequenceStart(..) => {
+                let path = match self.frames.last() {
+                    Some(parent) => {
+                        let mut path = parent.path.clone();
+                        path.extend(parent.key.clone());
+                        path
+                    }
+                    None => Vec::new(),
+                };
+                self.frames.push(Frame {
+                    path,
+                    is_mapping: matches!(event, Event::MappingStart(..)),
+                    key: None,
+                    expecting_key: true,
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.frames.pop();
+                self.end_value();
+            }
+            Event::Scalar(value, ..) => {
+                let Some(frame) = self.frames.last_mut() else {
+                    return;
+                };
+                if !(frame.is_mapping && frame.expecting_key) {
+                    self.end_value();
+                    return;
+                }
+
+                let path: Vec<&str> = frame.path.iter().map(String::as_str).collect();
+                match path.as_slice() {
+                    ["Resources"] => {
+                        let lines = ResourceLines {
+                            definition: mark.line(),
+                            properties: None,
+                        };
+                        self.resources.insert(value.clone(), lines);
+                    }
+                    ["Resources", logical_id] if value == "Properties" => {
+                        if let Some(lines) = self.resources.get_mut(*logical_id) {
+                            lines.properties = Some(mark.line());
+                        }
+                    }
+                    _ => {}
+                }
+                frame.key = Some(value);
+                frame.expecting_key = false;
+            }
+            Event::Alias(_) => self.end_value(),
+            _ => {}
+        }
+    }
+}
+
+impl Template {
+    pub(crate) fn from_file(path: &Path) -> Result<Self> {
+        let body = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("reading template {}", path.display()))?;
+        let mut template =
+            Self::parse(body).wrap_err_with(|| format!("parsing template {}", path.display()))?;
+        template.path = Some(path.to_path_buf());
+        Ok(template)
+    }
+
+    pub(crate) fn parse(body: String) -> Result<Self> {
+        let document = YamlLoader::load_from_str(&body)
+            .wrap_err("invalid template")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("template is empty"))?;
+
+        let mut locator = ResourceLocator::default();
+        Parser::new_from_str(&body)
+            .load(&mut locator, false)
+            .wrap_err("invalid template")?;
+
+        Ok(Self {
+            path: None,
+            body,
+            document,
+            resource_lines: locator.resources,
+        })
+    }
+
+    /// Where a resource is defined, with the first few lines of its properties
+    pub(crate) fn resource_snippet(&self, logical_id: &str) -> Option<ResourceSnippet> {
+        let resource_lines = self.resource_lines.get(logical_id)?;
+        let mut lines = Vec::new();
+        if let Some(start) = resource_lines.properties {
+            let source: Vec<&str> = self.body.lines().collect();
+            let indent = |line: &str| line.len() - line.trim_start().len();
+            let properties = source.get(start - 1)?;
+            lines.push((start, properties.to_string()));
+            // the properties are indented further than their key
+            for (i, line) in source.iter().enumerate().skip(start) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if indent(line) <= indent(properties) || lines.len() >= SNIPPET_LINES {
+                    break;
+                }
+                lines.push((i + 1, line.to_string()));
+            }
+        }
+        Some(ResourceSnippet {
+            line: resource_lines.definition,
+            lines,
+        })
+    }
+
+    /// Names of the parameters declared by the template
+    pub(crate) fn parameter_names(&self) -> Vec<String> {
+        match self.document["Parameters"].as_hash() {
+            Some(parameters) => parameters
+                .keys()
+                .filter_map(|key| key.as_str().map(String::from))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Number of resources in the template, leaving out nested stacks, whose
+    /// resources are counted from their own templates
+    pub(crate) fn resource_count(&self) -> usize {
+        match self.document["Resources"].as_hash() {
+            Some(resources) => resources
+                .values()
+                .filter(|resource| resource["Type"].as_str() != Some("AWS::CloudFormation::Stack"))
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// CDK construct path of each resource, from its `aws:cdk:path` metadata
+    ///
+    /// The trailing `/Resource` that the CDK adds for the underlying
+    /// CloudFormation resource is left off.
+    pub(crate) fn construct_paths(&self) -> ConstructPaths {
+        let mut paths = HashMap::new();
+        if let Some(resources) = self.document["Resources"].as_hash() {
+            for (logical_id, resource) in resources {
+                let (Some(logical_id), Some(path)) = (
+                    logical_id.as_str(),
+                    resource["Metadata"]["aws:cdk:path"].as_str(),
+                ) else {
+                    continue;
+                };
+                let path = path.strip_suffix("/Resource").unwrap_or(path);
+                paths.insert(logical_id.to_string(), path.to_string());
+            }
+        }
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameter_names() {
+        let template = Template::parse(
+            r#"
+Parameters:
+  Environment:
+    Type: String
+  BucketName:
+    Type: String
+Resources:
+  Bucket:
+    Type: AWS::S3::Bucket
+    Properties:
+      BucketName: !Sub "${Environment}-${BucketName}"
+"#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            template.parameter_names(),
+            vec!["Environment", "BucketName"]
+        );
+
+        let template = Template::parse(r#"{"Resources": {}}"#.to_string()).unwrap();
+        assert!(template.parameter_names().is_empty());
+    }
+
+    #[test]
+    fn test_resource_count() {
+        let template = Template::parse(
+            r#"
+Resources:
+  Bucket:
+    Type: AWS::S3::Bucket
+  Network:
+    Type: AWS::CloudFormation::Stack
+    Properties:
+      TemplateURL: https://example.com/network.yaml
+"#
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(template.resource_count(), 1);
+    }
+
+    #[test]
+    fn test_resource_snippet() {
+        let template = Template::parse(
+            r#"Resources:
+  Queue:
+    Type: AWS::SQS::Queue
+  Bucket:
+    Type: AWS::S3::Bucket
+    Properties:
+      BucketName: !Sub "${AWS::StackName}-bucket"
+      Tags:
+        - Key: queue
+          Value: !GetAtt Queue.Arn
+Outputs:
+  Bucket:
+    Value: !Ref Bucket
+"#
+            .to_string(),
+        )
+        .unwrap();
+
+        let snippet = template.resource_snippet("Bucket").unwrap();
+        assert_eq!(snippet.line, 4);
+        let numbers: Vec<usize> = snippet.lines.iter().map(|(n, _)| *n).collect();
+        assert_eq!(numbers, vec![6, 7, 8, 9, 10]);
+
+        let snippet = template.resource_snippet("Queue").unwrap();
+        assert_eq!(snippet.line, 2);
+        assert!(snippet.lines.is_empty());
+
+        let template = Template::parse(
+            "{\n  \"Resources\": {\n    \"Topic\": {\"Type\": \"AWS::SNS::Topic\"}\n  }\n}"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(template.resource_snippet("Topic").unwrap().line, 3);
+        assert_eq!(template.resource_snippet("Missing"), None);
+    }
+
+    #[test]
+    fn test_construct_paths() {
+        let template = Template::parse(
+            r#"{
+                "Resources": {
+                    "MyBucketF68F3FF0": {
+                        "Type": "AWS::S3::Bucket",
+                        "Metadata": {"aws:cdk:path": "App/MyBucket/Resource"}
+                    },
+                    "Plain": {"Type": "AWS::SNS::Topic"}
+                }
+            }"#
+            .to_string(),
+        )
+        .unwrap();
+        let paths = template.construct_paths();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths["MyBucketF68F3FF0"], "App/MyBucket");
+    }
+}
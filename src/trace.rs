@@ -0,0 +1,238 @@
+use chrono::{DateTime, Utc};
+use eyre::{Result, WrapErr};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// resource that is part way through an operation
+#[derive(Debug, Clone)]
+struct OpenSpan {
+    resource_type: String,
+    started: DateTime<Utc>,
+}
+
+// finished operation on a resource
+#[derive(Debug, Clone)]
+struct Span {
+    pid: usize,
+    tid: usize,
+    logical_id: String,
+    resource_type: String,
+    status: String,
+    started: DateTime<Utc>,
+    finished: DateTime<Utc>,
+}
+
+// stack shown as its own process, with a thread for each resource
+#[derive(Debug, Clone)]
+struct Track {
+    pid: usize,
+    stack_name: String,
+    threads: HashMap<String, usize>,
+}
+
+/// Timeline of a deployment in the Chrome Trace Event Format, which can be
+/// opened in `chrome://tracing` or Perfetto
+///
+/// Each stack, including nested stacks, is a process and each of its
+/// resources a thread, with a slice from when the resource started changing
+/// until it reached a terminal status.
+#[derive(Debug)]
+pub(crate) struct Trace {
+    path: PathBuf,
+    dirty: bool,
+    // keyed by stack id
+    tracks: HashMap<String, Track>,
+    open: HashMap<(String, String), OpenSpan>,
+    spans: Vec<Span>,
+}
+
+impl Trace {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            dirty: false,
+            tracks: HashMap::new(),
+            open: HashMap::new(),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Record an event on a resource, or on the stack itself
+    pub(crate) fn record(
+        &mut self,
+        stack_id: &str,
+        stack_name: &str,
+        logical_id: &str,
+        resource_type: &str,
+        status: &str,
+        timestamp: DateTime<Utc>,
+    ) {
+        // tracks are ordered by when their stack, and each resource, first started
+        let next_pid = self.tracks.len() + 1;
+        let track = self
+            .tracks
+            .entry(stack_id.to_string())
+            .or_insert_with(|| Track {
+                pid: next_pid,
+                stack_name: stack_name.to_string(),
+                threads: HashMap::new(),
+            });
+        let next_tid = track.threads.len() + 1;
+        let tid = *track
+            .threads
+            .entry(logical_id.to_string())
+            .or_insert(next_tid);
+
+        let key = (stack_id.to_string(), logical_id.to_string());
+        if status.ends_with("_IN_PROGRESS") {
+            self.open.entry(key).or_insert_with(|| OpenSpan {
+                resource_type: resource_type.to_string(),
+                started: timestamp,
+            });
+            return;
+        }
+
+        let Some(open) = self.open.remove(&key) else {
+            return;
+        };
+        self.spans.push(Span {
+            pid: track.pid,
+            tid,
+            logical_id: logical_id.to_string(),
+            resource_type: open.resource_type,
+            status: status.to_string(),
+            started: open.started,
+            finished: timestamp,
+        });
+        self.dirty = true;
+    }
+
+    fn to_json(&self) -> Value {
+        let mut events = Vec::new();
+        for track in self.tracks.values() {
+            events.push(json!({
+                "name": "process_name",
+                "ph": "M",
+                "pid": track.pid,
+                "args": {"name": track.stack_name},
+            }));
+            events.push(json!({
+                "name": "process_sort_index",
+                "ph": "M",
+                "pid": track.pid,
+                "args": {"sort_index": track.pid},
+            }));
+            for (logical_id, tid) in &track.threads {
+                events.push(json!({
+                    "name": "thread_name",
+                    "ph": "M",
+                    "pid": track.pid,
+                    "tid": tid,
+                    "args": {"name": logical_id},
+                }));
+                events.push(json!({
+                    "name": "thread_sort_index",
+                    "ph": "M",
+                    "pid": track.pid,
+                    "tid": tid,
+                    "args": {"sort_index": tid},
+                }));
+            }
+        }
+        for span in &self.spans {
+            events.push(json!({
+                "name": span.logical_id,
+                "cat": span.resource_type,
+                "ph": "X",
+                "ts": span.started.timestamp_micros(),
+                "dur": (span.finished - span.started).num_microseconds().unwrap_or(0),
+                "pid": span.pid,
+                "tid": span.tid,
+                "args": {"type": span.resource_type, "status": span.status},
+            }));
+        }
+        json!({"traceEvents": events, "displayTimeUnit": "ms"})
+    }
+
+    /// Write the trace to its file, if anything has finished since it was
+    /// last written
+    pub(crate) fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let contents = serde_json::to_string(&self.to_json()).wrap_err("serializing trace")?;
+        std::fs::write(&self.path, contents)
+            .wrap_err_with(|| format!("writing trace to {}", self.path.display()))?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_trace() {
+        let start = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+        let mut trace = Trace::new(PathBuf::from("trace.json"));
+        let stack_type = "AWS::CloudFormation::Stack";
+        trace.record(
+            "parent-id",
+            "parent",
+            "parent",
+            stack_type,
+            "CREATE_IN_PROGRESS",
+            start,
+        );
+        trace.record(
+            "parent-id",
+            "parent",
+            "Nested",
+            stack_type,
+            "CREATE_IN_PROGRESS",
+            start,
+        );
+        trace.record(
+            "nested-id",
+            "parent-Nested",
+            "Queue",
+            "AWS::SQS::Queue",
+            "CREATE_IN_PROGRESS",
+            start + Duration::seconds(5),
+        );
+        trace.record(
+            "nested-id",
+            "parent-Nested",
+            "Queue",
+            "AWS::SQS::Queue",
+            "CREATE_COMPLETE",
+            start + Duration::seconds(65),
+        );
+        trace.record(
+            "parent-id",
+            "parent",
+            "Nested",
+            stack_type,
+            "CREATE_COMPLETE",
+            start + Duration::seconds(70),
+        );
+        assert!(trace.dirty);
+
+        let json = trace.to_json();
+        let events = json["traceEvents"].as_array().unwrap();
+        let spans: Vec<_> = events.iter().filter(|e| e["ph"] == "X").collect();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0]["name"], "Queue");
+        assert_eq!(spans[0]["ts"], start.timestamp_micros() + 5_000_000);
+        assert_eq!(spans[0]["dur"], 60_000_000);
+        // the nested stack has its own track
+        assert_eq!((&spans[0]["pid"], &spans[0]["tid"]), (&json!(2), &json!(1)));
+        assert_eq!((&spans[1]["pid"], &spans[1]["tid"]), (&json!(1), &json!(2)));
+        assert!(events
+            .iter()
+            .any(|e| e["name"] == "process_name" && e["args"]["name"] == "parent-Nested"));
+    }
+}
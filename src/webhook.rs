@@ -0,0 +1,202 @@
+use backoff::ExponentialBackoff;
+use eyre::{Result, WrapErr};
+use minijinja::Environment;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::progress::format_duration;
+use crate::report::Report;
+
+// give up on a webhook after retrying for this long
+const MAX_RETRY_DURATION: Duration = Duration::from_secs(60);
+
+const TEMPLATE_NAME: &str = "webhook";
+
+/// Why a webhook was sent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum WebhookEvent {
+    /// The stack finished deploying
+    Complete,
+    /// The stack rolled back, or failed to
+    Failure,
+    /// The stack started rolling back
+    RollbackStarted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FailedResource {
+    logical_id: String,
+    resource_type: String,
+    status: String,
+    reason: String,
+}
+
+/// Body of a webhook, and the context that templates are rendered with
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Payload {
+    pub(crate) event: WebhookEvent,
+    pub(crate) stack: String,
+    pub(crate) status: String,
+    started: String,
+    finished: String,
+    pub(crate) duration_seconds: i64,
+    /// Duration formatted for people, e.g. `3m12s`
+    duration: String,
+    failures: Vec<FailedResource>,
+    outputs: BTreeMap<String, String>,
+}
+
+impl Payload {
+    pub(crate) fn new(event: WebhookEvent, report: &Report) -> Self {
+        Self {
+            event,
+            stack: report.stack_name.clone(),
+            status: report.status.clone(),
+            started: report.started.to_rfc3339(),
+            finished: report.finished.to_rfc3339(),
+            duration_seconds: report.duration().num_seconds(),
+            duration: format_duration(report.duration()),
+            failures: report
+                .failures()
+                .into_iter()
+                .filter(|f| !f.is_cancelled())
+                .map(|f| FailedResource {
+                    logical_id: f.logical_id,
+                    resource_type: f.resource_type,
+                    status: f.status,
+                    reason: f.reason,
+                })
+                .collect(),
+            outputs: report
+                .outputs
+                .iter()
+                .filter_map(|o| Some((o.output_key()?.to_string(), o.output_value()?.to_string())))
+                .collect(),
+        }
+    }
+}
+
+/// URLs to post the result of each deployment to
+#[derive(Debug, Clone)]
+pub(crate) struct Webhook {
+    urls: Vec<String>,
+    // minijinja template for the body, which is the payload as JSON otherwise
+    template: Option<String>,
+    /// Whether to also post when a stack starts rolling back
+    pub(crate) on_rollback: bool,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub(crate) fn new(
+        urls: Vec<String>,
+        template_path: Option<&Path>,
+        on_rollback: bool,
+    ) -> Result<Self> {
+        let template = match template_path {
+            Some(path) => {
+                let template = std::fs::read_to_string(path)
+                    .wrap_err_with(|| format!("reading webhook template {}", path.display()))?;
+                // check the template before anything is deployed
+                Environment::new()
+                    .template_from_str(&template)
+                    .wrap_err_with(|| format!("parsing webhook template {}", path.display()))?;
+                Some(template)
+            }
+            None => None,
+        };
+        Ok(Self {
+            urls,
+            template,
+            on_rollback,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    fn render(&self, payload: &Payload) -> Result<String> {
+        let Some(template) = &self.template else {
+            return serde_json::to_string(payload).wrap_err("serializing webhook payload");
+        };
+        let mut env = Environment::new();
+        env.add_template(TEMPLATE_NAME, template)
+            .wrap_err("parsing webhook template")?;
+        let body = env
+            .get_template(TEMPLATE_NAME)
+            .and_then(|t| t.render(payload))
+            .wrap_err("rendering webhook template")?;
+        serde_json::from_str::<serde_json::Value>(&body)
+            .wrap_err_with(|| format!("webhook template did not render valid JSON: {}", body))?;
+        Ok(body)
+    }
+
+    /// Post the payload to each URL, retrying with backoff when the request
+    /// fails or the server is unavailable
+    pub(crate) async fn send(&self, payload: &Payload) -> Result<()> {
+        let body = self.render(payload)?;
+        for url in &self.urls {
+            tracing::debug!(%url, event = ?payload.event, "sending webhook");
+            let backoff = ExponentialBackoff {
+                max_elapsed_time: Some(MAX_RETRY_DURATION),
+                ..Default::default()
+            };
+            backoff::future::retry(backoff, || async {
+                let res = self
+                    .client
+                    .post(url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+                    .send()
+                    .await
+                    .map_err(|e| backoff::Error::transient(eyre::Report::new(e)))?;
+                let status = res.status();
+                if status.is_success() {
+                    return Ok(());
+                }
+                let err = eyre::eyre!("webhook {} responded with {}", url, status);
+                if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                    Err(backoff::Error::transient(err))
+                } else {
+                    Err(backoff::Error::permanent(err))
+                }
+            })
+            .await
+            .wrap_err_with(|| format!("sending webhook to {}", url))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::tests::failed_update;
+
+    #[test]
+    fn test_render() {
+        let payload = Payload::new(WebhookEvent::Failure, &failed_update());
+        let webhook = Webhook::new(Vec::new(), None, false).unwrap();
+        let body: serde_json::Value =
+            serde_json::from_str(&webhook.render(&payload).unwrap()).unwrap();
+        assert_eq!(body["event"], "failure");
+        assert_eq!(body["duration"], "1m40s");
+        // cancelled resources are not the cause of the failure
+        assert_eq!(body["failures"].as_array().unwrap().len(), 1);
+        assert_eq!(body["outputs"]["Url"], "https://example.com");
+
+        let webhook = Webhook {
+            template: Some(
+                r#"{"text": {{ (stack ~ " " ~ status ~ ": " ~ failures[0].reason) | tojson }}}"#
+                    .to_string(),
+            ),
+            ..webhook
+        };
+        assert_eq!(
+            webhook.render(&payload).unwrap(),
+            r#"{"text": "my-stack UPDATE_ROLLBACK_COMPLETE: Invalid runtime"}"#
+        );
+    }
+}